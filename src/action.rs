@@ -1,9 +1,21 @@
+use std::path::PathBuf;
+
 use strum::Display;
 
+use crate::components::editor::Edit;
+use crate::external_editor::NoteFields;
+use crate::keymap::Command;
+use crate::models::operation::Operation;
+
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub enum Screen {
     Home,
-    Practice(uuid::Uuid),
+    /// A deck to practice, limited to the notes with a tag when there is one.
+    Practice(uuid::Uuid, Option<String>),
+    Profiles,
+    Browser,
+    /// Statistics of a deck and its subdecks, or of the whole collection.
+    Stats(Option<uuid::Uuid>),
 }
 #[derive(Debug, Clone, PartialEq, Eq, Display)]
 pub enum Action {
@@ -17,17 +29,41 @@ pub enum Action {
     Error(String),
     Help,
     Char(char),
+    /// Text pasted in one go, with line breaks as `\n`.
+    Paste(String),
     Esc,
     Enter,
     Tab,
     Backspace,
     CtrlSpace,
-    Up,
-    Down,
-    Right,
-    Left,
+    Edit(Edit),
     Space,
     Save,
     Load,
+    Command(Command),
+    Commit(Operation),
+    Undo,
+    Redo,
+    RestoreBackup(PathBuf),
+    OpenProfile(String),
+    /// Puts notes cut in the browser on the home screen's clipboard, to be pasted into a deck.
+    CutNotes(Vec<uuid::Uuid>),
+    /// Hands the terminal to the user's editor to edit a note's fields.
+    EditExternally(NoteFields),
+    /// The fields saved in the editor, or why they could not be used.
+    EditedExternally(Result<NoteFields, String>),
     Screen(Screen),
 }
+
+impl From<Command> for Action {
+    /// The action a command runs, whether bound to a key or clicked.
+    fn from(command: Command) -> Self {
+        match command {
+            Command::Quit => Action::Quit,
+            Command::Undo => Action::Undo,
+            Command::Redo => Action::Redo,
+            Command::Help => Action::Help,
+            command => Action::Command(command),
+        }
+    }
+}
@@ -8,7 +8,7 @@ use std::{fs::File, path::PathBuf};
 use tempfile::tempdir;
 use zip::ZipArchive;
 
-use crate::models::{deck::Deck, note::Note};
+use crate::models::{deck::Deck, note::Note, tag::parse_tags};
 
 pub fn load_from_anki_package(path: PathBuf) -> Deck {
     let file = File::open(&path).unwrap();
@@ -24,22 +24,24 @@ pub fn load_from_anki_package(path: PathBuf) -> Deck {
     }
 
     let conn = rusqlite::Connection::open(&collection_path).unwrap();
-    let mut stmt = conn.prepare("SELECT flds FROM notes LIMIT 200;").unwrap();
+    let mut stmt = conn.prepare("SELECT flds, tags FROM notes LIMIT 200;").unwrap();
     let rows = stmt
         .query_map([], |row| {
             let flds: String = row.get(0)?;
-            Ok(flds)
+            let tags: String = row.get(1)?;
+            Ok((flds, tags))
         })
         .unwrap();
 
     let notes: Vec<Note> = rows
         .into_iter()
         .flatten()
-        .map(|flds| {
+        .map(|(flds, tags)| {
             let fields: Vec<_> = flds.split('\x1f').collect();
             let front = *fields.first().unwrap_or(&"");
             let back = *fields.get(1).unwrap_or(&"");
-            Note::new(front.into(), back.into(), crate::models::note::NoteType::Basic)
+            // Anki stores tags separated by spaces, with `::` between levels like ours.
+            Note { tags: parse_tags(&tags), ..Note::new(front.into(), back.into(), crate::models::note::NoteType::Basic) }
         })
         .collect();
 
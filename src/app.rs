@@ -5,8 +5,12 @@ use tokio::sync::mpsc;
 use tracing::{debug, info};
 
 use crate::{
-    action::Action,
+    action::{Action, Screen},
+    cli::Cli,
     components::base::Base,
+    config::Config,
+    external_editor::{self, NoteFields},
+    profile::{DEFAULT_PROFILE, Profile, Profiles},
     tui::{Event, Tui},
 };
 
@@ -22,12 +26,30 @@ pub struct App {
 }
 
 impl App {
-    pub fn new() -> Result<Self> {
+    /// Opens the profile chosen on the command line, or the last used one, in the data directory of
+    /// the user's `config`.
+    pub fn new(cli: Cli, config: Config) -> Result<Self> {
         let (action_tx, action_rx) = mpsc::unbounded_channel();
+        let profiles = Profiles::new(config.app.data_dir());
+        let (profile, screen) = match (cli.collection, cli.profile) {
+            (Some(path), _) => (Profile::for_collection(&path), Screen::Home),
+            (None, Some(name)) => {
+                let profile = profiles.get_or_create(&name)?;
+                profiles.set_last_used(&name)?;
+                (profile, Screen::Home)
+            }
+            (None, None) => {
+                let profile = profiles.get(&profiles.last_used().unwrap_or_else(|| DEFAULT_PROFILE.to_string()));
+                // Only ask which profile to use once there is more than one to choose from.
+                let screen = if profiles.list()?.len() > 1 { Screen::Profiles } else { Screen::Home };
+                (profile, screen)
+            }
+        };
+        let config = Config::for_profile(&profile)?;
         Ok(Self {
-            tick_rate: 60.into(),
-            frame_rate: 60.into(),
-            app: Base::new(action_tx.clone()),
+            tick_rate: config.app.tick_rate,
+            frame_rate: config.app.frame_rate,
+            app: Base::new(action_tx.clone(), profiles, profile, config, screen, cli.ignore_lock)?,
             should_quit: false,
             should_suspend: false,
             last_tick_key_events: Vec::new(),
@@ -37,7 +59,7 @@ impl App {
     }
 
     pub async fn run(&mut self) -> Result<()> {
-        let mut tui = Tui::new()?.mouse(true).tick_rate(self.tick_rate).frame_rate(self.frame_rate);
+        let mut tui = Tui::new()?.mouse(true).paste(true).tick_rate(self.tick_rate).frame_rate(self.frame_rate);
         tui.enter()?;
         let action_tx = self.action_tx.clone();
         loop {
@@ -68,6 +90,8 @@ impl App {
             Event::Render => action_tx.send(Action::Render)?,
             Event::Resize(x, y) => action_tx.send(Action::Resize(x, y))?,
             Event::Key(key) => self.handle_key_event(key)?,
+            // Terminals send line breaks in pasted text as carriage returns.
+            Event::Paste(ref text) => action_tx.send(Action::Paste(text.replace("\r\n", "\n").replace('\r', "\n")))?,
             _ => {}
         }
 
@@ -109,6 +133,7 @@ impl App {
                 Action::ClearScreen => tui.terminal.clear()?,
                 Action::Resize(w, h) => self.handle_resize(tui, w, h)?,
                 Action::Render => self.render(tui)?,
+                Action::EditExternally(ref fields) => self.edit_externally(tui, fields)?,
                 _ => {}
             }
 
@@ -119,6 +144,16 @@ impl App {
         Ok(())
     }
 
+    /// Leaves the terminal to the external editor while it runs, then takes it back.
+    fn edit_externally(&mut self, tui: &mut Tui, fields: &NoteFields) -> Result<()> {
+        tui.exit()?;
+        let result = external_editor::edit(fields).map_err(|err| format!("{err:#}"));
+        tui.resume()?;
+        tui.terminal.clear()?;
+        self.action_tx.send(Action::EditedExternally(result))?;
+        Ok(())
+    }
+
     fn handle_resize(&mut self, tui: &mut Tui, w: u16, h: u16) -> Result<()> {
         tui.resize(Rect::new(0, 0, w, h))?;
         self.render(tui)?;
@@ -0,0 +1,146 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::Result;
+
+use crate::storage::Storage;
+use crate::time::{self, DAY};
+
+/// Number of most recent backups that are always kept.
+pub const KEEP_RECENT: usize = 10;
+/// Number of days for which the newest backup of each day is kept.
+pub const KEEP_DAILY: i64 = 30;
+
+const PREFIX: &str = "collection-";
+const EXTENSION: &str = "sqlite";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub created: i64,
+    pub size: u64,
+}
+
+impl Backup {
+    pub fn seconds_ago(&self) -> i64 {
+        time::now() - self.created
+    }
+}
+
+/// Copies the collection into a new timestamped backup in `dir`.
+pub fn create(storage: &Storage, dir: &Path) -> Result<Backup> {
+    fs::create_dir_all(dir)?;
+    let created = time::now();
+    let path = dir.join(format!("{PREFIX}{created}.{EXTENSION}"));
+    if !path.exists() {
+        // Copy to a temporary name first so an interrupted backup is never listed.
+        let tmp = path.with_extension("tmp");
+        if tmp.exists() {
+            fs::remove_file(&tmp)?;
+        }
+        storage.copy_to(&tmp)?;
+        fs::rename(&tmp, &path)?;
+    }
+    Ok(Backup { size: fs::metadata(&path)?.len(), path, created })
+}
+
+/// Lists the backups in `dir`, newest first.
+pub fn list(dir: &Path) -> Result<Vec<Backup>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|extension| extension != EXTENSION) {
+            continue;
+        }
+        let created = path.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| stem.strip_prefix(PREFIX)).and_then(|time| time.parse().ok());
+        if let Some(created) = created {
+            backups.push(Backup { size: fs::metadata(&path)?.len(), path, created });
+        }
+    }
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.created));
+    Ok(backups)
+}
+
+/// Deletes the backups in `dir` that are neither among the [`KEEP_RECENT`] newest nor the newest
+/// of one of the last [`KEEP_DAILY`] days.
+pub fn prune(dir: &Path, now: i64) -> Result<()> {
+    for backup in expired(&list(dir)?, now) {
+        fs::remove_file(&backup.path)?;
+    }
+    Ok(())
+}
+
+/// `backups` must be sorted newest first.
+fn expired(backups: &[Backup], now: i64) -> Vec<&Backup> {
+    let mut days = HashSet::new();
+    backups
+        .iter()
+        .enumerate()
+        .filter(|(i, backup)| {
+            let day = backup.created.div_euclid(DAY);
+            let is_daily = now.div_euclid(DAY) - day < KEEP_DAILY && days.insert(day);
+            *i >= KEEP_RECENT && !is_daily
+        })
+        .map(|(_, backup)| backup)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{collection::Collection, deck::Deck};
+    use crate::time::HOUR;
+
+    fn backup(created: i64) -> Backup {
+        Backup { path: PathBuf::from(format!("{PREFIX}{created}.{EXTENSION}")), created, size: 0 }
+    }
+
+    #[test]
+    fn test_expired_keeps_recent_and_daily() {
+        let now = 100 * DAY + 23 * HOUR;
+        // Hourly backups over the last three days, newest first.
+        let backups: Vec<Backup> = (0..72).map(|i| backup(now - i * HOUR)).collect();
+
+        let expired = expired(&backups, now);
+
+        // The newest backup of today is among the recent ones, the other two days add one each.
+        assert_eq!(expired.len(), 72 - KEEP_RECENT - 2);
+        assert!(backups[..KEEP_RECENT].iter().all(|backup| !expired.contains(&backup)));
+        for day in [100, 99, 98] {
+            let newest = backups.iter().find(|backup| backup.created.div_euclid(DAY) == day).unwrap();
+            assert!(!expired.contains(&newest));
+        }
+    }
+
+    #[test]
+    fn test_expired_drops_old_days() {
+        let now = 100 * DAY;
+        let backups: Vec<Backup> = (0..KEEP_DAILY + 20).map(|i| backup(now - i * DAY)).collect();
+
+        let expired = expired(&backups, now);
+
+        assert_eq!(expired.len(), 20);
+        assert!(expired.iter().all(|backup| now - backup.created >= KEEP_DAILY * DAY));
+    }
+
+    #[test]
+    fn test_create_and_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut storage = Storage::open(&dir.path().join("collection.sqlite")).unwrap();
+        let mut collection = Collection::new();
+        collection.add_deck(Deck::new("Spanish".to_string()));
+        storage.save_all(&collection).unwrap();
+
+        let created = create(&storage, &dir.path().join("backups")).unwrap();
+        fs::write(dir.path().join("backups").join("notes.txt"), "").unwrap();
+        let backups = list(&dir.path().join("backups")).unwrap();
+
+        assert_eq!(backups, vec![created.clone()]);
+        let restored = Storage::load_copy(&created.path).unwrap();
+        assert!(restored == collection);
+    }
+}
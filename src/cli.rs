@@ -0,0 +1,19 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about)]
+pub struct Cli {
+    /// Open the collection database at this path instead of a profile's collection
+    #[arg(short, long, value_name = "PATH", conflicts_with = "profile")]
+    pub collection: Option<PathBuf>,
+
+    /// Open the named profile, creating it if it does not exist yet
+    #[arg(short, long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Open the collection even if another running instance holds its lock
+    #[arg(long)]
+    pub ignore_lock: bool,
+}
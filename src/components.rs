@@ -1,6 +1,12 @@
 pub mod base;
+pub mod browser_screen;
+pub mod conflict;
+pub mod editor;
+pub mod help;
 
 pub mod home_screen;
 pub mod practice_screen;
+pub mod profile_screen;
+pub mod stats_screen;
 pub mod title;
 pub mod utils;
@@ -1,53 +1,241 @@
-use color_eyre::eyre::Result;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use color_eyre::eyre::{Result, WrapErr};
 use crossterm::event;
 use tokio::sync::mpsc::UnboundedSender;
+use tracing::error;
 
 use crate::action::Screen;
+use crate::components::editor::{Edit, Movement};
+use crate::config::Config;
+use crate::keymap::{Command, Hint, Key, KeyContext, Resolution};
+use crate::models::history::History;
+use crate::models::operation::Operation;
+use crate::profile::{Profile, Profiles};
+use crate::storage::{self, Storage};
 use crate::{action::Action, models::collection::Collection};
+use crate::{backup, time};
 
+use super::browser_screen::BrowserScreen;
 use super::home_screen::HomeScreen;
 use super::practice_screen::PracticeScreen;
-use super::utils;
+use super::profile_screen::ProfileScreen;
+use super::stats_screen::StatsScreen;
+use super::{conflict, help};
 
 pub struct Base {
-    _tx: UnboundedSender<Action>,
+    tx: UnboundedSender<Action>,
     home: HomeScreen,
     practice: PracticeScreen,
+    profile_screen: ProfileScreen,
+    browser: BrowserScreen,
+    stats: StatsScreen,
     screen: Screen,
+    profiles: Profiles,
+    profile: Profile,
+    config: Config,
     collection: Collection,
+    history: History,
+    storage: Storage,
+    ignore_lock: bool,
+    /// Keys pressed so far of a sequence like `gg`.
+    pending_keys: Vec<Key>,
+    /// How far the help is scrolled down, while it is open.
+    help_scroll: Option<usize>,
+    /// Set once the collection was found to be modified on disk, until the user picks what to do.
+    changed_on_disk: bool,
+    /// The write that found the collection changed on disk, held back until the user picks what to do.
+    held_write: Option<Action>,
+    last_change_check: Instant,
 }
 
+const CHANGE_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
 impl Base {
-    pub fn new(tx: UnboundedSender<Action>) -> Self {
-        let tx_clone = tx.clone();
-        Self {
-            _tx: tx,
-            home: HomeScreen::new(tx_clone.clone()),
-            screen: Screen::Home,
-            practice: PracticeScreen::default(),
-            collection: Collection::load_from_file(utils::save_file_location()),
+    pub fn new(tx: UnboundedSender<Action>, profiles: Profiles, profile: Profile, config: Config, screen: Screen, ignore_lock: bool) -> Result<Self> {
+        let (storage, collection) = open_profile(&profile, ignore_lock)?;
+        tx.send(Action::Screen(screen.clone()))?;
+        Ok(Self {
+            home: HomeScreen::new(tx.clone(), profile.backup_dir(), &config),
+            tx,
+            practice: PracticeScreen::new(&config),
+            profile_screen: ProfileScreen::new(profiles.clone(), &config),
+            browser: BrowserScreen::new(&config),
+            stats: StatsScreen::new(&config),
+            screen,
+            profiles,
+            profile,
+            config,
+            collection,
+            history: History::new(),
+            storage,
+            ignore_lock,
+            pending_keys: Vec::new(),
+            help_scroll: None,
+            changed_on_disk: false,
+            held_write: None,
+            last_change_check: Instant::now(),
+        })
+    }
+
+    fn switch_profile(&mut self, name: &str) -> Result<()> {
+        let profile = self.profiles.get(name);
+        let config = Config::for_profile(&profile)?;
+        let (storage, collection) = open_profile(&profile, self.ignore_lock)?;
+        self.profiles.set_last_used(name)?;
+        self.home = HomeScreen::new(self.tx.clone(), profile.backup_dir(), &config);
+        self.practice = PracticeScreen::new(&config);
+        self.profile_screen = ProfileScreen::new(self.profiles.clone(), &config);
+        self.browser = BrowserScreen::new(&config);
+        self.stats = StatsScreen::new(&config);
+        self.profile = profile;
+        self.config = config;
+        self.storage = storage;
+        self.collection = collection;
+        self.history = History::new();
+        Ok(())
+    }
+
+    /// Replaces the collection with the backup at `path`, backing up the current state first.
+    fn restore_backup(&mut self, path: &Path) -> Result<()> {
+        backup::create(&self.storage, &self.profile.backup_dir())?;
+        let collection = Storage::load_copy(path)?;
+        self.storage.save_all(&collection)?;
+        self.collection = collection;
+        self.history = History::new();
+        Ok(())
+    }
+
+    fn check_for_changes(&mut self, force: bool) {
+        if self.changed_on_disk || (!force && self.last_change_check.elapsed() < CHANGE_CHECK_INTERVAL) {
+            return;
+        }
+        self.last_change_check = Instant::now();
+        match self.storage.changed_externally() {
+            Ok(changed) => self.changed_on_disk = changed,
+            Err(err) => error!("Failed to check collection for changes: {err}"),
+        }
+    }
+
+    /// Handles the choice after the collection changed on disk; every other action waits until then.
+    /// The write that found the change is sent again afterwards, unless reloading dropped it.
+    fn resolve_conflict(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::Command(Command::Reload) => {
+                self.collection = self.storage.load()?;
+                self.history = History::new();
+                self.home = HomeScreen::new(self.tx.clone(), self.profile.backup_dir(), &self.config);
+                self.changed_on_disk = false;
+                if let Some(held) = self.held_write.take().and_then(kept_after_reload) {
+                    self.tx.send(held)?;
+                }
+                Ok(Some(Action::Screen(Screen::Home)))
+            }
+            Action::Command(Command::Overwrite) => {
+                self.storage.save_all(&self.collection)?;
+                self.storage.acknowledge_external_changes()?;
+                self.changed_on_disk = false;
+                if let Some(held) = self.held_write.take() {
+                    self.tx.send(held)?;
+                }
+                Ok(None)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// The commands the current screen offers, which the help lists.
+    fn hints(&self) -> Option<(KeyContext, Vec<Hint>)> {
+        match self.screen {
+            Screen::Home => self.home.hints(),
+            Screen::Practice(..) => Some(self.practice.hints()),
+            Screen::Profiles => self.profile_screen.hints(),
+            Screen::Browser => self.browser.hints(),
+            Screen::Stats(_) => Some(self.stats.hints()),
         }
     }
+
+    fn update_help(&mut self, scroll: usize, action: Action) {
+        self.help_scroll = match action {
+            Action::Command(Command::Up) => Some(scroll.saturating_sub(1)),
+            Action::Command(Command::Down) => Some(scroll + 1),
+            Action::Command(Command::Top) => Some(0),
+            // Clamped to the last row when drawn.
+            Action::Command(Command::Bottom) => Some(usize::MAX),
+            Action::Command(Command::Back) | Action::Help => None,
+            _ => Some(scroll),
+        };
+    }
+
+    fn persist(&mut self, operation: &Operation) {
+        if let Err(err) = self.storage.persist(&self.collection, operation) {
+            error!("Failed to write operation, rewriting collection: {err}");
+            let _ = self.tx.send(Action::Save);
+        }
+    }
+}
+
+/// The held write that is still made after reloading. A change, undo or redo was worked out against
+/// the collection that was replaced, so it could overwrite the other changes and is dropped along with
+/// the history; restoring a backup does not depend on what was loaded.
+fn kept_after_reload(held: Action) -> Option<Action> {
+    match held {
+        Action::RestoreBackup(_) => Some(held),
+        _ => None,
+    }
 }
 
 impl Base {
+    /// Where keys are looked up in the keymap, or `None` while text is being typed.
+    fn key_context(&self) -> Option<KeyContext> {
+        if self.changed_on_disk {
+            return Some(KeyContext::Conflict);
+        }
+        if self.help_scroll.is_some() {
+            return Some(KeyContext::Help);
+        }
+        match self.screen {
+            Screen::Home => self.home.key_context(),
+            Screen::Practice(..) => Some(KeyContext::Practice),
+            Screen::Profiles => self.profile_screen.key_context(),
+            Screen::Browser => self.browser.key_context(),
+            Screen::Stats(_) => Some(KeyContext::Stats),
+        }
+    }
+
     pub fn handle_key_event(&mut self, key: event::KeyEvent) -> Result<Option<Action>> {
+        let sequence_started = !self.pending_keys.is_empty();
+        self.pending_keys.push(Key::from(key));
+        match self.config.keymap.resolve(self.key_context(), &self.pending_keys) {
+            Resolution::Pending => return Ok(None),
+            Resolution::Command(command) => {
+                self.pending_keys.clear();
+                return Ok(Some(command.into()));
+            }
+            Resolution::Unbound => {
+                self.pending_keys.clear();
+                // Like in vim, a key that does not continue a sequence cancels it and is dropped.
+                if sequence_started {
+                    return Ok(None);
+                }
+            }
+        }
+        if let Some(edit) = edit_key(key) {
+            return Ok(Some(Action::Edit(edit)));
+        }
         let action = match key.code {
             event::KeyCode::Char(' ') if event::KeyModifiers::CONTROL == key.modifiers => Some(Action::CtrlSpace),
             event::KeyCode::Char(' ') => Some(Action::Space),
             event::KeyCode::Backspace => Some(Action::Backspace),
             event::KeyCode::Enter => Some(Action::Enter),
-            event::KeyCode::Left => Some(Action::Left),
-            event::KeyCode::Right => Some(Action::Right),
-            event::KeyCode::Up => Some(Action::Up),
-            event::KeyCode::Down => Some(Action::Down),
-            event::KeyCode::Home => None,
-            event::KeyCode::End => None,
+            // Handled as edits above.
+            event::KeyCode::Left | event::KeyCode::Right | event::KeyCode::Up | event::KeyCode::Down => None,
+            event::KeyCode::Home | event::KeyCode::End | event::KeyCode::Delete => None,
             event::KeyCode::PageUp => None,
             event::KeyCode::PageDown => None,
             event::KeyCode::Tab => Some(Action::Tab),
             event::KeyCode::BackTab => None,
-            event::KeyCode::Delete => todo!(),
             event::KeyCode::Insert => None,
             event::KeyCode::F(_) => None,
             event::KeyCode::Char(c) => Some(Action::Char(c)),
@@ -67,29 +255,171 @@ impl Base {
     }
 
     pub fn handle_mouse_event(&mut self, mouse: event::MouseEvent) -> Result<Option<Action>> {
-        let _ = mouse;
-        Ok(None)
+        if self.changed_on_disk {
+            return Ok(None);
+        }
+        if self.help_scroll.is_some() {
+            return Ok(match mouse.kind {
+                event::MouseEventKind::ScrollUp => Some(Action::Command(Command::Up)),
+                event::MouseEventKind::ScrollDown => Some(Action::Command(Command::Down)),
+                _ => None,
+            });
+        }
+        Ok(match self.screen {
+            Screen::Home => self.home.handle_mouse_event(&self.collection, mouse),
+            Screen::Practice(..) => self.practice.handle_mouse_event(mouse),
+            Screen::Profiles => self.profile_screen.handle_mouse_event(mouse),
+            Screen::Browser => self.browser.handle_mouse_event(mouse),
+            Screen::Stats(_) => self.stats.handle_mouse_event(mouse),
+        })
     }
 
     pub fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::Tick => self.check_for_changes(false),
+            // Never write on top of changes made elsewhere, even between two periodic checks.
+            Action::Save | Action::Commit(_) | Action::Undo | Action::Redo | Action::RestoreBackup(_) => {
+                let already_changed = self.changed_on_disk;
+                self.check_for_changes(true);
+                if self.changed_on_disk && !already_changed {
+                    self.held_write = Some(action);
+                    return Ok(None);
+                }
+            }
+            _ => {}
+        }
+        if self.changed_on_disk {
+            return self.resolve_conflict(action);
+        }
+        if let Some(scroll) = self.help_scroll
+            && matches!(action, Action::Command(_) | Action::Help)
+        {
+            self.update_help(scroll, action);
+            return Ok(None);
+        }
         match action.clone() {
-            Action::Save => self.collection.save_to_file(utils::save_file_location()),
+            Action::Save => {
+                if let Err(err) = self.storage.save_all(&self.collection) {
+                    error!("Failed to save collection: {err}");
+                }
+            }
+            Action::Commit(operation) => match self.history.commit(&mut self.collection, operation.clone()) {
+                Ok(()) => self.persist(&operation),
+                Err(err) => error!("Failed to apply operation: {err}"),
+            },
+            Action::Undo => match self.history.undo(&mut self.collection) {
+                Ok(Some(operation)) => self.persist(&operation.inverse()),
+                Ok(None) => {}
+                Err(err) => error!("Failed to undo operation: {err}"),
+            },
+            Action::Redo => match self.history.redo(&mut self.collection) {
+                Ok(Some(operation)) => self.persist(&operation),
+                Ok(None) => {}
+                Err(err) => error!("Failed to redo operation: {err}"),
+            },
+            Action::RestoreBackup(path) => match self.restore_backup(&path) {
+                Ok(()) => self.screen = Screen::Home,
+                Err(err) => error!("Failed to restore backup {}: {err}", path.display()),
+            },
+            Action::OpenProfile(name) => match self.switch_profile(&name) {
+                Ok(()) => self.screen = Screen::Home,
+                Err(err) => error!("Failed to open profile {name}: {err}"),
+            },
             Action::Screen(new_screen) => {
                 self.screen = new_screen;
             }
+            Action::CutNotes(_) => self.screen = Screen::Home,
+            Action::Help if self.hints().is_some() => self.help_scroll = Some(0),
             _ => {}
         };
+        if matches!(action, Action::Commit(_) | Action::Undo | Action::Redo | Action::RestoreBackup(_)) {
+            self.home.collection_changed();
+        }
 
         match self.screen {
-            Screen::Home => self.home.update(&mut self.collection, action),
-            Screen::Practice(uuid) => self.practice.update(self.collection.find_deck(uuid).unwrap(), action),
+            Screen::Home => self.home.update(&self.collection, action),
+            Screen::Practice(..) => self.practice.update(&self.collection, action),
+            Screen::Profiles => self.profile_screen.update(&self.profile, action),
+            Screen::Browser => self.browser.update(&self.collection, action),
+            Screen::Stats(_) => self.stats.update(action),
         }
     }
 
     pub fn draw(&mut self, frame: &mut ratatui::Frame, area: ratatui::prelude::Rect) -> Result<()> {
         match self.screen {
             Screen::Home => self.home.draw(&self.collection, frame, area),
-            Screen::Practice(_) => self.practice.draw(frame, area),
+            Screen::Practice(..) => self.practice.draw(&self.collection, frame, area),
+            Screen::Profiles => self.profile_screen.draw(&self.profile, frame, area),
+            Screen::Browser => self.browser.draw(&self.collection, frame, area),
+            Screen::Stats(_) => self.stats.draw(&self.collection, frame, area),
+        }?;
+        if let Some(scroll) = self.help_scroll
+            && let Some((context, hints)) = self.hints()
+        {
+            self.help_scroll = Some(help::draw_help(frame, area, &self.config.keymap, context, &hints, scroll, &self.config.theme));
+        }
+        if self.changed_on_disk {
+            conflict::draw_conflict(frame, area, self.held_write.as_ref(), &self.config.theme, &self.config.keymap);
+        }
+        Ok(())
+    }
+}
+
+/// Opens the profile's collection and takes a backup of it, which only logs on failure.
+fn open_profile(profile: &Profile, ignore_lock: bool) -> Result<(Storage, Collection)> {
+    let (storage, collection) =
+        storage::open_collection(profile.collection_path(), profile.legacy_collection_path(), ignore_lock).wrap_err_with(|| {
+            format!(
+                "could not open the collection at {}; backups are kept in {}",
+                profile.collection_path().display(),
+                profile.backup_dir().display()
+            )
+        })?;
+    if let Err(err) = backup::create(&storage, &profile.backup_dir()).and_then(|_| backup::prune(&profile.backup_dir(), time::now())) {
+        error!("Failed to back up collection: {err}");
+    }
+    Ok((storage, collection))
+}
+
+/// The edit `key` makes in a text field: arrows move by grapheme, or by word with Ctrl or Alt, and
+/// extend the selection with Shift.
+fn edit_key(key: event::KeyEvent) -> Option<Edit> {
+    let control = key.modifiers.contains(event::KeyModifiers::CONTROL);
+    let alt = key.modifiers.contains(event::KeyModifiers::ALT);
+    let movement = match key.code {
+        event::KeyCode::Left if control || alt => Movement::WordLeft,
+        event::KeyCode::Right if control || alt => Movement::WordRight,
+        event::KeyCode::Left => Movement::Left,
+        event::KeyCode::Right => Movement::Right,
+        event::KeyCode::Up => Movement::Up,
+        event::KeyCode::Down => Movement::Down,
+        event::KeyCode::Home => Movement::RowStart,
+        event::KeyCode::End => Movement::RowEnd,
+        event::KeyCode::Backspace if control || alt => return Some(Edit::DeleteWordBackward),
+        event::KeyCode::Char('w') if control => return Some(Edit::DeleteWordBackward),
+        event::KeyCode::Delete if control || alt => return Some(Edit::DeleteWordForward),
+        event::KeyCode::Char('d') if alt => return Some(Edit::DeleteWordForward),
+        event::KeyCode::Delete => return Some(Edit::DeleteForward),
+        _ => return None,
+    };
+    Some(if key.modifiers.contains(event::KeyModifiers::SHIFT) { Edit::Select(movement) } else { Edit::Move(movement) })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn test_reloading_drops_held_changes() {
+        let commit = Action::Commit(Operation::DeleteDeck { deck: Uuid::new_v4() });
+        for held in [commit, Action::Undo, Action::Redo, Action::Save] {
+            assert!(kept_after_reload(held).is_none());
         }
+        let restore = Action::RestoreBackup(PathBuf::from("backup.json"));
+        assert_eq!(kept_after_reload(restore.clone()), Some(restore));
     }
 }
@@ -0,0 +1,480 @@
+use std::collections::HashSet;
+
+use color_eyre::Result;
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Layout, Margin, Position, Rect},
+    style::{Modifier, Style},
+    text::Text,
+    widgets::{Block, Cell, Paragraph, Row, Table, TableState},
+};
+use strum::Display;
+use uuid::Uuid;
+
+use crate::{
+    action::{Action, Screen},
+    config::{Config, Theme},
+    keymap::{Command, Hint, KeyContext, Keymap},
+    models::{
+        card::Card,
+        collection::Collection,
+        note::{Note, NoteType},
+        operation::Operation,
+        schedule::{CardState, Flag, Schedule},
+        search::{self, Query, SearchResult},
+    },
+    time::{self, DAY, HOUR},
+};
+
+use super::{
+    editor::{Edit, Editor},
+    home_screen::deck_panel::{self, InsertNoteState},
+    title, utils,
+};
+
+const SEARCH_PROMPT: &str = "/ ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+enum Column {
+    #[strum(to_string = "sort field")]
+    SortField,
+    #[strum(to_string = "deck")]
+    Deck,
+    #[strum(to_string = "type")]
+    Type,
+    #[strum(to_string = "tags")]
+    Tags,
+    #[strum(to_string = "due")]
+    Due,
+    #[strum(to_string = "interval")]
+    Interval,
+    #[strum(to_string = "ease")]
+    Ease,
+    #[strum(to_string = "lapses")]
+    Lapses,
+}
+
+const COLUMNS: [Column; 8] =
+    [Column::SortField, Column::Deck, Column::Type, Column::Tags, Column::Due, Column::Interval, Column::Ease, Column::Lapses];
+
+/// The search of the leeches view.
+const LEECHES_QUERY: &str = "tag:leech";
+
+/// A note in the results, with what its columns show.
+struct BrowserRow {
+    note: Uuid,
+    sort_field: String,
+    deck: String,
+    note_type: NoteType,
+    tags: String,
+    /// When the note's next card is due, or `None` while all of its cards are new.
+    due: Option<i64>,
+    interval: u32,
+    ease: u32,
+    /// The most times any of the note's cards was forgotten.
+    lapses: u32,
+    /// Whether all of the note's cards are suspended.
+    suspended: bool,
+    /// The flag of the note's first flagged card.
+    flag: Option<Flag>,
+}
+
+impl BrowserRow {
+    fn new(result: SearchResult) -> Self {
+        let cards = result.note.get_cards();
+        // The columns describe the card that is due first, like Anki's notes mode.
+        let scheduled =
+            cards.iter().map(|card| &card.schedule).filter(|schedule| schedule.state != CardState::New).min_by_key(|schedule| schedule.due);
+        let schedule = scheduled.unwrap_or(&cards[0].schedule);
+        BrowserRow {
+            note: result.note.uuid,
+            sort_field: result.note.front.replace('\n', " "),
+            deck: result.deck.qualified_name(),
+            note_type: result.note.note_type.clone(),
+            tags: result.note.tags.join(" "),
+            due: scheduled.map(|schedule| schedule.due),
+            interval: schedule.interval,
+            ease: schedule.ease,
+            lapses: cards.iter().map(|card| card.schedule.lapses).max().unwrap_or_default(),
+            suspended: cards.iter().all(|card| card.schedule.suspended),
+            flag: cards.iter().find_map(|card| card.schedule.flag),
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Mode {
+    Browse,
+    Search,
+    Edit(Box<InsertNoteState>),
+}
+
+/// Every note in the collection, filtered by a search and sorted by a column.
+pub struct BrowserScreen {
+    query: Editor,
+    /// Why the query could not be parsed, while it cannot.
+    error: Option<String>,
+    rows: Vec<BrowserRow>,
+    state: TableState,
+    marked: HashSet<Uuid>,
+    sort: Column,
+    descending: bool,
+    mode: Mode,
+    theme: Theme,
+    keymap: Keymap,
+    search_area: Rect,
+    table_area: Rect,
+    command_bar: Vec<(Rect, Command)>,
+}
+
+impl BrowserScreen {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            query: Editor::new(SEARCH_PROMPT),
+            error: None,
+            rows: Vec::new(),
+            state: TableState::default(),
+            marked: HashSet::new(),
+            sort: Column::SortField,
+            descending: false,
+            mode: Mode::Browse,
+            theme: config.theme,
+            keymap: config.keymap.clone(),
+            search_area: Rect::default(),
+            table_area: Rect::default(),
+            command_bar: Vec::new(),
+        }
+    }
+
+    pub fn key_context(&self) -> Option<KeyContext> {
+        match self.mode {
+            Mode::Browse => Some(KeyContext::Browser),
+            Mode::Search | Mode::Edit(_) => None,
+        }
+    }
+
+    /// The commands offered in the current mode, unless text is being typed.
+    pub fn hints(&self) -> Option<(KeyContext, Vec<Hint>)> {
+        self.key_context().map(|context| {
+            (
+                context,
+                vec![
+                    (Command::Up, "up"),
+                    (Command::Down, "down"),
+                    (Command::Search, "search"),
+                    (Command::Leeches, "leeches"),
+                    (Command::Edit, "edit"),
+                    (Command::Mark, "mark"),
+                    (Command::CycleType, "type"),
+                    (Command::Suspend, "suspend"),
+                    (Command::Flag, "flag"),
+                    (Command::Cut, "cut"),
+                    (Command::Delete, "delete"),
+                    (Command::SortBy, "sort"),
+                    (Command::ReverseSort, "reverse"),
+                    (Command::Undo, "undo"),
+                    (Command::Back, "collection"),
+                ],
+            )
+        })
+    }
+
+    pub fn update(&mut self, collection: &Collection, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::Screen(Screen::Browser) => {
+                self.mode = Mode::Browse;
+                self.refresh(collection);
+                return Ok(None);
+            }
+            Action::Commit(_) | Action::Undo | Action::Redo => self.refresh(collection),
+            _ => {}
+        }
+        match self.mode.clone() {
+            Mode::Browse => self.update_browse(collection, action),
+            Mode::Search => self.update_search(collection, action),
+            Mode::Edit(state) => self.update_edit(collection, action, state),
+        }
+    }
+
+    fn update_browse(&mut self, collection: &Collection, action: Action) -> Result<Option<Action>> {
+        let selected = self.state.selected().and_then(|i| self.rows.get(i)).and_then(|row| collection.find_note(row.note));
+        match action {
+            Action::Command(command) if command.moves_selection() => {
+                self.state.select(utils::move_selection(command, self.state.selected(), self.rows.len()))
+            }
+            Action::Command(Command::Search) => self.mode = Mode::Search,
+            Action::Command(Command::Leeches) => {
+                self.query = Editor::with_text(SEARCH_PROMPT, LEECHES_QUERY.to_string());
+                (self.sort, self.descending) = (Column::Lapses, true);
+                self.refresh(collection);
+            }
+            Action::Command(Command::Edit) => {
+                if let Some(note) = selected {
+                    self.mode = Mode::Edit(Box::new(InsertNoteState::from_note(note)));
+                }
+            }
+            Action::Command(Command::Mark) => {
+                if let Some(note) = selected
+                    && !self.marked.remove(&note.uuid)
+                {
+                    self.marked.insert(note.uuid);
+                }
+            }
+            Action::Command(Command::SortBy) => {
+                let next = COLUMNS.iter().position(|column| *column == self.sort).map_or(0, |i| (i + 1) % COLUMNS.len());
+                self.sort = COLUMNS[next];
+                self.sort_rows();
+            }
+            Action::Command(Command::ReverseSort) => {
+                self.descending = !self.descending;
+                self.sort_rows();
+            }
+            Action::Command(Command::CycleType) => {
+                let notes: Vec<Note> = self
+                    .targets()
+                    .iter()
+                    .filter_map(|uuid| collection.find_note(*uuid))
+                    .map(|note| Note { note_type: note.note_type.next(), ..note.clone() })
+                    .collect();
+                return Ok(Operation::edit_notes(collection, notes).map(Action::Commit));
+            }
+            Action::Command(Command::Suspend) => {
+                let cards = self.target_cards(collection);
+                // Unsuspends the cards when all of them already are.
+                let suspended = !cards.iter().all(|card| card.schedule.suspended);
+                return Ok(Operation::change_schedules(&cards, |schedule| Schedule { suspended, ..schedule.clone() }).map(Action::Commit));
+            }
+            Action::Command(Command::Flag) => {
+                let cards = self.target_cards(collection);
+                let flag = Flag::cycle(cards.iter().find_map(|card| card.schedule.flag));
+                return Ok(Operation::change_schedules(&cards, |schedule| Schedule { flag, ..schedule.clone() }).map(Action::Commit));
+            }
+            Action::Command(Command::Delete) => {
+                let notes = self.targets();
+                self.marked.clear();
+                return Ok(Operation::remove_notes(collection, &notes).map(Action::Commit));
+            }
+            Action::Command(Command::Cut) => {
+                let notes = self.targets();
+                if !notes.is_empty() {
+                    self.marked.clear();
+                    return Ok(Some(Action::CutNotes(notes)));
+                }
+            }
+            Action::Command(Command::Back) => return Ok(Some(Action::Screen(Screen::Home))),
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn update_search(&mut self, collection: &Collection, action: Action) -> Result<Option<Action>> {
+        let width = self.search_area.width.saturating_sub(2);
+        match action {
+            Action::Char(c) => self.query.insert(&c.to_string()),
+            Action::Space => self.query.insert(" "),
+            Action::Paste(text) => self.query.insert(&text.replace('\n', " ")),
+            Action::Backspace => self.query.edit(Edit::DeleteBackward, width),
+            Action::Edit(edit) => self.query.edit(edit, width),
+            Action::Enter | Action::Esc => {
+                self.mode = Mode::Browse;
+                return Ok(None);
+            }
+            _ => return Ok(None),
+        }
+        self.refresh(collection);
+        Ok(None)
+    }
+
+    fn update_edit(&mut self, collection: &Collection, action: Action, state: Box<InsertNoteState>) -> Result<Option<Action>> {
+        let Some(deck) = state.editing.and_then(|uuid| collection.find_note_deck(uuid)) else {
+            self.mode = Mode::Browse;
+            return Ok(None);
+        };
+        if action == Action::Command(Command::OpenInEditor) {
+            return Ok(Some(Action::EditExternally(state.fields())));
+        }
+        let (state, operation) =
+            deck_panel::update_deck_panel_note_insert(action, *state, collection, deck, deck_panel::editor_width(self.table_area));
+        if state.completed {
+            self.mode = Mode::Browse;
+            return Ok(operation.map(Action::Commit));
+        }
+        self.mode = Mode::Edit(Box::new(state));
+        Ok(None)
+    }
+
+    pub fn handle_mouse_event(&mut self, mouse: MouseEvent) -> Option<Action> {
+        if !matches!(self.mode, Mode::Browse | Mode::Search) {
+            return None;
+        }
+        let position = Position::new(mouse.column, mouse.row);
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if let Some(command) = utils::clicked_command(&self.command_bar, position) {
+                    return Some(command.into());
+                }
+                if self.search_area.contains(position) {
+                    self.mode = Mode::Search;
+                    return None;
+                }
+                // The first row of the table is its header.
+                let rows = Rect { y: self.table_area.y + 1, height: self.table_area.height.saturating_sub(1), ..self.table_area };
+                let row = utils::row_at(rows, self.state.offset(), position).filter(|row| *row < self.rows.len())?;
+                self.mode = Mode::Browse;
+                self.state.select(Some(row));
+                None
+            }
+            MouseEventKind::ScrollUp if self.table_area.contains(position) => Some(Action::Command(Command::Up)),
+            MouseEventKind::ScrollDown if self.table_area.contains(position) => Some(Action::Command(Command::Down)),
+            _ => None,
+        }
+    }
+
+    /// The marked notes in the order shown, or the selected note if none are marked.
+    fn targets(&self) -> Vec<Uuid> {
+        let marked: Vec<Uuid> = self.rows.iter().map(|row| row.note).filter(|uuid| self.marked.contains(uuid)).collect();
+        if !marked.is_empty() {
+            return marked;
+        }
+        self.state.selected().and_then(|i| self.rows.get(i)).map(|row| row.note).into_iter().collect()
+    }
+
+    /// All cards of the notes `targets` returns.
+    fn target_cards(&self, collection: &Collection) -> Vec<Card> {
+        self.targets().iter().filter_map(|uuid| collection.find_note(*uuid)).flat_map(Note::get_cards).collect()
+    }
+
+    /// Runs the search again on `collection`, keeping the selected note selected.
+    fn refresh(&mut self, collection: &Collection) {
+        let query = match Query::parse(self.query.text()) {
+            Ok(query) => query,
+            Err(err) => {
+                self.error = Some(err);
+                return;
+            }
+        };
+        self.error = None;
+        self.rows = search::search(collection, &query, time::now()).into_iter().map(BrowserRow::new).collect();
+        self.marked.retain(|uuid| collection.find_note(*uuid).is_some());
+        self.sort_rows();
+    }
+
+    fn sort_rows(&mut self) {
+        let selected = self.state.selected().and_then(|i| self.rows.get(i)).map(|row| row.note);
+        let (sort, descending) = (self.sort, self.descending);
+        self.rows.sort_by(|a, b| {
+            let ordering = match sort {
+                Column::SortField => a.sort_field.to_lowercase().cmp(&b.sort_field.to_lowercase()),
+                Column::Deck => a.deck.to_lowercase().cmp(&b.deck.to_lowercase()),
+                Column::Type => a.note_type.to_string().cmp(&b.note_type.to_string()),
+                Column::Tags => a.tags.to_lowercase().cmp(&b.tags.to_lowercase()),
+                // New notes, which are not due at all, go last.
+                Column::Due => (a.due.is_none(), a.due).cmp(&(b.due.is_none(), b.due)),
+                Column::Interval => a.interval.cmp(&b.interval),
+                Column::Ease => a.ease.cmp(&b.ease),
+                Column::Lapses => a.lapses.cmp(&b.lapses),
+            };
+            let ordering = ordering.then_with(|| a.sort_field.cmp(&b.sort_field));
+            if descending { ordering.reverse() } else { ordering }
+        });
+        let position = selected.and_then(|uuid| self.rows.iter().position(|row| row.note == uuid));
+        self.state.select(match position {
+            Some(position) => Some(position),
+            None if self.rows.is_empty() => None,
+            None => Some(self.state.selected().unwrap_or(0).min(self.rows.len() - 1)),
+        });
+    }
+
+    pub fn draw(&mut self, collection: &Collection, frame: &mut Frame, area: Rect) -> Result<()> {
+        let chunks = Layout::vertical([Constraint::Length(7), Constraint::Min(0), Constraint::Length(3)]).split(area);
+        title::draw_title(frame, chunks[0])?;
+        let [search_area, table_area] = Layout::vertical([Constraint::Length(3), Constraint::Min(0)]).areas(chunks[1]);
+        self.search_area = search_area;
+        self.table_area = table_area;
+
+        self.draw_search(frame, search_area);
+        match &self.mode {
+            Mode::Edit(state) => match state.editing.and_then(|uuid| collection.find_note_deck(uuid)) {
+                Some(deck) => deck_panel::draw_deck_panel_insert_view(frame, table_area, deck.clone(), (**state).clone(), &self.theme),
+                None => self.draw_table(frame, table_area),
+            },
+            _ => self.draw_table(frame, table_area),
+        }
+
+        let mut commands = match (&self.mode, self.hints()) {
+            (_, Some((context, hints))) => utils::command_bar_hints(&self.keymap, context, &hints),
+            (Mode::Edit(_), None) => utils::fixed_command_bar(&["<C-Space> : newline", "<Esc> : cancel", "<CR> : submit", "<Tab> : switch side"]),
+            (_, None) => utils::fixed_command_bar(&["<CR> : done"]),
+        };
+        let editor = (Command::OpenInEditor, "$EDITOR");
+        if let (Mode::Edit(_), Some(hint)) = (&self.mode, self.keymap.hint(KeyContext::Global, &editor)) {
+            commands.insert(1, (Some(editor.0), hint));
+        }
+        self.command_bar = utils::draw_command_bar(frame, chunks[2], &commands);
+        Ok(())
+    }
+
+    fn draw_search(&self, frame: &mut Frame, area: Rect) {
+        let inner = area.inner(Margin::new(1, 1));
+        let view = self.query.view(inner.width, inner.height, Style::default().add_modifier(Modifier::REVERSED));
+        let block = Block::bordered().title("[search]");
+        let block = match &self.error {
+            Some(error) => block.title_bottom(error.as_str()).border_style(Style::default().fg(self.theme.warning)),
+            None => block,
+        };
+        let searching = matches!(self.mode, Mode::Search);
+        let style = if searching { Style::default().fg(self.theme.highlight) } else { Style::default() };
+        frame.render_widget(Paragraph::new(view.text).scroll((view.scroll, 0)).block(block).style(style), area);
+        if searching {
+            frame.set_cursor_position((inner.x + view.cursor.0, inner.y + view.cursor.1));
+        }
+    }
+
+    fn draw_table(&mut self, frame: &mut Frame, area: Rect) {
+        let now = time::now();
+        let header = Row::new(COLUMNS.map(|column| match column == self.sort {
+            true => format!("{column} {}", if self.descending { "▼" } else { "▲" }),
+            false => column.to_string(),
+        }))
+        .style(Style::default().add_modifier(Modifier::BOLD));
+        let rows: Vec<Row> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let flag = row.flag.map_or_else(Style::default, |flag| Style::default().fg(utils::flag_color(flag)));
+                Row::new([
+                    Cell::from(Text::from(row.sort_field.clone())).style(flag),
+                    Cell::from(Text::from(row.deck.clone())),
+                    Cell::from(Text::from(row.note_type.to_string())),
+                    Cell::from(Text::from(row.tags.clone())),
+                    Cell::from(Text::from(row.due.map_or_else(|| "new".to_string(), |due| format_due(due, now)))),
+                    Cell::from(Text::from(format!("{}d", row.interval))),
+                    Cell::from(Text::from(format!("{}%", row.ease / 10))),
+                    Cell::from(Text::from(row.lapses.to_string())),
+                ])
+                .style(match (self.marked.contains(&row.note), row.suspended) {
+                    (true, _) => Style::default().fg(self.theme.marked),
+                    (false, true) => Style::default().fg(self.theme.muted),
+                    (false, false) => Style::default(),
+                })
+            })
+            .collect();
+        let widths = Constraint::from_percentages([26, 15, 10, 13, 9, 9, 9, 9]);
+        let table = Table::new(rows, widths)
+            .header(header)
+            .column_spacing(2)
+            .row_highlight_style(self.theme.selected())
+            .block(Block::bordered().title(format!("[browser][{} notes]", self.rows.len())));
+        frame.render_stateful_widget(table, area, &mut self.state);
+    }
+}
+
+/// When a card is due relative to `now`, like `due`, `in 5h` or `in 3d`.
+fn format_due(due: i64, now: i64) -> String {
+    match due - now {
+        left if left <= 0 => "due".to_string(),
+        left if left < DAY => format!("in {}h", (left + HOUR - 1) / HOUR),
+        left => format!("in {}d", (left + DAY - 1) / DAY),
+    }
+}
@@ -0,0 +1,44 @@
+use ratatui::{
+    layout::Rect,
+    style::Style,
+    text::{Line, Text},
+    widgets::{Block, Clear, Paragraph, Wrap},
+};
+
+use crate::action::Action;
+use crate::config::Theme;
+use crate::keymap::{Command, KeyContext, Keymap};
+
+use super::utils::popup_area;
+
+/// Asks what to do after the collection was modified on disk by something else, and what happens to
+/// the write that found it, which has not been done yet.
+pub fn draw_conflict(frame: &mut ratatui::Frame, area: Rect, held: Option<&Action>, theme: &Theme, keymap: &Keymap) {
+    let mut lines = vec![
+        Line::from("the collection was changed by another program"),
+        Line::from(""),
+        Line::from("reloading keeps the other changes and everything saved here before;"),
+        Line::from("overwriting replaces the other changes with what is shown here"),
+    ];
+    match held {
+        Some(Action::RestoreBackup(_)) => {
+            lines.push(Line::from("the backup is not restored yet and is restored after either choice"));
+        }
+        Some(Action::Commit(_) | Action::Undo | Action::Redo) => {
+            lines.push(Line::from("your last change, undo or redo is not done yet: overwriting does it, reloading discards it"));
+        }
+        _ => {}
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(keymap.hints(KeyContext::Conflict, &[(Command::Reload, "reload"), (Command::Overwrite, "overwrite")]).join("   ")));
+    let text = Text::from(lines);
+    let area = popup_area(area, 70, 12);
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(text)
+            .centered()
+            .wrap(Wrap { trim: true })
+            .block(Block::bordered().title("[changed on disk]").border_style(Style::default().fg(theme.warning))),
+        area,
+    );
+}
@@ -0,0 +1,405 @@
+use std::ops::Range;
+
+use ratatui::{
+    style::Style,
+    text::{Line, Span, Text},
+};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Cursor movements, by grapheme, word or wrapped row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Movement {
+    Left,
+    Right,
+    WordLeft,
+    WordRight,
+    Up,
+    Down,
+    RowStart,
+    RowEnd,
+}
+
+/// Editing keys that are not plain text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    Move(Movement),
+    /// Moves the cursor while extending the selection.
+    Select(Movement),
+    DeleteBackward,
+    DeleteForward,
+    DeleteWordBackward,
+    DeleteWordForward,
+}
+
+/// What to draw for an editor: its rows, how far they are scrolled, and where the cursor is within
+/// the visible rows.
+pub struct EditorView {
+    pub text: Text<'static>,
+    pub scroll: u16,
+    pub cursor: (u16, u16),
+}
+
+/// A multi-line text field that moves and deletes by grapheme and wraps its text itself, so that
+/// the cursor is always drawn where the text is.
+#[derive(Clone, Debug)]
+pub struct Editor {
+    prompt: &'static str,
+    text: String,
+    /// Byte index of the cursor, always on a grapheme boundary.
+    cursor: usize,
+    /// The other end of the selection, while there is one.
+    anchor: Option<usize>,
+    /// Column to return to when moving up and down through shorter rows.
+    column: Option<usize>,
+}
+
+impl Editor {
+    /// An empty editor whose first row starts with `prompt`.
+    pub fn new(prompt: &'static str) -> Self {
+        Self::with_text(prompt, String::new())
+    }
+
+    pub fn with_text(prompt: &'static str, text: String) -> Self {
+        Self { prompt, cursor: text.len(), text, anchor: None, column: None }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Inserts `text` at the cursor, replacing the selection.
+    pub fn insert(&mut self, text: &str) {
+        self.delete_selection();
+        self.text.insert_str(self.cursor, text);
+        self.cursor += text.len();
+        self.column = None;
+    }
+
+    /// The text between the last whitespace and the cursor, like a tag being typed.
+    pub fn word_before_cursor(&self) -> &str {
+        self.text[..self.cursor].rsplit(char::is_whitespace).next().unwrap_or("")
+    }
+
+    /// Replaces the word before the cursor with `word`, leaving the cursor after it.
+    pub fn complete_word(&mut self, word: &str) {
+        let start = self.cursor - self.word_before_cursor().len();
+        self.anchor = None;
+        self.text.replace_range(start..self.cursor, word);
+        self.cursor = start + word.len();
+        self.column = None;
+    }
+
+    /// Applies `edit`, laying the text out in rows of `width` columns for moving between rows.
+    pub fn edit(&mut self, edit: Edit, width: u16) {
+        match edit {
+            Edit::Move(movement) => {
+                let target = self.target(movement, width);
+                self.anchor = None;
+                self.move_to(target, movement);
+            }
+            Edit::Select(movement) => {
+                let target = self.target(movement, width);
+                self.anchor.get_or_insert(self.cursor);
+                self.move_to(target, movement);
+            }
+            Edit::DeleteBackward => self.delete_towards(self.previous_boundary()),
+            Edit::DeleteForward => self.delete_towards(self.next_boundary()),
+            Edit::DeleteWordBackward => self.delete_towards(self.word_left()),
+            Edit::DeleteWordForward => self.delete_towards(self.word_right()),
+        }
+    }
+
+    /// The text laid out in rows of `width` columns, scrolled to keep the cursor within `height`
+    /// rows, with the selection drawn in `selection_style`.
+    pub fn view(&self, width: u16, height: u16, selection_style: Style) -> EditorView {
+        let rows = self.rows(width);
+        let (row, column) = self.position(&rows, self.cursor);
+        let scroll = (row + 1).saturating_sub(height.max(1) as usize);
+        let selection = self.selection().unwrap_or(0..0);
+        let lines: Vec<Line> = rows
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let mut spans = if i == 0 { vec![Span::raw(self.prompt)] } else { Vec::new() };
+                // Split the row where the selection starts and ends.
+                let mut bounds = vec![row.start, row.end];
+                bounds.extend([selection.start, selection.end].into_iter().filter(|bound| row.contains(bound)));
+                bounds.sort();
+                for part in bounds.windows(2).filter(|part| part[0] < part[1]) {
+                    let content = self.text[part[0]..part[1]].to_string();
+                    spans.push(if selection.contains(&part[0]) { Span::styled(content, selection_style) } else { Span::raw(content) });
+                }
+                Line::from(spans)
+            })
+            .collect();
+        EditorView { text: Text::from(lines), scroll: scroll as u16, cursor: (column as u16, (row - scroll) as u16) }
+    }
+
+    fn selection(&self) -> Option<Range<usize>> {
+        let anchor = self.anchor.filter(|anchor| *anchor != self.cursor)?;
+        Some(anchor.min(self.cursor)..anchor.max(self.cursor))
+    }
+
+    fn delete_selection(&mut self) -> bool {
+        let selection = self.selection();
+        self.anchor = None;
+        match selection {
+            Some(selection) => {
+                self.cursor = selection.start;
+                self.text.replace_range(selection, "");
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Deletes from the cursor to `target`, or the selection if there is one.
+    fn delete_towards(&mut self, target: usize) {
+        self.column = None;
+        if self.delete_selection() {
+            return;
+        }
+        let range = self.cursor.min(target)..self.cursor.max(target);
+        self.cursor = range.start;
+        self.text.replace_range(range, "");
+    }
+
+    fn move_to(&mut self, target: usize, movement: Movement) {
+        if !matches!(movement, Movement::Up | Movement::Down) {
+            self.column = None;
+        }
+        self.cursor = target;
+    }
+
+    fn target(&mut self, movement: Movement, width: u16) -> usize {
+        match movement {
+            Movement::Left => self.previous_boundary(),
+            Movement::Right => self.next_boundary(),
+            Movement::WordLeft => self.word_left(),
+            Movement::WordRight => self.word_right(),
+            Movement::Up | Movement::Down | Movement::RowStart | Movement::RowEnd => {
+                let rows = self.rows(width);
+                let (row, column) = self.position(&rows, self.cursor);
+                match movement {
+                    Movement::RowStart => rows[row].start,
+                    Movement::RowEnd => self.last_position(&rows, row),
+                    _ => {
+                        let column = *self.column.get_or_insert(column);
+                        let target = if movement == Movement::Up { row.checked_sub(1) } else { Some(row + 1).filter(|row| *row < rows.len()) };
+                        match target {
+                            Some(target) => self.at_column(&rows, target, column),
+                            None if movement == Movement::Up => 0,
+                            None => self.text.len(),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn previous_boundary(&self) -> usize {
+        self.text[..self.cursor].grapheme_indices(true).next_back().map_or(0, |(i, _)| i)
+    }
+
+    fn next_boundary(&self) -> usize {
+        self.text[self.cursor..].graphemes(true).next().map_or(self.cursor, |grapheme| self.cursor + grapheme.len())
+    }
+
+    /// The start of the word before the cursor, skipping whitespace and punctuation.
+    fn word_left(&self) -> usize {
+        self.text[..self.cursor].split_word_bound_indices().rev().find(|(_, word)| word.chars().any(char::is_alphanumeric)).map_or(0, |(i, _)| i)
+    }
+
+    /// The end of the word after the cursor.
+    fn word_right(&self) -> usize {
+        self.text[self.cursor..]
+            .split_word_bound_indices()
+            .find(|(_, word)| word.chars().any(char::is_alphanumeric))
+            .map_or(self.text.len(), |(i, word)| self.cursor + i + word.len())
+    }
+
+    /// Byte ranges of the rows the text wraps into at `width` columns, not including line breaks.
+    /// Rows break after whitespace where possible, and within a word when it is wider than a row.
+    fn rows(&self, width: u16) -> Vec<Range<usize>> {
+        let width = (width as usize).max(1);
+        let mut rows = Vec::new();
+        let mut line_start = 0;
+        for line in self.text.split('\n') {
+            let mut start = line_start;
+            let mut column = if rows.is_empty() { self.prompt.width() } else { 0 };
+            let mut last_break = None;
+            for (i, grapheme) in line.grapheme_indices(true) {
+                let i = line_start + i;
+                let grapheme_width = grapheme.width();
+                if column + grapheme_width > width && i > start {
+                    let end = last_break.filter(|end| *end > start).unwrap_or(i);
+                    rows.push(start..end);
+                    start = end;
+                    column = self.text[start..i].width();
+                    last_break = None;
+                }
+                column += grapheme_width;
+                if grapheme.trim().is_empty() {
+                    last_break = Some(i + grapheme.len());
+                }
+            }
+            rows.push(start..line_start + line.len());
+            line_start += line.len() + 1;
+        }
+        rows
+    }
+
+    /// The row and column of the byte index `cursor`. At a soft wrap it belongs to the next row.
+    fn position(&self, rows: &[Range<usize>], cursor: usize) -> (usize, usize) {
+        let row = rows.iter().rposition(|row| row.start <= cursor).unwrap_or(0);
+        let prompt = if row == 0 { self.prompt.width() } else { 0 };
+        (row, prompt + self.text[rows[row].start..cursor].width())
+    }
+
+    /// The last cursor position on `row`: its end, or before its last grapheme if it wraps softly
+    /// into the next row.
+    fn last_position(&self, rows: &[Range<usize>], row: usize) -> usize {
+        let range = rows[row].clone();
+        match rows.get(row + 1) {
+            Some(next) if next.start == range.end => {
+                self.text[range.clone()].grapheme_indices(true).next_back().map_or(range.start, |(i, _)| range.start + i)
+            }
+            _ => range.end,
+        }
+    }
+
+    /// The position on `row` closest to `column` without going past it.
+    fn at_column(&self, rows: &[Range<usize>], row: usize, column: usize) -> usize {
+        let start = rows[row].start;
+        let last = self.last_position(rows, row);
+        let mut current = if row == 0 { self.prompt.width() } else { 0 };
+        for (i, grapheme) in self.text[start..last].grapheme_indices(true) {
+            current += grapheme.width();
+            if current > column {
+                return start + i;
+            }
+        }
+        last
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor(text: &str) -> Editor {
+        Editor::with_text("", text.to_string())
+    }
+
+    fn rows(editor: &Editor, width: u16) -> Vec<&str> {
+        editor.rows(width).into_iter().map(|row| &editor.text[row]).collect()
+    }
+
+    #[test]
+    fn test_edits_whole_graphemes() {
+        let mut editor = editor("");
+        for text in ["é", "日本", "e\u{301}", "👍🏽"] {
+            editor.insert(text);
+        }
+        assert_eq!(editor.text(), "é日本e\u{301}👍🏽");
+
+        editor.edit(Edit::DeleteBackward, 80);
+        editor.edit(Edit::DeleteBackward, 80);
+        assert_eq!(editor.text(), "é日本");
+        editor.edit(Edit::Move(Movement::Left), 80);
+        editor.edit(Edit::Move(Movement::Left), 80);
+        editor.edit(Edit::DeleteForward, 80);
+        assert_eq!(editor.text(), "é本");
+        editor.insert("x");
+        assert_eq!(editor.text(), "éx本");
+    }
+
+    #[test]
+    fn test_wraps_at_whitespace_and_within_long_words() {
+        let editor = editor("the quick brown fox\njumps\n");
+
+        assert_eq!(rows(&editor, 10), vec!["the quick ", "brown fox", "jumps", ""]);
+        assert_eq!(rows(&Editor::with_text(">> ", "abcdefghij".to_string()), 6), vec!["abc", "defghi", "j"]);
+        assert_eq!(rows(&Editor::with_text("", "日本語です".to_string()), 4), vec!["日本", "語で", "す"]);
+    }
+
+    #[test]
+    fn test_cursor_position_follows_rows() {
+        let mut editor = editor("the quick brown fox");
+        let rows = editor.rows(10);
+        assert_eq!(editor.position(&rows, 10), (1, 0));
+        assert_eq!(editor.position(&rows, 19), (1, 9));
+
+        let view = editor.view(10, 1, Style::default());
+        assert_eq!((view.scroll, view.cursor), (1, (9, 0)));
+
+        editor.edit(Edit::Move(Movement::RowStart), 10);
+        assert_eq!(editor.cursor, 10);
+        editor.edit(Edit::Move(Movement::Up), 10);
+        assert_eq!(editor.cursor, 0);
+        editor.edit(Edit::Move(Movement::RowEnd), 10);
+        assert_eq!(editor.cursor, 9);
+    }
+
+    #[test]
+    fn test_up_and_down_keep_the_column() {
+        let mut editor = editor("long line\nab\nanother line");
+        editor.edit(Edit::Move(Movement::RowStart), 80);
+        for _ in 0..4 {
+            editor.edit(Edit::Move(Movement::Right), 80);
+        }
+
+        editor.edit(Edit::Move(Movement::Up), 80);
+        assert_eq!(editor.cursor, 12);
+        editor.edit(Edit::Move(Movement::Up), 80);
+        assert_eq!(editor.cursor, 4);
+        editor.edit(Edit::Move(Movement::Down), 80);
+        editor.edit(Edit::Move(Movement::Down), 80);
+        assert_eq!(editor.cursor, 17);
+        editor.edit(Edit::Move(Movement::Down), 80);
+        assert_eq!(editor.cursor, editor.text.len());
+    }
+
+    #[test]
+    fn test_word_movement_and_deletion() {
+        let mut editor = editor("hello, big world");
+
+        editor.edit(Edit::DeleteWordBackward, 80);
+        assert_eq!(editor.text(), "hello, big ");
+        editor.edit(Edit::Move(Movement::WordLeft), 80);
+        editor.edit(Edit::Move(Movement::WordLeft), 80);
+        assert_eq!(editor.cursor, 0);
+        editor.edit(Edit::Move(Movement::WordRight), 80);
+        assert_eq!(editor.cursor, 5);
+        editor.edit(Edit::DeleteWordForward, 80);
+        assert_eq!(editor.text(), "hello ");
+    }
+
+    #[test]
+    fn test_selection_is_replaced_and_deleted() {
+        let mut editor = editor("hello world");
+
+        editor.edit(Edit::Select(Movement::WordLeft), 80);
+        editor.insert("there");
+        assert_eq!(editor.text(), "hello there");
+
+        editor.edit(Edit::Move(Movement::RowStart), 80);
+        editor.edit(Edit::Select(Movement::Right), 80);
+        editor.edit(Edit::Select(Movement::Right), 80);
+        editor.edit(Edit::DeleteBackward, 80);
+        assert_eq!(editor.text(), "llo there");
+        assert_eq!(editor.selection(), None);
+    }
+
+    #[test]
+    fn test_complete_word_before_cursor() {
+        let mut editor = editor("exam gram");
+
+        assert_eq!(editor.word_before_cursor(), "gram");
+        editor.complete_word("grammar::verbs");
+        assert_eq!(editor.text(), "exam grammar::verbs");
+        editor.insert(" ");
+        assert_eq!(editor.word_before_cursor(), "");
+    }
+}
@@ -0,0 +1,53 @@
+use ratatui::{
+    layout::{Constraint, Margin, Rect},
+    style::{Modifier, Style},
+    widgets::{Block, Cell, Clear, Row, Scrollbar, ScrollbarOrientation, ScrollbarState, Table, TableState},
+};
+use strum::EnumMessage;
+
+use crate::{
+    config::Theme,
+    keymap::{Command, Hint, KeyContext, Keymap},
+};
+
+use super::utils::popup_area;
+
+/// Lists every command in `hints` with all of its keys in `context` and what it does, scrolled down
+/// by `scroll` rows. Returns the scroll clamped to the rows there are.
+pub fn draw_help(
+    frame: &mut ratatui::Frame,
+    area: Rect,
+    keymap: &Keymap,
+    context: KeyContext,
+    hints: &[Hint],
+    scroll: usize,
+    theme: &Theme,
+) -> usize {
+    let rows: Vec<[String; 3]> = hints
+        .iter()
+        .filter_map(|(command, label)| {
+            let keys = keymap.keys_for(context, *command);
+            (!keys.is_empty()).then(|| [keys.join(" "), label.to_string(), command.get_message().unwrap_or_default().to_string()])
+        })
+        .collect();
+    let column_width = |column: usize| rows.iter().map(|row| row[column].chars().count()).max().unwrap_or(0) as u16;
+    let widths = [Constraint::Length(column_width(0)), Constraint::Length(column_width(1)), Constraint::Min(0)];
+
+    let area = popup_area(area, 90, rows.len() as u16 + 3);
+    // Borders and the header take three rows.
+    let max_scroll = rows.len().saturating_sub(area.height.saturating_sub(3) as usize);
+    let scroll = scroll.min(max_scroll);
+
+    let close = keymap.hints(KeyContext::Help, &[(Command::Back, "close")]).join("");
+    let table = Table::new(rows.into_iter().map(|row| Row::new(row.map(Cell::from))), widths)
+        .header(Row::new(["keys", "command", ""]).style(Style::default().fg(theme.muted).add_modifier(Modifier::BOLD)))
+        .column_spacing(3)
+        .block(Block::bordered().title("[help]").title_bottom(close));
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(table, area, &mut TableState::default().with_offset(scroll));
+    if max_scroll > 0 {
+        let mut scrollbar = ScrollbarState::new(max_scroll).position(scroll);
+        frame.render_stateful_widget(Scrollbar::new(ScrollbarOrientation::VerticalRight), area.inner(Margin::new(0, 1)), &mut scrollbar);
+    }
+    scroll
+}
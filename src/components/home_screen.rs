@@ -1,132 +1,337 @@
 use crate::anki_importer;
+use crate::backup::{self, Backup};
 use crate::components::title;
-use std::collections::HashSet;
+use crate::csv;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 use color_eyre::Result;
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
 use deck_panel::InsertNoteState;
 use ratatui::{prelude::*, widgets::*};
 use tokio::sync::mpsc::UnboundedSender;
+use tracing::error;
 use uuid::Uuid;
 
 use crate::action::{Action, Screen};
+use crate::components::utils::{self, move_selection};
+use crate::config::{Config, Theme};
+use crate::keymap::{Command, Hint, KeyContext, Keymap};
 use crate::models::collection::Collection;
 use crate::models::deck::Deck;
+use crate::models::due::{DueCounts, counts_change_at, deck_counts};
+use crate::models::filtered_deck::FilteredDeck;
+use crate::models::note::Note;
+use crate::models::operation::Operation;
+use crate::models::schedule::Scheduler;
+use crate::models::streak::{Goal, Progress};
+use crate::models::tag::{TagNode, tag_tree};
+use crate::time;
 
+mod backups;
 mod command_bar;
-mod deck_panel;
-mod input_state;
+pub(crate) mod deck_panel;
+mod deletion;
 
 static DECK_SYMBOL: &str = "";
 static CARD_SYMBOL: &str = "";
 static COLLAPSED_SYMBOL: &str = "";
 static EXPANDED_SYMBOL: &str = "";
+static FILTERED_SYMBOL: &str = "";
 static CURSOR: &str = "█";
 static INPUT_PROMPT: &str = ">> ";
+const DECK_SEPARATOR: char = ':';
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
 
 #[derive(Clone)]
 enum Mode {
     Normal(Option<Uuid>),
+    /// A filtered deck is selected in the deck list.
+    Filtered(Uuid),
+    Notes(Uuid),
     InsertDeck(Uuid, String),
-    InsertNote(InsertNoteState),
+    /// Typing the search of a new filtered deck, with why the last one was rejected.
+    InsertFilteredDeck(String, Option<String>),
+    RenameDeck(Uuid, String),
+    InsertNote(Box<InsertNoteState>),
+    ConfirmDelete(Uuid),
+    Trash,
+    Backups(Vec<Backup>),
+    Tags,
 }
 
 enum Options {
-    DeckItem(Uuid),
+    /// A deck and how deeply it is nested.
+    DeckItem(Uuid, u16),
+    FilteredDeck(Uuid),
     AddToItem(Uuid),
 }
 
+#[derive(Clone)]
+enum Clipboard {
+    Deck(Uuid),
+    Notes(Vec<Uuid>),
+}
+
+/// What the home screen counts from the whole collection, which holds until the collection changes
+/// or until `until`, when learning steps come due or a new study day starts.
+struct Counted {
+    until: i64,
+    deck_counts: HashMap<Uuid, DueCounts>,
+    progress: Progress,
+}
+
 pub struct HomeScreen {
     _tx: UnboundedSender<Action>,
+    backup_dir: PathBuf,
     state: ListState,
+    note_state: TableState,
+    trash_state: ListState,
+    backup_state: ListState,
+    tag_state: ListState,
     num_options: usize,
     options: Vec<Options>,
     expanded: HashSet<Uuid>,
+    marked_notes: HashSet<Uuid>,
+    clipboard: Option<Clipboard>,
+    /// The tags of the collection as a tree, found again along with `counted`.
+    tags: Vec<TagNode>,
+    /// Only notes with this tag are listed and practiced, when there is one.
+    tag_filter: Option<String>,
     mode: Mode,
+    theme: Theme,
+    keymap: Keymap,
+    /// For the daily limits of the due counts of each deck.
+    scheduler: Scheduler,
+    /// The daily goal shown in the title.
+    goal: Goal,
+    /// What was counted from the whole collection, dropped when the collection changes.
+    counted: Option<Counted>,
+    /// Where the deck list, the deck panel and the command bar's commands were last drawn, for
+    /// finding what was clicked.
+    deck_list_area: Rect,
+    deck_panel_area: Rect,
+    tag_list_area: Rect,
+    command_bar: Vec<(Rect, Command)>,
+    /// The deck or note clicked last, to tell when it is clicked again as a double click.
+    last_click: Option<(Instant, Uuid)>,
 }
 
 impl HomeScreen {
-    pub fn new(tx: UnboundedSender<Action>) -> Self {
-        Self { _tx: tx, state: ListState::default(), num_options: 0, expanded: HashSet::new(), options: Vec::new(), mode: Mode::Normal(None) }
+    pub fn new(tx: UnboundedSender<Action>, backup_dir: PathBuf, config: &Config) -> Self {
+        Self {
+            _tx: tx,
+            backup_dir,
+            state: ListState::default(),
+            note_state: TableState::default(),
+            trash_state: ListState::default(),
+            backup_state: ListState::default(),
+            tag_state: ListState::default(),
+            num_options: 0,
+            expanded: HashSet::new(),
+            marked_notes: HashSet::new(),
+            clipboard: None,
+            tags: Vec::new(),
+            tag_filter: None,
+            options: Vec::new(),
+            mode: Mode::Normal(None),
+            theme: config.theme,
+            keymap: config.keymap.clone(),
+            scheduler: config.scheduler.clone(),
+            goal: config.goal.clone(),
+            counted: None,
+            deck_list_area: Rect::default(),
+            deck_panel_area: Rect::default(),
+            tag_list_area: Rect::default(),
+            command_bar: Vec::new(),
+            last_click: None,
+        }
+    }
+
+    pub fn key_context(&self) -> Option<KeyContext> {
+        key_context(&self.mode)
+    }
+
+    /// Drops what was counted from the collection, to count it again when next drawn.
+    pub fn collection_changed(&mut self) {
+        self.counted = None;
     }
 
-    pub fn update(&mut self, collection: &mut Collection, action: Action) -> Result<Option<Action>> {
+    /// The commands offered in the current mode, unless text is being typed.
+    pub fn hints(&self) -> Option<(KeyContext, Vec<Hint>)> {
+        self.key_context().map(|context| (context, command_bar::hints(&self.mode, self.clipboard.as_ref())))
+    }
+
+    pub fn update(&mut self, collection: &Collection, action: Action) -> Result<Option<Action>> {
+        if let Action::CutNotes(notes) = action {
+            self.marked_notes.clear();
+            self.clipboard = Some(Clipboard::Notes(notes));
+            self.mode = self.browse_mode(collection);
+            return Ok(None);
+        }
         match &self.mode {
             Mode::Normal(_) => self.update_normal(collection, action),
+            Mode::Filtered(uuid) => self.update_filtered(collection, action, *uuid),
+            Mode::Notes(uuid) => self.update_notes(collection, action, *uuid),
             Mode::InsertDeck(uuid, input) => self.update_insert(collection, action, *uuid, input.clone()),
+            Mode::InsertFilteredDeck(input, _) => self.update_insert_filtered(collection, action, input.clone()),
+            Mode::RenameDeck(uuid, input) => self.update_rename(collection, action, *uuid, input.clone()),
+            Mode::ConfirmDelete(uuid) => self.update_confirm_delete(collection, action, *uuid),
+            Mode::Trash => self.update_trash(collection, action),
+            Mode::Backups(backups) => self.update_backups(collection, action, backups.clone()),
+            Mode::Tags => self.update_tags(collection, action),
             Mode::InsertNote(state) => {
-                let state = deck_panel::update_deck_panel_note_insert(action, state.clone(), self.get_selected_deck_mut(collection).unwrap());
+                let Some(deck) = self.get_selected_deck(collection) else {
+                    self.mode = Mode::Normal(None);
+                    return Ok(None);
+                };
+                if action == Action::Command(Command::OpenInEditor) {
+                    return Ok(Some(Action::EditExternally(state.fields())));
+                }
+                let (state, operation) = deck_panel::update_deck_panel_note_insert(
+                    action,
+                    (**state).clone(),
+                    collection,
+                    &deck,
+                    deck_panel::editor_width(self.deck_panel_area),
+                );
                 if state.completed {
-                    self.mode = Mode::Normal(self.get_selected_deck(collection).map(|d| d.uuid));
-                    Ok(Some(Action::Save))
+                    self.mode = match state.editing {
+                        Some(_) => Mode::Notes(deck.uuid),
+                        None => Mode::Normal(Some(deck.uuid)),
+                    };
+                    Ok(operation.map(Action::Commit))
                 } else {
-                    self.mode = Mode::InsertNote(state);
+                    self.mode = Mode::InsertNote(Box::new(state));
                     Ok(None)
                 }
             }
         }
     }
-    pub fn update_normal(&mut self, collection: &mut Collection, action: Action) -> Result<Option<Action>> {
+    pub fn update_normal(&mut self, collection: &Collection, action: Action) -> Result<Option<Action>> {
         match action {
-            Action::Char('n') => {
-                if self.get_selected_deck(collection).is_some() {
-                    self.mode = Mode::InsertNote(InsertNoteState::new());
-                }
+            Action::Command(Command::NewNote) if self.get_selected_deck(collection).is_some() => {
+                self.mode = Mode::InsertNote(Box::new(InsertNoteState::new()));
             }
-            Action::Char('s') => {
+            Action::Command(Command::FocusNotes) => self.focus_notes(collection),
+            Action::Command(Command::NewSubdeck) => {
                 let parent_uuid = if let Some(deck) = self.get_selected_deck(collection) { deck.uuid } else { collection.uuid };
                 if !self.expanded.contains(&parent_uuid) {
                     self.expanded.insert(parent_uuid);
                 }
                 self.mode = Mode::InsertDeck(parent_uuid, String::new());
             }
-            Action::Char('a') => {
+            Action::Command(Command::NewDeck) => {
                 self.mode = Mode::InsertDeck(collection.uuid, String::new());
             }
-            Action::Char('i') => {
+            Action::Command(Command::NewFilteredDeck) => {
+                self.mode = Mode::InsertFilteredDeck(String::new(), None);
+            }
+            Action::Command(Command::Import) => {
                 if let Some(path) = rfd::FileDialog::new()
                     .add_filter("Anki Package", &["apkg"])
+                    .add_filter("CSV", &["csv"])
                     .set_directory(dirs::download_dir().unwrap_or_else(|| std::env::home_dir().unwrap()))
                     .pick_file()
                 {
-                    let deck = anki_importer::load_from_anki_package(path.clone());
-                    collection.add_deck(deck);
-                    return Ok(Some(Action::Save));
-                }
-            }
-            Action::Char('q') => return Ok(Some(Action::Quit)),
-            Action::Char('D') => {
-                if self.get_selected_deck(collection).is_some() {
-                    if let Some(selected) = self.state.selected() {
-                        if let Options::DeckItem(uuid) = &self.options[selected] {
-                            collection.remove_deck(*uuid);
-                            self.state.select(None);
-                            self.mode = Mode::Normal(None);
-                        }
-                    }
+                    let deck = match path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("csv")) {
+                        true => match csv::load_from_csv(path) {
+                            Ok(deck) => deck,
+                            Err(err) => {
+                                error!("Failed to import: {err:#}");
+                                return Ok(None);
+                            }
+                        },
+                        false => anki_importer::load_from_anki_package(path.clone()),
+                    };
+                    return Ok(Operation::add_deck(collection, collection.uuid, deck).map(Action::Commit));
                 }
-                return Ok(Some(Action::Save));
             }
-            Action::Up | Action::Down => {
-                update_list_selection(action, &mut self.state, self.num_options);
-                self.mode = Mode::Normal(self.get_selected_deck(collection).map(|d| d.uuid));
+            Action::Command(Command::Export) => {
+                if let Some(deck) = self.get_selected_deck(collection)
+                    && let Some(path) = rfd::FileDialog::new()
+                        .add_filter("CSV", &["csv"])
+                        .set_directory(dirs::download_dir().unwrap_or_else(|| std::env::home_dir().unwrap()))
+                        .set_file_name(format!("{}.csv", deck.name))
+                        .save_file()
+                    && let Err(err) = csv::export_deck(&deck, &path)
+                {
+                    error!("Failed to export {}: {err:#}", deck.name);
+                }
+            }
+            Action::Command(Command::Tags) => {
+                self.tags = tag_tree(&collection.get_tags());
+                if !self.tags.is_empty() {
+                    self.tag_state.select(Some(self.tag_state.selected().unwrap_or(0).min(self.tags.len() - 1)));
+                    self.mode = Mode::Tags;
+                }
             }
-            Action::Space => {
+            Action::Command(Command::Rename) => {
+                if let Some(deck) = self.get_selected_deck(collection) {
+                    self.mode = Mode::RenameDeck(deck.uuid, deck.name);
+                }
+            }
+            Action::Command(Command::Cut) => {
+                if let Some(deck) = self.get_selected_deck(collection) {
+                    self.clipboard = Some(Clipboard::Deck(deck.uuid));
+                }
+            }
+            Action::Command(Command::Paste) => {
+                if let Some(deck) = self.get_selected_deck(collection) {
+                    return Ok(self.paste(collection, deck.uuid));
+                }
+            }
+            Action::Command(Command::PasteTopLevel) => {
+                if let Some(Clipboard::Deck(_)) = self.clipboard {
+                    return Ok(self.paste(collection, collection.uuid));
+                }
+            }
+            Action::Command(Command::Back) => self.clipboard = None,
+            Action::Command(Command::Delete) => {
+                if self.get_selected_deck(collection).is_some()
+                    && let Some(selected) = self.state.selected()
+                    && let Options::DeckItem(uuid, _) = &self.options[selected]
+                {
+                    self.mode = Mode::ConfirmDelete(*uuid);
+                }
+            }
+            Action::Command(Command::Profiles) => return Ok(Some(Action::Screen(Screen::Profiles))),
+            Action::Command(Command::Browse) => return Ok(Some(Action::Screen(Screen::Browser))),
+            Action::Command(Command::Stats) => {
+                return Ok(Some(Action::Screen(Screen::Stats(self.get_selected_deck(collection).map(|deck| deck.uuid)))));
+            }
+            Action::Command(Command::Trash) => {
+                self.trash_state.select(if collection.get_trash().is_empty() { None } else { Some(0) });
+                self.mode = Mode::Trash;
+            }
+            Action::Command(Command::Backups) => {
+                let backups = backup::list(&self.backup_dir)?;
+                self.backup_state.select(if backups.is_empty() { None } else { Some(0) });
+                self.mode = Mode::Backups(backups);
+            }
+            Action::Command(command) if command.moves_selection() => {
+                self.state.select(move_selection(command, self.state.selected(), self.num_options));
+                self.note_state.select(None);
+                self.mode = self.browse_mode(collection);
+            }
+            Action::Command(Command::ToggleExpand) => {
                 if let Some(selected) = self.state.selected() {
                     match self.options[selected] {
-                        Options::DeckItem(uuid) => {
+                        Options::DeckItem(uuid, _) => {
                             if self.expanded.contains(&uuid) {
                                 self.expanded.remove(&uuid);
                             } else {
                                 self.expanded.insert(uuid);
                             }
                         }
-                        Options::AddToItem(_) => {}
+                        Options::FilteredDeck(_) | Options::AddToItem(_) => {}
                     }
                 }
             }
-            Action::Enter => {
+            Action::Command(Command::Practice) => {
                 if let Some(selected) = self.get_selected_deck(collection) {
-                    return Ok(Some(Action::Screen(Screen::Practice(selected.uuid))));
+                    return Ok(Some(Action::Screen(Screen::Practice(selected.uuid, self.tag_filter.clone()))));
                 }
             }
             _ => {}
@@ -134,26 +339,363 @@ impl HomeScreen {
         Ok(None)
     }
 
-    fn update_insert(&mut self, collection: &mut Collection, action: Action, uuid: Uuid, input: String) -> Result<Option<Action>> {
+    fn focus_notes(&mut self, collection: &Collection) {
+        if let Some(deck) = self.get_selected_deck(collection) {
+            let num_notes = deck_panel::visible_notes(&deck, self.tag_filter.as_deref()).len();
+            if num_notes > 0 {
+                self.note_state.select(Some(self.note_state.selected().unwrap_or(0).min(num_notes - 1)));
+                self.mode = Mode::Notes(deck.uuid);
+            }
+        }
+    }
+
+    fn update_filtered(&mut self, collection: &Collection, action: Action, uuid: Uuid) -> Result<Option<Action>> {
+        let Some(deck) = collection.find_filtered_deck(uuid) else {
+            self.mode = self.browse_mode(collection);
+            return self.update_normal(collection, action);
+        };
+        let edit = |edited: FilteredDeck| Ok(Operation::edit_filtered_deck(collection, edited).map(Action::Commit));
+        match action {
+            Action::Command(Command::Practice) => return Ok(Some(Action::Screen(Screen::Practice(uuid, None)))),
+            Action::Command(Command::Rebuild) => match deck.gather(collection, time::now()) {
+                Ok(cards) => return edit(FilteredDeck { cards, ..deck.clone() }),
+                Err(err) => error!("Failed to rebuild {}: {err}", deck.name),
+            },
+            Action::Command(Command::Empty) => return edit(FilteredDeck { cards: Vec::new(), ..deck.clone() }),
+            Action::Command(Command::ToggleReschedule) => return edit(FilteredDeck { reschedule: !deck.reschedule, ..deck.clone() }),
+            Action::Command(Command::Delete) => {
+                self.state.select(None);
+                self.mode = Mode::Normal(None);
+                return Ok(Operation::remove_filtered_deck(collection, uuid).map(Action::Commit));
+            }
+            action => return self.update_normal(collection, action),
+        }
+        Ok(None)
+    }
+
+    fn update_tags(&mut self, collection: &Collection, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::Command(command) if command.moves_selection() => {
+                self.tag_state.select(move_selection(command, self.tag_state.selected(), self.tags.len()))
+            }
+            Action::Command(Command::FilterByTag) => {
+                if let Some(node) = self.tag_state.selected().and_then(|selected| self.tags.get(selected)) {
+                    self.tag_filter = match self.tag_filter.as_ref() == Some(&node.tag) {
+                        true => None,
+                        false => Some(node.tag.clone()),
+                    };
+                    self.note_state.select(None);
+                }
+            }
+            Action::Command(Command::Back) => self.mode = self.browse_mode(collection),
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    pub fn handle_mouse_event(&mut self, collection: &Collection, mouse: MouseEvent) -> Option<Action> {
+        let position = Position::new(mouse.column, mouse.row);
+        let browsing = matches!(self.mode, Mode::Normal(_) | Mode::Notes(_) | Mode::Tags);
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if let Some(command) = utils::clicked_command(&self.command_bar, position) {
+                    Some(command.into())
+                } else if browsing && self.deck_list_area.contains(position) {
+                    self.click_deck(position)
+                } else if browsing && self.deck_panel_area.contains(position) {
+                    self.click_note(collection, position)
+                } else if browsing && self.tag_list_area.contains(position) {
+                    self.click_tag(position)
+                } else {
+                    None
+                }
+            }
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown if browsing => {
+                let command = if mouse.kind == MouseEventKind::ScrollUp { Command::Up } else { Command::Down };
+                if self.deck_list_area.contains(position) {
+                    self.mode = self.browse_mode(collection);
+                } else if self.deck_panel_area.contains(position) {
+                    self.focus_notes(collection);
+                }
+                let scrolled = match self.mode {
+                    Mode::Normal(_) => self.deck_list_area.contains(position),
+                    _ => self.deck_panel_area.contains(position),
+                };
+                scrolled.then_some(Action::Command(command))
+            }
+            _ => None,
+        }
+    }
+
+    /// Selects the clicked deck, expanding or collapsing it when its arrow was clicked and practicing
+    /// it on a double click.
+    fn click_deck(&mut self, position: Position) -> Option<Action> {
+        let row = utils::row_at(self.deck_list_area, self.state.offset(), position)?;
+        if let Some(Options::FilteredDeck(uuid)) = self.options.get(row) {
+            let uuid = *uuid;
+            self.state.select(Some(row));
+            self.mode = Mode::Filtered(uuid);
+            return self.double_clicked(uuid).then_some(Action::Command(Command::Practice));
+        }
+        let Some(Options::DeckItem(uuid, depth)) = self.options.get(row) else {
+            return None;
+        };
+        let (uuid, depth) = (*uuid, *depth);
+        self.state.select(Some(row));
+        self.note_state.select(None);
+        self.mode = Mode::Normal(Some(uuid));
+        // The arrow comes after the border and two columns of indentation per level, and may be drawn two columns wide.
+        let arrow = self.deck_list_area.x + 1 + depth * 2;
+        if (arrow..arrow + 2).contains(&position.x) {
+            return Some(Action::Command(Command::ToggleExpand));
+        }
+        self.double_clicked(uuid).then_some(Action::Command(Command::Practice))
+    }
+
+    /// Selects the clicked note of the selected deck, editing it on a double click.
+    fn click_note(&mut self, collection: &Collection, position: Position) -> Option<Action> {
+        let deck = self.get_selected_deck(collection)?;
+        // The table only keeps its scroll position while the notes have focus.
+        let offset = if let Mode::Notes(_) = self.mode { self.note_state.offset() } else { 0 };
+        let row = utils::row_at(self.deck_panel_area, offset, position)?;
+        let note = *deck_panel::visible_notes(&deck, self.tag_filter.as_deref()).get(row)?;
+        self.note_state.select(Some(row));
+        self.mode = Mode::Notes(deck.uuid);
+        self.double_clicked(note.uuid).then_some(Action::Command(Command::Edit))
+    }
+
+    /// Filters by the clicked tag, or shows all notes again when it is the one filtered by.
+    fn click_tag(&mut self, position: Position) -> Option<Action> {
+        let offset = if let Mode::Tags = self.mode { self.tag_state.offset() } else { 0 };
+        let row = utils::row_at(self.tag_list_area, offset, position)?;
+        self.tags.get(row)?;
+        self.tag_state.select(Some(row));
+        self.mode = Mode::Tags;
+        Some(Action::Command(Command::FilterByTag))
+    }
+
+    fn double_clicked(&mut self, uuid: Uuid) -> bool {
+        let double = matches!(self.last_click, Some((at, last)) if last == uuid && at.elapsed() < DOUBLE_CLICK_INTERVAL);
+        self.last_click = if double { None } else { Some((Instant::now(), uuid)) };
+        double
+    }
+
+    fn update_notes(&mut self, collection: &Collection, action: Action, deck_uuid: Uuid) -> Result<Option<Action>> {
+        let Some(deck) = collection.find_deck(deck_uuid) else {
+            self.mode = Mode::Normal(None);
+            return Ok(None);
+        };
+        let notes = deck_panel::visible_notes(deck, self.tag_filter.as_deref());
+        let num_notes = notes.len();
+        let selected_note = self.note_state.selected().and_then(|i| notes.get(i)).cloned().cloned();
+        match action {
+            Action::Command(command) if command.moves_selection() => {
+                self.note_state.select(move_selection(command, self.note_state.selected(), num_notes))
+            }
+            Action::Command(Command::Edit) => {
+                if let Some(note) = selected_note {
+                    self.mode = Mode::InsertNote(Box::new(InsertNoteState::from_note(&note)));
+                }
+            }
+            Action::Command(Command::NewNote) => {
+                self.mode = Mode::InsertNote(Box::new(InsertNoteState::new()));
+            }
+            Action::Command(Command::Mark) => {
+                if let Some(note) = selected_note
+                    && !self.marked_notes.remove(&note.uuid)
+                {
+                    self.marked_notes.insert(note.uuid);
+                }
+            }
+            Action::Command(Command::Cut) => {
+                let mut notes: Vec<Uuid> = deck.get_notes().iter().map(|n| n.uuid).filter(|uuid| self.marked_notes.contains(uuid)).collect();
+                if notes.is_empty()
+                    && let Some(note) = selected_note
+                {
+                    notes.push(note.uuid);
+                }
+                if !notes.is_empty() {
+                    self.marked_notes.clear();
+                    self.clipboard = Some(Clipboard::Notes(notes));
+                    self.mode = Mode::Normal(Some(deck_uuid));
+                }
+            }
+            Action::Command(Command::CycleType) => {
+                if let Some(note) = selected_note {
+                    return Ok(Operation::edit_note(collection, Note { note_type: note.note_type.next(), ..note }).map(Action::Commit));
+                }
+            }
+            Action::Command(Command::Delete) => {
+                if let Some(note) = selected_note {
+                    if num_notes <= 1 {
+                        self.note_state.select(None);
+                        self.mode = Mode::Normal(Some(deck_uuid));
+                    } else {
+                        self.note_state.select(self.note_state.selected().map(|i| i.min(num_notes - 2)));
+                    }
+                    return Ok(Operation::remove_note(collection, note.uuid).map(Action::Commit));
+                }
+            }
+            Action::Command(Command::Back) => {
+                self.marked_notes.clear();
+                self.mode = Mode::Normal(Some(deck_uuid));
+            }
+            _ => {}
+        };
+        Ok(None)
+    }
+
+    fn update_confirm_delete(&mut self, collection: &Collection, action: Action, uuid: Uuid) -> Result<Option<Action>> {
+        match action {
+            Action::Command(Command::Confirm) => {
+                if let Some(Clipboard::Deck(cut)) = self.clipboard
+                    && collection.find_deck(uuid).and_then(|deck| deck.find_deck(cut)).is_some()
+                {
+                    self.clipboard = None;
+                }
+                self.state.select(None);
+                self.mode = Mode::Normal(None);
+                return Ok(Operation::delete_deck(collection, uuid).map(Action::Commit));
+            }
+            Action::Command(Command::Back) => self.mode = Mode::Normal(Some(uuid)),
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn update_trash(&mut self, collection: &Collection, action: Action) -> Result<Option<Action>> {
+        let num_deleted = collection.get_trash().len();
+        match action {
+            Action::Command(command) if command.moves_selection() => {
+                self.trash_state.select(move_selection(command, self.trash_state.selected(), num_deleted))
+            }
+            Action::Command(Command::Restore) => {
+                // The trash is listed most recently deleted first.
+                if let Some(selected) = self.trash_state.selected()
+                    && selected < num_deleted
+                {
+                    let uuid = collection.get_trash()[num_deleted - 1 - selected].deck.uuid;
+                    self.state.select(None);
+                    self.mode = Mode::Normal(None);
+                    return Ok(Operation::restore_deck(collection, uuid).map(Action::Commit));
+                }
+            }
+            Action::Command(Command::Back) => self.mode = self.browse_mode(collection),
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn update_backups(&mut self, collection: &Collection, action: Action, backups: Vec<Backup>) -> Result<Option<Action>> {
+        match action {
+            Action::Command(command) if command.moves_selection() => {
+                self.backup_state.select(move_selection(command, self.backup_state.selected(), backups.len()))
+            }
+            Action::Command(Command::Restore) => {
+                if let Some(backup) = self.backup_state.selected().and_then(|selected| backups.get(selected)) {
+                    self.state.select(None);
+                    self.mode = Mode::Normal(None);
+                    return Ok(Some(Action::RestoreBackup(backup.path.clone())));
+                }
+            }
+            Action::Command(Command::Back) => self.mode = self.browse_mode(collection),
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn paste(&mut self, collection: &Collection, target: Uuid) -> Option<Action> {
+        let operation = match self.clipboard.take()? {
+            Clipboard::Deck(uuid) => {
+                let operation = Operation::move_deck(collection, uuid, target);
+                if operation.is_some() {
+                    self.expanded.insert(target);
+                } else {
+                    self.clipboard = Some(Clipboard::Deck(uuid));
+                }
+                operation
+            }
+            Clipboard::Notes(notes) => Operation::move_notes(collection, &notes, target),
+        }?;
+        self.state.select(None);
+        self.note_state.select(None);
+        self.mode = Mode::Normal(None);
+        Some(Action::Commit(operation))
+    }
+
+    fn update_insert(&mut self, collection: &Collection, action: Action, uuid: Uuid, input: String) -> Result<Option<Action>> {
         match action {
             Action::Space => self.mode = Mode::InsertDeck(uuid, input + " "),
+            Action::Char(DECK_SEPARATOR) => {}
             Action::Char(c) => self.mode = Mode::InsertDeck(uuid, input + &c.to_string()),
+            Action::Paste(text) => self.mode = Mode::InsertDeck(uuid, input + &deck_name(&text)),
             Action::Backspace => {
-                self.mode = Mode::InsertDeck(uuid, input[..input.len().saturating_sub(1)].to_string());
+                let mut input = input;
+                input.pop();
+                self.mode = Mode::InsertDeck(uuid, input);
             }
             Action::Enter => {
                 if !input.is_empty() {
                     self.state.select(None);
                     self.mode = Mode::Normal(None);
-                    collection.add_deck_to(uuid, Deck::new(input));
-                    return Ok(Some(Action::Save));
+                    return Ok(Operation::add_deck(collection, uuid, Deck::new(input)).map(Action::Commit));
                 }
                 self.state.select(None);
                 self.mode = Mode::Normal(None);
             }
             Action::Esc => {
-                self.mode = Mode::Normal(self.get_selected_deck(collection).map(|d| d.uuid));
+                self.mode = self.browse_mode(collection);
+            }
+            _ => {}
+        };
+        Ok(None)
+    }
+
+    fn update_rename(&mut self, collection: &Collection, action: Action, uuid: Uuid, mut input: String) -> Result<Option<Action>> {
+        match action {
+            Action::Space => self.mode = Mode::RenameDeck(uuid, input + " "),
+            Action::Char(DECK_SEPARATOR) => {}
+            Action::Char(c) => self.mode = Mode::RenameDeck(uuid, input + &c.to_string()),
+            Action::Paste(text) => self.mode = Mode::RenameDeck(uuid, input + &deck_name(&text)),
+            Action::Backspace => {
+                input.pop();
+                self.mode = Mode::RenameDeck(uuid, input);
+            }
+            Action::Enter => {
+                self.mode = Mode::Normal(Some(uuid));
+                let name = input.trim();
+                if !name.is_empty() {
+                    return Ok(Operation::rename_deck(collection, uuid, name.to_string()).map(Action::Commit));
+                }
+            }
+            Action::Esc => self.mode = Mode::Normal(Some(uuid)),
+            _ => {}
+        };
+        Ok(None)
+    }
+
+    fn update_insert_filtered(&mut self, collection: &Collection, action: Action, mut input: String) -> Result<Option<Action>> {
+        match action {
+            Action::Space => self.mode = Mode::InsertFilteredDeck(input + " ", None),
+            Action::Char(c) => self.mode = Mode::InsertFilteredDeck(input + &c.to_string(), None),
+            Action::Paste(text) => self.mode = Mode::InsertFilteredDeck(input + &text.replace('\n', " "), None),
+            Action::Backspace => {
+                input.pop();
+                self.mode = Mode::InsertFilteredDeck(input, None);
             }
+            Action::Enter => {
+                let query = input.trim().to_string();
+                let deck = FilteredDeck::new(if query.is_empty() { "*".to_string() } else { query.clone() }, query);
+                match deck.gather(collection, time::now()) {
+                    Ok(cards) => {
+                        self.state.select(None);
+                        self.mode = Mode::Normal(None);
+                        return Ok(Some(Action::Commit(Operation::add_filtered_deck(collection, FilteredDeck { cards, ..deck }))));
+                    }
+                    Err(error) => self.mode = Mode::InsertFilteredDeck(input, Some(error)),
+                }
+            }
+            Action::Esc => self.mode = self.browse_mode(collection),
             _ => {}
         };
         Ok(None)
@@ -167,11 +709,13 @@ impl HomeScreen {
         }
     }
 
+    #[allow(clippy::collapsible_if)]
     fn get_selected_deck(&self, collection: &Collection) -> Option<Deck> {
         if let Some(selected) = self.state.selected() {
             if selected < self.options.len() {
                 match &self.options[selected] {
-                    Options::DeckItem(uuid) => return collection.find_deck(*uuid).cloned(),
+                    Options::DeckItem(uuid, _) => return collection.find_deck(*uuid).cloned(),
+                    Options::FilteredDeck(_) => return None,
                     Options::AddToItem(uuid) => return collection.find_deck(*uuid).cloned(),
                 }
             }
@@ -179,18 +723,22 @@ impl HomeScreen {
         None
     }
 
-    fn get_selected_deck_mut<'a>(&mut self, collection: &'a mut Collection) -> Option<&'a mut Deck> {
-        if let Some(selected) = self.state.selected() {
-            if selected < self.options.len() {
-                match &self.options[selected] {
-                    Options::DeckItem(uuid) => return collection.find_deck_mut(*uuid),
-                    Options::AddToItem(uuid) => return collection.find_deck_mut(*uuid),
-                }
-            }
+    fn get_selected_filtered_deck<'a>(&self, collection: &'a Collection) -> Option<&'a FilteredDeck> {
+        match self.state.selected().and_then(|selected| self.options.get(selected)) {
+            Some(Options::FilteredDeck(uuid)) => collection.find_filtered_deck(*uuid),
+            _ => None,
         }
-        None
     }
 
+    /// The mode for browsing the deck list with the current selection.
+    fn browse_mode(&self, collection: &Collection) -> Mode {
+        match self.get_selected_filtered_deck(collection) {
+            Some(deck) => Mode::Filtered(deck.uuid),
+            None => Mode::Normal(self.get_selected_deck(collection).map(|deck| deck.uuid)),
+        }
+    }
+
+    #[allow(clippy::collapsible_if)]
     fn build_deck_list_items(&self, collection: &Collection, parent_uuid: Uuid, _depth: u32) -> (Vec<ListItem<'static>>, Vec<Options>) {
         let decks = match collection.find_deck(parent_uuid) {
             Some(deck) => deck.get_subdecks(),
@@ -209,8 +757,20 @@ impl HomeScreen {
         for deck in decks.iter() {
             let deck_expanded = self.expanded.contains(&deck.uuid);
             let header = spacing.clone() + &(if deck_expanded { EXPANDED_SYMBOL.to_string() } else { COLLAPSED_SYMBOL.to_string() });
-            deck_items.push(ListItem::new(build_deck_label(deck, header)));
-            options.push(Options::DeckItem(deck.uuid));
+            let item = match &self.mode {
+                Mode::RenameDeck(uuid, input) if *uuid == deck.uuid => ListItem::new(Text::from(header + " " + INPUT_PROMPT + input + CURSOR)),
+                _ => ListItem::new(build_deck_label(
+                    deck,
+                    header,
+                    self.counted.as_ref().and_then(|counted| counted.deck_counts.get(&deck.uuid).copied()).unwrap_or_default(),
+                    &self.theme,
+                )),
+            };
+            deck_items.push(match self.clipboard {
+                Some(Clipboard::Deck(uuid)) if uuid == deck.uuid => item.style(Style::default().fg(self.theme.muted).add_modifier(Modifier::ITALIC)),
+                _ => item,
+            });
+            options.push(Options::DeckItem(deck.uuid, _depth as u16));
             if deck_expanded {
                 let (d_items, o_items) = self.build_deck_list_items(collection, deck.uuid, _depth + 1);
                 deck_items.extend(d_items);
@@ -227,61 +787,158 @@ impl HomeScreen {
     }
 
     pub fn draw(&mut self, collection: &Collection, frame: &mut Frame, area: Rect) -> Result<()> {
-        let (decks, options) = self.build_deck_list_items(collection, collection.uuid, 0);
+        let (days, now) = (self.scheduler.days(), time::now());
+        if self.counted.as_ref().is_none_or(|counted| now >= counted.until) {
+            self.counted = Some(Counted {
+                until: counts_change_at(collection, &days, now),
+                deck_counts: deck_counts(collection, &self.scheduler, &days, now),
+                progress: self.goal.progress(collection.get_review_log(), &days, now),
+            });
+            self.tags = tag_tree(&collection.get_tags());
+            if self.tag_filter.as_ref().is_some_and(|filter| !self.tags.iter().any(|node| node.tag == *filter)) {
+                self.tag_filter = None;
+            }
+        }
+        let (mut decks, mut options) = self.build_deck_list_items(collection, collection.uuid, 0);
+        for deck in collection.get_filtered_decks() {
+            decks.push(ListItem::new(build_filtered_deck_label(deck)));
+            options.push(Options::FilteredDeck(deck.uuid));
+        }
+        if let Mode::InsertFilteredDeck(input, error) = &self.mode {
+            let mut text = Text::from(INPUT_PROMPT.to_string() + FILTERED_SYMBOL + " " + input + CURSOR);
+            if let Some(error) = error {
+                text.push_line(Line::styled(error.clone(), Style::default().fg(self.theme.warning)));
+            }
+            decks.push(ListItem::new(text));
+            options.push(Options::AddToItem(collection.uuid));
+        }
         self.options = options;
         self.num_options = self.options.len();
-        if matches!(self.mode, Mode::InsertDeck(_, _)) {
+        if matches!(self.mode, Mode::InsertDeck(_, _) | Mode::InsertFilteredDeck(_, _)) {
             self.select_add_item();
         }
 
         let chunks = Layout::vertical([Constraint::Length(7), Constraint::Min(0), Constraint::Length(3)]).split(area);
-        title::draw_title(frame, chunks[0])?;
+        let progress = self.counted.as_ref().map(|counted| counted.progress).unwrap_or_default();
+        title::draw_title_with_goal(frame, chunks[0], &self.goal, progress, &self.theme)?;
 
         let horizontal_chunks = Layout::horizontal(Constraint::from_percentages([25, 75])).split(chunks[1]);
-        deck_panel::draw_deck_panel(
-            frame,
-            horizontal_chunks[1],
-            self.get_selected_deck(collection),
-            if let Mode::InsertNote(state) = &self.mode { Some(state.clone()) } else { None },
-        )?;
-        command_bar::draw_command_bar(frame, chunks[2], self.mode.clone());
-        frame.render_widget(Block::bordered(), horizontal_chunks[0]);
-
-        let list =
-            List::new(decks).highlight_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)).direction(ListDirection::TopToBottom);
-        frame.render_stateful_widget(list.block(Block::bordered().title("[collection]")), horizontal_chunks[0], &mut self.state);
+        let (deck_list_area, tag_list_area) = match self.tags.is_empty() {
+            true => (horizontal_chunks[0], Rect::default()),
+            false => {
+                let sidebar = Layout::vertical(Constraint::from_percentages([65, 35])).split(horizontal_chunks[0]);
+                (sidebar[0], sidebar[1])
+            }
+        };
+        let mut deck_panel_area = horizontal_chunks[1];
+        match (&self.mode, self.get_selected_deck(collection)) {
+            (Mode::Filtered(uuid), _) if let Some(deck) = collection.find_filtered_deck(*uuid) => {
+                deck_panel::draw_filtered_deck_panel(frame, horizontal_chunks[1], deck, collection)
+            }
+            (Mode::InsertNote(state), Some(deck)) => {
+                deck_panel::draw_deck_panel_insert_view(frame, horizontal_chunks[1], deck, (**state).clone(), &self.theme)
+            }
+            (Mode::InsertNote(_), None) => return Err(color_eyre::eyre::eyre!("Error: Cannot insert note into a non-existent deck.")),
+            (mode, deck) => {
+                let [notes_area, forecast_area] = Layout::vertical([Constraint::Min(0), Constraint::Length(8)]).areas(horizontal_chunks[1]);
+                deck_panel::draw_forecast(frame, forecast_area, deck.as_ref(), collection, &self.scheduler.days(), &self.theme);
+                deck_panel::draw_deck_panel_normal_view(
+                    frame,
+                    notes_area,
+                    deck,
+                    if let Mode::Notes(_) = mode { Some(&mut self.note_state) } else { None },
+                    &self.marked_notes,
+                    self.tag_filter.as_deref(),
+                    &self.theme,
+                );
+                deck_panel_area = notes_area;
+            }
+        }
+        self.deck_list_area = deck_list_area;
+        self.deck_panel_area = deck_panel_area;
+        self.tag_list_area = tag_list_area;
+        self.command_bar = command_bar::draw_command_bar(frame, chunks[2], &self.mode, self.clipboard.as_ref(), &self.keymap);
+        frame.render_widget(Block::bordered(), deck_list_area);
+
+        let list = List::new(decks).highlight_style(self.theme.selected()).direction(ListDirection::TopToBottom);
+        frame.render_stateful_widget(list.block(Block::bordered().title("[collection]")), deck_list_area, &mut self.state);
+        if !self.tags.is_empty() {
+            self.draw_tags(frame, tag_list_area);
+        }
+
+        match &self.mode {
+            Mode::ConfirmDelete(uuid) => {
+                if let Some(deck) = collection.find_deck(*uuid) {
+                    deletion::draw_confirm_delete(frame, area, deck, &self.theme, &self.keymap);
+                }
+            }
+            Mode::Trash => deletion::draw_trash(frame, area, collection.get_trash(), &mut self.trash_state, &self.theme),
+            Mode::Backups(backups) => backups::draw_backups(frame, area, backups, &mut self.backup_state, &self.theme),
+            _ => {}
+        }
         Ok(())
     }
+
+    fn draw_tags(&mut self, frame: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .tags
+            .iter()
+            .map(|node| {
+                let item = ListItem::new(Text::from("  ".repeat(node.depth) + "#" + &node.name));
+                match self.tag_filter.as_ref() == Some(&node.tag) {
+                    true => item.style(Style::default().fg(self.theme.highlight)),
+                    false => item,
+                }
+            })
+            .collect();
+        let list = List::new(items).highlight_style(self.theme.selected()).block(Block::bordered().title("[tags]"));
+        // The selection is only shown while the tags have focus, like the notes of the deck panel.
+        match self.mode {
+            Mode::Tags => frame.render_stateful_widget(list, area, &mut self.tag_state),
+            _ => frame.render_widget(list, area),
+        }
+    }
 }
 
-fn update_list_selection(action: Action, state: &mut ListState, num_options: usize) {
-    if state.selected().is_none() {
-        state.select(Some(0));
-        return;
+fn key_context(mode: &Mode) -> Option<KeyContext> {
+    match mode {
+        Mode::Normal(_) | Mode::Filtered(_) => Some(KeyContext::Decks),
+        Mode::Notes(_) => Some(KeyContext::Notes),
+        Mode::ConfirmDelete(_) => Some(KeyContext::ConfirmDelete),
+        Mode::Trash => Some(KeyContext::Trash),
+        Mode::Backups(_) => Some(KeyContext::Backups),
+        Mode::Tags => Some(KeyContext::Tags),
+        Mode::InsertDeck(_, _) | Mode::RenameDeck(_, _) | Mode::InsertFilteredDeck(_, _) | Mode::InsertNote(_) => None,
     }
-    match action {
-        Action::Up => {
-            state.select(Some(if state.selected().unwrap() == 0 { num_options - 1 } else { state.selected().unwrap() - 1 }));
-        }
-        Action::Down => {
-            state.select(Some((state.selected().unwrap() + 1) % num_options));
-        }
-        _ => {}
+}
+
+/// Pasted text as part of a deck name, which is a single line without the separator.
+fn deck_name(text: &str) -> String {
+    text.chars().filter(|c| *c != DECK_SEPARATOR).map(|c| if c == '\n' { ' ' } else { c }).collect()
+}
+
+fn format_count(count: usize, noun: &str) -> String {
+    format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+}
+
+fn format_elapsed(seconds: i64) -> String {
+    match seconds {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", seconds / 60),
+        3600..86400 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
     }
 }
 
-fn build_deck_label(deck: &Deck, header: String) -> Text<'static> {
-    Text::from(
-        header
-            + " "
-            + CARD_SYMBOL
-            + " "
-            + &deck.get_cards().len().to_string()
-            + " "
-            + DECK_SYMBOL
-            + " "
-            + &deck.get_subdecks().len().to_string()
-            + " "
-            + &deck.name.clone(),
-    )
+/// The deck's name after its card and subdeck counts, and then the new, learning and review cards
+/// due today across its subdecks, like Anki's deck list. Counts of zero are muted.
+fn build_deck_label(deck: &Deck, header: String, due: DueCounts, theme: &Theme) -> Line<'static> {
+    let label = format!("{header} {CARD_SYMBOL} {} {DECK_SYMBOL} {} {}", deck.get_cards().len(), deck.get_subdecks().len(), deck.name);
+    let count = |count: usize, color: Color| Span::styled(format!(" {count}"), Style::default().fg(if count == 0 { theme.muted } else { color }));
+    Line::from(vec![Span::raw(label), count(due.new, Color::Blue), count(due.learning, Color::Red), count(due.review, Color::Green)])
+}
+
+fn build_filtered_deck_label(deck: &FilteredDeck) -> Text<'static> {
+    let mode = if deck.reschedule { "" } else { " (preview)" };
+    Text::from(format!("{FILTERED_SYMBOL} {CARD_SYMBOL} {} {}{mode}", deck.cards.len(), deck.name))
 }
@@ -0,0 +1,24 @@
+use ratatui::{
+    layout::Rect,
+    widgets::{Block, Clear, List, ListItem, ListState, Paragraph},
+};
+
+use crate::{backup::Backup, components::utils::popup_area, config::Theme};
+
+use super::format_elapsed;
+
+pub fn draw_backups(frame: &mut ratatui::Frame, area: Rect, backups: &[Backup], state: &mut ListState, theme: &Theme) {
+    let items: Vec<ListItem> = backups
+        .iter()
+        .map(|backup| ListItem::new(format!("{}  ({} KiB)", format_elapsed(backup.seconds_ago()), backup.size.div_ceil(1024))))
+        .collect();
+    let area = popup_area(area, 60, 20);
+    frame.render_widget(Clear, area);
+    let block = Block::bordered().title("[backups]").title_bottom("the current collection is backed up before restoring");
+    if items.is_empty() {
+        frame.render_widget(Paragraph::new("-----").centered().block(block), area);
+        return;
+    }
+    let list = List::new(items).highlight_style(theme.selected());
+    frame.render_stateful_widget(list.block(block), area, state);
+}
@@ -1,39 +1,124 @@
-use ratatui::{
-    layout::Rect,
-    text::{Line, Span},
-    widgets::{Block, Paragraph},
+use ratatui::layout::Rect;
+
+use crate::{
+    components::utils::{self, CommandBar},
+    keymap::{Command, Hint, KeyContext, Keymap},
 };
 
-use super::Mode;
+use super::{Clipboard, Mode, deck_panel::Field, key_context};
 
-pub fn draw_command_bar(frame: &mut ratatui::Frame, area: Rect, mode: Mode) {
-    let commands: Vec<&str> = match mode.clone() {
-        Mode::Normal(Some(_)) => {
-            vec![
-                "<Space> : expand/collapse",
-                "<CR> : practice",
-                "<Up> : up",
-                "<Down> : down",
-                "<n> : +note",
-                "<s> : +subdeck",
-                "<a> : +deck",
-                "<D> : delete",
-                "<q> : quit",
-                "<i> : import",
-            ]
-        }
-        Mode::Normal(None) => {
-            vec!["<Up> : up", "<Down> : down", "<a> : +deck", "<q> : quit", "<i> : import"]
-        }
-        Mode::InsertDeck(_, _) => vec!["<Esc> : cancel", "CR : submit"],
-        Mode::InsertNote(insert_note_state) => match insert_note_state.focused_front {
-            true => vec!["<C-Space> : newline", "<Esc> : cancel", "<CR> : back"],
-            false => vec!["<C-Space> : newline", "<Esc> : cancel", "<CR> : submit", "<tab> : front"],
+/// The commands offered in `mode`, in the order they are shown.
+pub(super) fn hints(mode: &Mode, clipboard: Option<&Clipboard>) -> Vec<Hint> {
+    match mode {
+        Mode::Normal(_) if clipboard.is_some() => match clipboard {
+            Some(Clipboard::Deck(_)) => vec![
+                (Command::Up, "up"),
+                (Command::Down, "down"),
+                (Command::Paste, "paste into"),
+                (Command::PasteTopLevel, "paste top-level"),
+                (Command::Back, "cancel"),
+            ],
+            _ => vec![(Command::Up, "up"), (Command::Down, "down"), (Command::Paste, "paste into"), (Command::Back, "cancel")],
+        },
+        Mode::Normal(Some(_)) => vec![
+            (Command::ToggleExpand, "expand/collapse"),
+            (Command::Practice, "practice"),
+            (Command::Up, "up"),
+            (Command::Down, "down"),
+            (Command::NewNote, "+note"),
+            (Command::FocusNotes, "notes"),
+            (Command::NewSubdeck, "+subdeck"),
+            (Command::NewDeck, "+deck"),
+            (Command::NewFilteredDeck, "+filtered"),
+            (Command::Rename, "rename"),
+            (Command::Cut, "cut"),
+            (Command::Delete, "delete"),
+            (Command::Undo, "undo"),
+            (Command::Redo, "redo"),
+            (Command::Trash, "trash"),
+            (Command::Backups, "backups"),
+            (Command::Profiles, "profiles"),
+            (Command::Browse, "browse"),
+            (Command::Stats, "stats"),
+            (Command::Tags, "tags"),
+            (Command::Quit, "quit"),
+            (Command::Import, "import"),
+            (Command::Export, "export"),
+        ],
+        Mode::Normal(None) => vec![
+            (Command::Up, "up"),
+            (Command::Down, "down"),
+            (Command::NewDeck, "+deck"),
+            (Command::NewFilteredDeck, "+filtered"),
+            (Command::Undo, "undo"),
+            (Command::Redo, "redo"),
+            (Command::Trash, "trash"),
+            (Command::Backups, "backups"),
+            (Command::Profiles, "profiles"),
+            (Command::Browse, "browse"),
+            (Command::Stats, "stats"),
+            (Command::Tags, "tags"),
+            (Command::Quit, "quit"),
+            (Command::Import, "import"),
+        ],
+        Mode::Filtered(_) => vec![
+            (Command::Practice, "practice"),
+            (Command::Up, "up"),
+            (Command::Down, "down"),
+            (Command::Rebuild, "rebuild"),
+            (Command::Empty, "empty"),
+            (Command::ToggleReschedule, "reschedule"),
+            (Command::Delete, "delete"),
+            (Command::NewFilteredDeck, "+filtered"),
+            (Command::Undo, "undo"),
+            (Command::Redo, "redo"),
+            (Command::Quit, "quit"),
+        ],
+        Mode::ConfirmDelete(_) => vec![(Command::Confirm, "delete"), (Command::Back, "cancel")],
+        Mode::Tags => vec![(Command::Up, "up"), (Command::Down, "down"), (Command::FilterByTag, "filter"), (Command::Back, "decks")],
+        Mode::Trash | Mode::Backups(_) => vec![(Command::Up, "up"), (Command::Down, "down"), (Command::Restore, "restore"), (Command::Back, "close")],
+        Mode::Notes(_) => vec![
+            (Command::Up, "up"),
+            (Command::Down, "down"),
+            (Command::Edit, "edit"),
+            (Command::NewNote, "+note"),
+            (Command::CycleType, "type"),
+            (Command::Mark, "mark"),
+            (Command::Cut, "cut"),
+            (Command::Delete, "delete"),
+            (Command::Undo, "undo"),
+            (Command::Back, "decks"),
+        ],
+        Mode::InsertDeck(_, _) | Mode::RenameDeck(_, _) | Mode::InsertFilteredDeck(_, _) | Mode::InsertNote(_) => Vec::new(),
+    }
+}
+
+/// The command bar entries for `mode`. Keys used while typing are fixed, everything else comes
+/// from the keymap.
+fn commands(mode: &Mode, clipboard: Option<&Clipboard>, keymap: &Keymap) -> CommandBar {
+    let typing: Vec<&str> = match mode {
+        Mode::InsertDeck(_, _) | Mode::RenameDeck(_, _) | Mode::InsertFilteredDeck(_, _) => vec!["<Esc> : cancel", "<CR> : submit"],
+        Mode::InsertNote(insert_note_state) => match insert_note_state.focused {
+            Field::Front => vec!["<C-Space> : newline", "<Esc> : cancel", "<CR> : back"],
+            Field::Back => vec!["<C-Space> : newline", "<Esc> : cancel", "<CR> : submit", "<Tab> : tags"],
+            Field::Tags if insert_note_state.can_complete() => vec!["<Esc> : cancel", "<CR> : submit", "<Tab> : complete"],
+            Field::Tags => vec!["<Esc> : cancel", "<CR> : submit", "<Tab> : front"],
         },
+        _ => Vec::new(),
+    };
+    match key_context(mode) {
+        Some(context) => utils::command_bar_hints(keymap, context, &hints(mode, clipboard)),
+        None => {
+            let mut commands = utils::fixed_command_bar(&typing);
+            let editor = (Command::OpenInEditor, "$EDITOR");
+            if let (Mode::InsertNote(_), Some(hint)) = (mode, keymap.hint(KeyContext::Global, &editor)) {
+                commands.insert(1, (Some(editor.0), hint));
+            }
+            commands
+        }
     }
-    .into_iter()
-    .flat_map(|c| [c, "   "])
-    .collect();
-    let line = Line::from(commands.into_iter().map(Span::from).collect::<Vec<_>>());
-    frame.render_widget(Paragraph::new(line).centered().block(Block::default().title("[commands]").borders(ratatui::widgets::Borders::ALL)), area);
+}
+
+pub fn draw_command_bar(frame: &mut ratatui::Frame, area: Rect, mode: &Mode, clipboard: Option<&Clipboard>, keymap: &Keymap) -> Vec<(Rect, Command)> {
+    utils::draw_command_bar(frame, area, &commands(mode, clipboard, keymap))
 }
@@ -1,174 +1,315 @@
+use std::collections::HashSet;
+
 use crate::{
     action::Action,
+    components::editor::{Edit, Editor},
+    config::Theme,
+    external_editor::NoteFields,
     models::{
+        collection::Collection,
         deck::Deck,
+        due::forecast,
+        filtered_deck::FilteredDeck,
         note::{Note, NoteType},
+        operation::Operation,
+        tag::{completions, parse_tags},
     },
+    time::{self, Days},
 };
 use ratatui::{
-    layout::{Constraint, Layout},
+    layout::{Constraint, Layout, Margin},
     prelude::{self, Rect},
+    style::{Modifier, Style},
     text::Text,
-    widgets::{Block, Cell, Paragraph, Row, Table, Wrap},
+    widgets::{Bar, BarChart, BarGroup, Block, Cell, Paragraph, Row, Table, TableState},
 };
+use uuid::Uuid;
+
+use super::INPUT_PROMPT;
+
+/// How many days ahead the forecast panel shows.
+pub const FORECAST_DAYS: usize = 30;
+
+/// How many completions for the tag being typed are shown below the tags field.
+const MAX_SUGGESTIONS: usize = 5;
 
-use super::input_state::InputState;
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Front,
+    Back,
+    Tags,
+}
 
 #[derive(Clone)]
 pub struct InsertNoteState {
-    front: InputState,
-    back: InputState,
-    pub focused_front: bool,
+    front: Editor,
+    back: Editor,
+    tags: Editor,
+    note_type: NoteType,
+    pub editing: Option<Uuid>,
+    pub focused: Field,
     pub completed: bool,
+    /// Why the last edit in the external editor was not applied.
+    error: Option<String>,
+    /// Existing tags that complete the tag being typed, the first of which <Tab> inserts.
+    suggestions: Vec<String>,
 }
 
 impl InsertNoteState {
     pub fn new() -> Self {
-        Self { front: InputState::new(), back: InputState::new(), focused_front: true, completed: false }
+        Self {
+            front: Editor::new(INPUT_PROMPT),
+            back: Editor::new(INPUT_PROMPT),
+            tags: Editor::new(INPUT_PROMPT),
+            note_type: NoteType::Basic,
+            editing: None,
+            focused: Field::Front,
+            completed: false,
+            error: None,
+            suggestions: Vec::new(),
+        }
     }
-}
 
-pub fn draw_deck_panel(
-    frame: &mut ratatui::Frame,
-    area: Rect,
-    deck: Option<Deck>,
-    insert_state: Option<InsertNoteState>,
-) -> color_eyre::eyre::Result<()> {
-    match insert_state {
-        None => {
-            draw_deck_panel_normal_view(frame, area, deck);
+    pub fn from_note(note: &Note) -> Self {
+        Self {
+            front: Editor::with_text(INPUT_PROMPT, note.front.clone()),
+            back: Editor::with_text(INPUT_PROMPT, note.back.clone()),
+            tags: Editor::with_text(INPUT_PROMPT, note.tags.join(" ")),
+            note_type: note.note_type.clone(),
+            editing: Some(note.uuid),
+            focused: Field::Front,
+            completed: false,
+            error: None,
+            suggestions: Vec::new(),
         }
-        Some(insert_sate) => match deck {
-            None => return Err(color_eyre::eyre::eyre!("Error: Cannot insert note into a non-existent deck.")),
-            Some(deck) => draw_deck_panel_insert_view(frame, area, deck, insert_sate),
-        },
     }
-    Ok(())
-}
 
-pub fn draw_deck_panel_insert_view(frame: &mut ratatui::Frame, area: Rect, deck: Deck, insert_state: InsertNoteState) {
-    let sections = Layout::vertical(Constraint::from_percentages([45, 55])).margin(2).split(area);
+    pub fn fields(&self) -> NoteFields {
+        NoteFields { front: self.front.text().to_string(), back: self.back.text().to_string() }
+    }
 
-    let (front_text, back_text) = if insert_state.focused_front {
-        (insert_state.front.input_display(), insert_state.back.get_input())
-    } else {
-        (insert_state.front.get_input(), insert_state.back.input_display())
-    };
+    /// Whether <Tab> would complete the tag being typed rather than move to the next field.
+    pub fn can_complete(&self) -> bool {
+        self.focused == Field::Tags && !self.suggestions.is_empty()
+    }
 
-    if insert_state.focused_front {
-        frame.set_cursor_position(insert_state.front.calculate_cursor_coordinates_wrapped(sections[0]));
-    } else {
-        frame.set_cursor_position(insert_state.back.calculate_cursor_coordinates_wrapped(sections[1]));
+    fn focused(&mut self) -> &mut Editor {
+        match self.focused {
+            Field::Front => &mut self.front,
+            Field::Back => &mut self.back,
+            Field::Tags => &mut self.tags,
+        }
+    }
+
+    fn suggest(&mut self, collection: &Collection) {
+        let word = self.tags.word_before_cursor();
+        self.suggestions = match self.focused == Field::Tags && !word.is_empty() {
+            true => completions(word, &collection.get_tags()).into_iter().take(MAX_SUGGESTIONS).cloned().collect(),
+            false => Vec::new(),
+        };
     }
+}
 
-    let front = Paragraph::new(Text::from(front_text))
-        .block(Block::bordered().title("Front"))
-        .wrap(Wrap { trim: true })
-        .style(if insert_state.focused_front { prelude::Style::default().fg(prelude::Color::Yellow) } else { prelude::Style::default() });
-    let back = Paragraph::new(Text::from(back_text))
-        .block(Block::bordered().title("Back"))
-        .wrap(Wrap { trim: true })
-        .style(if !insert_state.focused_front { prelude::Style::default().fg(prelude::Color::Yellow) } else { prelude::Style::default() });
-    frame.render_widget(Block::bordered().title(format_title(&deck.qualified_name())), area);
-    frame.render_widget(front, sections[0]);
-    frame.render_widget(back, sections[1]);
+/// Width of the text in the note editor's fields when the deck panel is drawn in `area`: inside
+/// the panel's margin and the fields' borders.
+pub fn editor_width(area: Rect) -> u16 {
+    area.width.saturating_sub(6)
+}
+
+pub fn draw_deck_panel_insert_view(frame: &mut ratatui::Frame, area: Rect, deck: Deck, insert_state: InsertNoteState, theme: &Theme) {
+    let sections = Layout::vertical([Constraint::Percentage(45), Constraint::Min(0), Constraint::Length(3)]).margin(2).split(area);
+    let selection_style = prelude::Style::default().add_modifier(Modifier::REVERSED);
+    for (editor, title, section, field) in [
+        (&insert_state.front, "Front", sections[0], Field::Front),
+        (&insert_state.back, "Back", sections[1], Field::Back),
+        (&insert_state.tags, "Tags", sections[2], Field::Tags),
+    ] {
+        let focused = insert_state.focused == field;
+        let inner = section.inner(Margin::new(1, 1));
+        let view = editor.view(editor_width(area), inner.height, selection_style);
+        let block = match field == Field::Tags && focused && !insert_state.suggestions.is_empty() {
+            true => Block::bordered().title(title).title_bottom(format!("<Tab> {}", insert_state.suggestions.join(" "))),
+            false => Block::bordered().title(title),
+        };
+        let field = Paragraph::new(view.text).scroll((view.scroll, 0)).block(block).style(if focused {
+            prelude::Style::default().fg(theme.highlight)
+        } else {
+            prelude::Style::default()
+        });
+        frame.render_widget(field, section);
+        if focused {
+            frame.set_cursor_position((inner.x + view.cursor.0, inner.y + view.cursor.1));
+        }
+    }
+    let title = match insert_state.editing {
+        Some(_) => format_title(&deck.qualified_name()) + &format_title("edit") + &format_title(&insert_state.note_type.to_string()),
+        None => format_title(&deck.qualified_name()) + &format_title(&insert_state.note_type.to_string()),
+    };
+    let block = Block::bordered().title(title);
+    let block = match insert_state.error {
+        Some(error) => block.title_bottom(error).border_style(prelude::Style::default().fg(theme.warning)),
+        None => block,
+    };
+    frame.render_widget(block, area);
 }
 
 fn format_title(title: &str) -> String {
     "[".to_string() + title + "]"
 }
 
-pub fn draw_deck_panel_normal_view(frame: &mut ratatui::Frame, area: Rect, deck: Option<Deck>) {
+pub fn draw_deck_panel_normal_view(
+    frame: &mut ratatui::Frame,
+    area: Rect,
+    deck: Option<Deck>,
+    table_state: Option<&mut TableState>,
+    marked_notes: &HashSet<Uuid>,
+    tag_filter: Option<&str>,
+    theme: &Theme,
+) {
+    let title = |deck: &Deck| match tag_filter {
+        Some(tag) => format_title(&deck.qualified_name()) + &format_title(&format!("#{tag}")),
+        None => format_title(&deck.qualified_name()),
+    };
     match deck {
         None => {
             frame.render_widget(Paragraph::new(Text::from("-----")).block(Block::bordered().title(format_title("*"))), area);
         }
-        Some(deck) if deck.get_notes().is_empty() => {
-            frame.render_widget(Paragraph::new(Text::from("-----")).block(Block::bordered().title(format_title(&deck.qualified_name()))), area);
+        Some(deck) if visible_notes(&deck, tag_filter).is_empty() => {
+            frame.render_widget(Paragraph::new(Text::from("-----")).block(Block::bordered().title(title(&deck))), area);
         }
         Some(deck) => {
-            let rows: Vec<Row> = deck
-                .get_notes()
-                .iter()
+            let rows: Vec<Row> = visible_notes(&deck, tag_filter)
+                .into_iter()
                 .map(|note| {
                     Row::new([
                         Cell::from(Text::from(remove_newlines(note.front.clone()))),
                         Cell::from(Text::from(remove_newlines(note.back.clone()))),
+                        Cell::from(Text::from(note.note_type.to_string())),
+                        Cell::from(Text::from(note.tags.join(" "))),
                     ])
+                    .style(if marked_notes.contains(&note.uuid) {
+                        prelude::Style::default().fg(theme.marked)
+                    } else {
+                        prelude::Style::default()
+                    })
                 })
                 .collect();
 
-            let widths = Constraint::from_percentages([30, 70]);
-            let table = Table::new(rows, widths).column_spacing(3);
+            let widths = Constraint::from_percentages([27, 43, 13, 17]);
+            let table = Table::new(rows, widths).column_spacing(3).row_highlight_style(theme.selected()).block(Block::bordered().title(title(&deck)));
 
-            frame.render_widget(table.block(Block::bordered().title(format_title(&deck.qualified_name()))), area);
+            match table_state {
+                Some(table_state) => frame.render_stateful_widget(table, area, table_state),
+                None => frame.render_widget(table, area),
+            }
         }
     }
 }
 
+/// The cards of a filtered deck with the decks they come from.
+pub fn draw_filtered_deck_panel(frame: &mut ratatui::Frame, area: Rect, deck: &FilteredDeck, collection: &Collection) {
+    let title = format_title(&deck.name) + &format_title(if deck.reschedule { "reschedule" } else { "preview" });
+    let cards = deck.get_cards(collection);
+    if cards.is_empty() {
+        frame.render_widget(Paragraph::new(Text::from("-----")).block(Block::bordered().title(title)), area);
+        return;
+    }
+    let rows: Vec<Row> = cards
+        .into_iter()
+        .map(|card| {
+            let home = collection.find_note_deck(card.note).map(|deck| deck.qualified_name()).unwrap_or_default();
+            Row::new([
+                Cell::from(Text::from(remove_newlines(card.front))),
+                Cell::from(Text::from(remove_newlines(card.back))),
+                Cell::from(Text::from(home)),
+            ])
+        })
+        .collect();
+    let widths = Constraint::from_percentages([35, 45, 20]);
+    frame.render_widget(Table::new(rows, widths).column_spacing(3).block(Block::bordered().title(title)), area);
+}
+
+/// Reviews due on each of the next days for `deck` and its subdecks, or the whole collection, a bar
+/// per day with today first.
+pub fn draw_forecast(frame: &mut ratatui::Frame, area: Rect, deck: Option<&Deck>, collection: &Collection, days: &Days, theme: &Theme) {
+    let cards = match deck {
+        Some(deck) => deck.get_all_cards(),
+        None => collection.get_decks().iter().flat_map(|deck| deck.get_all_cards()).collect(),
+    };
+    let per_day = forecast(&cards, days, time::now(), FORECAST_DAYS);
+    let scope = deck.map_or_else(|| "collection".to_string(), |deck| deck.qualified_name());
+    let title = format_title(&scope)
+        + &format_title(&format!("forecast {FORECAST_DAYS} days"))
+        + &format_title(&format!("{} today, {} total", per_day[0], per_day.iter().sum::<u64>()));
+    let bar_width = (area.width.saturating_sub(2) / FORECAST_DAYS as u16).saturating_sub(1).max(1);
+    let bars: Vec<Bar> =
+        per_day.iter().map(|count| Bar::default().value(*count).text_value(String::new()).style(Style::default().fg(theme.highlight))).collect();
+    let chart = BarChart::default().block(Block::bordered().title(title)).data(BarGroup::default().bars(&bars)).bar_width(bar_width).bar_gap(1);
+    frame.render_widget(chart, area);
+}
+
+/// The notes of `deck` shown in the panel: those with the tag filtered by, or all of them.
+pub fn visible_notes<'a>(deck: &'a Deck, tag_filter: Option<&str>) -> Vec<&'a Note> {
+    deck.get_notes().iter().filter(|note| tag_filter.is_none_or(|tag| note.has_tag(tag))).collect()
+}
+
 fn remove_newlines(str: String) -> String {
     str.replace("\n", " ")
 }
 
-pub fn update_deck_panel_note_insert(action: Action, mut state: InsertNoteState, deck: &mut Deck) -> InsertNoteState {
+pub fn update_deck_panel_note_insert(
+    action: Action,
+    mut state: InsertNoteState,
+    collection: &Collection,
+    deck: &Deck,
+    width: u16,
+) -> (InsertNoteState, Option<Operation>) {
+    let mut operation = None;
+    if matches!(action, Action::Char(_) | Action::Space | Action::Paste(_) | Action::CtrlSpace | Action::Backspace | Action::Edit(_)) {
+        state.error = None;
+    }
     match action {
-        Action::Char(c) => {
-            if state.focused_front {
-                state.front.push(c);
-            } else {
-                state.back.push(c);
-            }
-        }
-        Action::Space => {
-            if state.focused_front {
-                state.front.push(' ');
-            } else {
-                state.back.push(' ');
-            }
-        }
-        Action::Backspace => {
-            if state.focused_front {
-                state.front.pop();
-            } else {
-                state.back.pop();
-            }
-        }
-        Action::CtrlSpace => {
-            if state.focused_front {
-                state.front.push('\n');
-            } else {
-                state.back.push('\n');
-            }
+        Action::EditedExternally(Ok(fields)) => {
+            state.front = Editor::with_text(INPUT_PROMPT, fields.front);
+            state.back = Editor::with_text(INPUT_PROMPT, fields.back);
+            state.error = None;
         }
+        Action::EditedExternally(Err(error)) => state.error = Some(error),
+        Action::Char(c) => state.focused().insert(&c.to_string()),
+        Action::Space => state.focused().insert(" "),
+        Action::Paste(text) => state.focused().insert(&text),
+        Action::CtrlSpace => state.focused().insert("\n"),
+        Action::Backspace => state.focused().edit(Edit::DeleteBackward, width),
+        Action::Edit(edit) => state.focused().edit(edit, width),
         Action::Enter => {
-            if state.focused_front && state.back.get_input().is_empty() {
-                state.focused_front = false;
+            if state.focused == Field::Front && state.back.text().is_empty() {
+                state.focused = Field::Back;
             } else {
-                deck.add_note(Note::new(state.front.get_input(), state.back.get_input(), NoteType::Basic));
+                let (front, back) = (state.front.text().to_string(), state.back.text().to_string());
+                let (note_type, tags) = (state.note_type.clone(), parse_tags(state.tags.text()));
+                operation = match state.editing.and_then(|uuid| deck.find_note(uuid)) {
+                    Some(note) => Operation::edit_note(collection, Note { front, back, note_type, tags, ..note.clone() }),
+                    None => Operation::add_note(collection, deck.uuid, Note { tags, ..Note::new(front, back, note_type) }),
+                };
                 state.completed = true;
             }
         }
+        Action::Tab if state.can_complete() => {
+            let completion = state.suggestions[0].clone();
+            state.tags.complete_word(&completion);
+        }
         Action::Tab => {
-            state.focused_front = !state.focused_front;
+            state.focused = match state.focused {
+                Field::Front => Field::Back,
+                Field::Back => Field::Tags,
+                Field::Tags => Field::Front,
+            };
         }
         Action::Esc => {
             state.completed = true;
         }
-        Action::Right => {
-            if state.focused_front {
-                state.front.cursor_right();
-            } else {
-                state.back.cursor_right();
-            }
-        }
-        Action::Left => {
-            if state.focused_front {
-                state.front.cursor_left();
-            } else {
-                state.back.cursor_left();
-            }
-        }
         _ => {}
     };
-    state
+    state.suggest(collection);
+    (state, operation)
 }
@@ -0,0 +1,63 @@
+use ratatui::{
+    layout::Rect,
+    style::Style,
+    text::{Line, Text},
+    widgets::{Block, Clear, List, ListItem, ListState, Paragraph, Wrap},
+};
+
+use crate::{
+    components::utils::popup_area,
+    config::Theme,
+    keymap::{Command, KeyContext, Keymap},
+    models::{deck::Deck, trash::DeletedDeck},
+};
+
+use super::{format_count, format_elapsed};
+
+pub fn draw_confirm_delete(frame: &mut ratatui::Frame, area: Rect, deck: &Deck, theme: &Theme, keymap: &Keymap) {
+    let text = Text::from(vec![
+        Line::from(format!("delete [{}] ?", deck.qualified_name())),
+        Line::from(""),
+        Line::from(format!(
+            "{}, {}, {}",
+            format_count(deck.get_all_subdecks().len(), "subdeck"),
+            format_count(deck.get_all_notes().len(), "note"),
+            format_count(deck.get_all_cards().len(), "card"),
+        )),
+        Line::from(""),
+        Line::from(keymap.hints(KeyContext::ConfirmDelete, &[(Command::Confirm, "delete"), (Command::Back, "cancel")]).join("   ")),
+    ]);
+    let area = popup_area(area, 60, 7);
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(text)
+            .centered()
+            .wrap(Wrap { trim: true })
+            .block(Block::bordered().title("[delete]").border_style(Style::default().fg(theme.warning))),
+        area,
+    );
+}
+
+pub fn draw_trash(frame: &mut ratatui::Frame, area: Rect, trash: &[DeletedDeck], state: &mut ListState, theme: &Theme) {
+    let items: Vec<ListItem> = trash
+        .iter()
+        .rev()
+        .map(|deleted| {
+            ListItem::new(format!(
+                "{}  ({}, {})  {}",
+                deleted.deck.qualified_name(),
+                format_count(deleted.deck.get_all_notes().len(), "note"),
+                format_count(deleted.deck.get_all_subdecks().len(), "subdeck"),
+                format_elapsed(deleted.seconds_ago()),
+            ))
+        })
+        .collect();
+    let area = popup_area(area, 80, 20);
+    frame.render_widget(Clear, area);
+    if items.is_empty() {
+        frame.render_widget(Paragraph::new("-----").centered().block(Block::bordered().title("[recently deleted]")), area);
+        return;
+    }
+    let list = List::new(items).highlight_style(theme.selected());
+    frame.render_stateful_widget(list.block(Block::bordered().title("[recently deleted]")), area, state);
+}
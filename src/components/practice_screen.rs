@@ -1,23 +1,55 @@
+use std::time::Instant;
+
 use crate::{
     action::{Action, Screen},
-    models::{card::Card, deck::Deck},
+    config::{Config, Theme},
+    keymap::{Command, Hint, KeyContext, Keymap},
+    models::{
+        card::Card,
+        collection::Collection,
+        deck::Deck,
+        due::deck_due_cards,
+        filtered_deck::FilteredDeck,
+        note::Note,
+        operation::Operation,
+        schedule::{CardState, Flag, Grade, Schedule, Scheduler},
+        tag::{LEECH_TAG, MARKED_TAG, toggle_tag},
+    },
+    time,
 };
 use color_eyre::Result;
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
 use ratatui::{
     Frame,
-    layout::{Constraint, Layout, Rect},
+    layout::{Constraint, Layout, Position, Rect},
+    style::Style,
     text::{Line, Span},
     widgets::{Block, Borders, Paragraph},
 };
+use uuid::Uuid;
 
-use super::title;
+use super::{title, utils};
 
 const DIVIDER_TEXT: &str = "\n\n──────────\n\n";
 
 pub struct PracticeScreen {
     cnt: usize,
     cards: Vec<Card>,
+    /// The deck or filtered deck being practiced.
+    deck: Uuid,
+    /// Whether answers change the cards' schedules, which filtered decks can turn off.
+    reschedule: bool,
+    /// Only notes with this tag are practiced, when there is one.
+    tag: Option<String>,
     mode: Mode,
+    shown_at: Instant,
+    scheduler: Scheduler,
+    /// What happened to the last card answered that deserves attention, like it becoming a leech.
+    notice: Option<String>,
+    theme: Theme,
+    keymap: Keymap,
+    /// Where each command in the command bar was drawn, to run it when clicked.
+    command_bar: Vec<(Rect, Command)>,
 }
 
 #[derive(Clone)]
@@ -27,90 +59,272 @@ enum Mode {
     Complete,
 }
 
-impl Default for PracticeScreen {
-    fn default() -> Self {
-        Self { cnt: 0, cards: Vec::new(), mode: Mode::Front }
+impl PracticeScreen {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            cnt: 0,
+            cards: Vec::new(),
+            deck: Uuid::nil(),
+            reschedule: true,
+            tag: None,
+            mode: Mode::Front,
+            shown_at: Instant::now(),
+            scheduler: config.scheduler.clone(),
+            notice: None,
+            theme: config.theme,
+            keymap: config.keymap.clone(),
+            command_bar: Vec::new(),
+        }
     }
-}
 
-impl PracticeScreen {
-    pub fn update(&mut self, deck: &Deck, action: Action) -> Result<Option<Action>> {
+    pub fn update(&mut self, collection: &Collection, action: Action) -> Result<Option<Action>> {
+        if let Action::Screen(Screen::Practice(deck, tag)) = &action {
+            self.deck = *deck;
+            self.tag = tag.clone();
+        }
+        if collection.find_deck(self.deck).is_none() && collection.find_filtered_deck(self.deck).is_none() {
+            return Ok(Some(Action::Screen(Screen::Home)));
+        }
         match action {
-            Action::Screen(Screen::Practice(_)) => self.reset(deck),
-            Action::Char('r') => self.reset(deck),
-            Action::Char('c') => return Ok(Some(Action::Screen(Screen::Home))),
-            Action::Char('q') => return Ok(Some(Action::Quit)),
-            Action::Space => match self.mode {
+            Action::Screen(_) => self.reset(collection),
+            // The deck's schedules changed underneath the session, so rebuild it from what is due now.
+            Action::Undo | Action::Redo => self.reset(collection),
+            Action::Command(Command::Restart) => self.reset(collection),
+            Action::Command(Command::Back) => return Ok(Some(Action::Screen(Screen::Home))),
+            Action::Command(Command::Flip) => match self.mode {
                 Mode::Front => self.mode = Mode::Back,
-                Mode::Back => {
-                    if self.cnt == self.cards.len() - 1 {
-                        self.mode = Mode::Complete;
-                    } else {
-                        self.cnt = self.cnt.wrapping_add(1) % self.cards.len();
-                        self.mode = Mode::Front;
-                    }
-                }
+                Mode::Back => return Ok(self.answer(collection, Grade::Good)),
                 Mode::Complete => {}
             },
+            Action::Command(command @ (Command::Again | Command::Hard | Command::Good | Command::Easy)) if matches!(self.mode, Mode::Back) => {
+                let grade = match command {
+                    Command::Again => Grade::Again,
+                    Command::Hard => Grade::Hard,
+                    Command::Good => Grade::Good,
+                    _ => Grade::Easy,
+                };
+                return Ok(self.answer(collection, grade));
+            }
+            Action::Command(Command::Suspend) if self.is_showing() => {
+                return Ok(self.hide(collection, |schedule| Schedule { suspended: true, ..schedule.clone() }));
+            }
+            Action::Command(Command::Bury) if self.is_showing() => {
+                let until = self.scheduler.days().next_day(time::now());
+                return Ok(self.hide(collection, |schedule| Schedule { buried_until: until, ..schedule.clone() }));
+            }
+            Action::Command(Command::Flag) if self.is_showing() => {
+                let Some(card) = self.current_card(collection) else { return Ok(None) };
+                let flag = Flag::cycle(card.schedule.flag);
+                return Ok(Operation::change_schedules(&[card], |schedule| Schedule { flag, ..schedule.clone() }).map(Action::Commit));
+            }
+            Action::Command(Command::Mark) if self.is_showing() => {
+                let Some(note) = collection.find_note(self.cards[self.cnt].note) else { return Ok(None) };
+                let tags = toggle_tag(&note.tags, MARKED_TAG);
+                return Ok(Operation::edit_note(collection, Note { tags, ..note.clone() }).map(Action::Commit));
+            }
             _ => {}
         }
         Ok(None)
     }
 
-    fn reset(&mut self, deck: &Deck) {
+    pub fn handle_mouse_event(&mut self, mouse: MouseEvent) -> Option<Action> {
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                utils::clicked_command(&self.command_bar, Position::new(mouse.column, mouse.row)).map(Action::from)
+            }
+            _ => None,
+        }
+    }
+
+    /// The commands offered in the current mode.
+    pub fn hints(&self) -> (KeyContext, Vec<Hint>) {
+        (KeyContext::Practice, hints(&self.mode))
+    }
+
+    fn is_showing(&self) -> bool {
+        matches!(self.mode, Mode::Front | Mode::Back)
+    }
+
+    /// The card being shown as it is in `collection` now, with the flag it may have been given since
+    /// the session started.
+    fn current_card(&self, collection: &Collection) -> Option<Card> {
+        let card = &self.cards[self.cnt];
+        collection.find_note(card.note)?.get_cards().into_iter().find(|current| current.ordinal == card.ordinal)
+    }
+
+    /// Keeps the current card out of practice by changing its schedule with `change`, and moves on
+    /// without coming back to it in this session.
+    fn hide(&mut self, collection: &Collection, change: impl Fn(&Schedule) -> Schedule) -> Option<Action> {
+        let card = self.current_card(collection)?;
+        self.skip_later(|other| other.note == card.note && other.ordinal == card.ordinal);
+        self.next();
+        Operation::change_schedules(&[card], change).map(Action::Commit)
+    }
+
+    /// Drops the cards matching `skip` from the rest of the session.
+    fn skip_later(&mut self, skip: impl Fn(&Card) -> bool) {
+        let later = self.cards.split_off(self.cnt + 1);
+        self.cards.extend(later.into_iter().filter(|card| !skip(card)));
+    }
+
+    fn answer(&mut self, collection: &Collection, grade: Grade) -> Option<Action> {
+        let card = self.current_card(collection).unwrap_or_else(|| self.cards[self.cnt].clone());
+        let mut operations = Vec::new();
+        self.notice = None;
+        // Without rescheduling, cards answered again come back later in the same session.
+        let mut again = (grade == Grade::Again).then(|| card.schedule.clone());
+        if self.reschedule {
+            let now = time::now();
+            let duration_ms = self.shown_at.elapsed().as_millis() as u64;
+            let answered = Operation::answer_card(collection, &card, grade, now, duration_ms, &self.scheduler, self.scheduler.days().next_day(now));
+            if answered.leech {
+                let handled = if self.scheduler.suspend_leeches { "tagged and suspended" } else { "tagged" };
+                let front = card.front.lines().next().unwrap_or_default();
+                self.notice = Some(format!("leech: \"{front}\" was forgotten {} times, so it was {handled}", answered.after.lapses));
+            }
+            operations.extend(answered.operations);
+            // Cards still in (re)learning come back later in the same session, unless suspended.
+            let after = answered.after;
+            again = (matches!(after.state, CardState::Learning | CardState::Relearning) && !after.suspended).then_some(after);
+            if self.scheduler.bury_siblings {
+                self.skip_later(|other| other.note == card.note && other.ordinal != card.ordinal);
+            }
+        }
+        match again {
+            Some(schedule) => self.cards.push(Card { schedule, ..card }),
+            // Cards that are done return from a filtered deck to their home deck.
+            None => {
+                if let Some(filtered) = collection.find_filtered_deck(self.deck) {
+                    let cards = filtered.cards.iter().filter(|held| **held != (card.note, card.ordinal)).copied().collect();
+                    operations.extend(Operation::edit_filtered_deck(collection, FilteredDeck { cards, ..filtered.clone() }));
+                }
+            }
+        }
+        self.next();
+        match operations.len() {
+            0 => None,
+            1 => operations.pop().map(Action::Commit),
+            _ => Some(Action::Commit(Operation::Batch(operations))),
+        }
+    }
+
+    fn next(&mut self) {
+        if self.cnt + 1 >= self.cards.len() {
+            self.mode = Mode::Complete;
+        } else {
+            self.cnt += 1;
+            self.mode = Mode::Front;
+            self.shown_at = Instant::now();
+        }
+    }
+
+    fn reset(&mut self, collection: &Collection) {
+        let now = time::now();
         self.cnt = 0;
-        self.mode = Mode::Front;
-        self.cards = deck.get_cards();
+        self.notice = None;
+        (self.cards, self.reschedule) = match (collection.find_filtered_deck(self.deck), collection.find_deck(self.deck)) {
+            // Filtered decks are for cramming, so all of their cards are practiced, due or not.
+            (Some(filtered), _) => {
+                let cards = filtered.get_cards(collection).into_iter().filter(|card| !card.schedule.is_hidden(now)).collect();
+                (cards, filtered.reschedule)
+            }
+            (None, Some(deck)) => (self.due_cards(collection, deck, now), true),
+            (None, None) => (Vec::new(), true),
+        };
+        self.mode = if self.cards.is_empty() { Mode::Complete } else { Mode::Front };
+        self.shown_at = Instant::now();
+    }
+
+    /// The cards of `deck` and its subdecks that are due today, limited to the tag when there is
+    /// one. The daily limits are counted like the deck list does, so the two agree.
+    fn due_cards(&self, collection: &Collection, deck: &Deck, now: i64) -> Vec<Card> {
+        deck_due_cards(collection, deck, &self.scheduler, &self.scheduler.days(), now, &|note| {
+            self.tag.as_deref().is_none_or(|tag| note.has_tag(tag))
+        })
     }
 
-    pub fn draw(&mut self, frame: &mut Frame, area: Rect) -> Result<()> {
-        let card = self.cards[self.cnt].clone();
+    pub fn draw(&mut self, collection: &Collection, frame: &mut Frame, area: Rect) -> Result<()> {
         let chunks = Layout::vertical([Constraint::Length(7), Constraint::Min(0), Constraint::Length(3)]).split(area);
         title::draw_title(frame, chunks[0])?;
         match self.mode {
-            Mode::Front => self.draw_front(card, frame, chunks[1]),
-            Mode::Back => self.draw_back(card, frame, chunks[1]),
+            Mode::Front => self.draw_front(collection, self.cards[self.cnt].clone(), frame, chunks[1]),
+            Mode::Back => self.draw_back(collection, self.cards[self.cnt].clone(), frame, chunks[1]),
             Mode::Complete => self.draw_complete(frame, chunks[1]),
         };
-        draw_command_bar(frame, chunks[2], self.mode.clone());
+        self.command_bar =
+            utils::draw_command_bar(frame, chunks[2], &utils::command_bar_hints(&self.keymap, KeyContext::Practice, &hints(&self.mode)));
         Ok(())
     }
 
-    fn draw_front(&self, card: Card, frame: &mut Frame, area: Rect) {
-        let front = Paragraph::new("\n".to_string() + &card.front + "\n")
-            .centered()
-            .block(Block::default().title(format!("[practice][{}/{}]", self.cnt + 1, self.cards.len())).borders(Borders::ALL));
+    /// The title of the card being shown, with whether its note is marked or a leech and its flag.
+    fn title(&self, collection: &Collection) -> Line<'static> {
+        let mut title = match &self.tag {
+            Some(tag) => format!("[practice][#{tag}][{}/{}]", self.cnt + 1, self.cards.len()),
+            None => format!("[practice][{}/{}]", self.cnt + 1, self.cards.len()),
+        };
+        if let Some(note) = collection.find_note(self.cards[self.cnt].note) {
+            for tag in [MARKED_TAG, LEECH_TAG].into_iter().filter(|tag| note.tags.iter().any(|existing| existing.eq_ignore_ascii_case(tag))) {
+                title += &format!("[{tag}]");
+            }
+        }
+        let mut spans = vec![Span::raw(title)];
+        if let Some(flag) = self.current_card(collection).and_then(|card| card.schedule.flag) {
+            spans.push(Span::styled(format!("[{flag}]"), Style::default().fg(utils::flag_color(flag))));
+        }
+        Line::from(spans)
+    }
+
+    fn draw_front(&self, collection: &Collection, card: Card, frame: &mut Frame, area: Rect) {
+        let front = Paragraph::new("\n".to_string() + &card.front + "\n").centered().block(self.block(self.title(collection)));
         frame.render_widget(front, area);
     }
 
-    fn draw_back(&self, card: Card, frame: &mut Frame<'_>, area: Rect) {
-        let front = Paragraph::new("\n".to_string() + &card.front + DIVIDER_TEXT + &card.back + "\n")
-            .centered()
-            .block(Block::default().title(format!("[practice][{}/{}]", self.cnt + 1, self.cards.len())).borders(Borders::ALL));
+    fn draw_back(&self, collection: &Collection, card: Card, frame: &mut Frame<'_>, area: Rect) {
+        let front =
+            Paragraph::new("\n".to_string() + &card.front + DIVIDER_TEXT + &card.back + "\n").centered().block(self.block(self.title(collection)));
         frame.render_widget(front, area);
     }
 
+    /// The border around the card, with the notice about the last card below it.
+    fn block<'a>(&self, title: impl Into<Line<'a>>) -> Block<'a> {
+        let block = Block::default().title(title).borders(Borders::ALL);
+        match &self.notice {
+            Some(notice) => block.title_bottom(Line::styled(notice.clone(), Style::default().fg(self.theme.warning))),
+            None => block,
+        }
+    }
+
     fn draw_complete(&self, frame: &mut Frame<'_>, area: Rect) {
-        frame.render_widget(
-            Paragraph::new("\ncompleted!\n").centered().block(Block::default().title("[practice][complete]").borders(Borders::ALL)),
-            area,
-        );
+        let text = if self.cards.is_empty() { "\nnothing due!\n" } else { "\ncompleted!\n" };
+        frame.render_widget(Paragraph::new(text).centered().block(self.block("[practice][complete]")), area);
     }
 }
 
-fn draw_command_bar(frame: &mut ratatui::Frame, area: Rect, mode: Mode) {
-    let commands: Vec<&str> = match mode {
-        Mode::Front => {
-            vec!["<Space> : flip", "<c> : collection"]
-        }
-        Mode::Back => {
-            vec!["<Space> : next", "<c> : collection"]
-        }
-        Mode::Complete => vec!["<r> : restart", "<c> : collection"],
+/// The commands offered in `mode`, in the order they are shown.
+fn hints(mode: &Mode) -> Vec<Hint> {
+    match mode {
+        Mode::Front => vec![
+            (Command::Flip, "flip"),
+            (Command::Suspend, "suspend"),
+            (Command::Bury, "bury"),
+            (Command::Flag, "flag"),
+            (Command::Mark, "mark"),
+            (Command::Undo, "undo"),
+            (Command::Back, "collection"),
+        ],
+        Mode::Back => vec![
+            (Command::Again, "again"),
+            (Command::Hard, "hard"),
+            (Command::Good, "good"),
+            (Command::Flip, "good"),
+            (Command::Easy, "easy"),
+            (Command::Suspend, "suspend"),
+            (Command::Bury, "bury"),
+            (Command::Flag, "flag"),
+            (Command::Mark, "mark"),
+            (Command::Undo, "undo"),
+            (Command::Back, "collection"),
+        ],
+        Mode::Complete => vec![(Command::Restart, "restart"), (Command::Undo, "undo"), (Command::Back, "collection")],
     }
-    .into_iter()
-    .flat_map(|c| [c, "   "])
-    .collect();
-    let line = Line::from(commands.into_iter().map(Span::from).collect::<Vec<_>>());
-    frame.render_widget(Paragraph::new(line).centered().block(Block::default().title("[commands]").borders(ratatui::widgets::Borders::ALL)), area);
 }
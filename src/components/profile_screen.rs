@@ -0,0 +1,194 @@
+use color_eyre::Result;
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Layout, Position, Rect},
+    style::Style,
+    widgets::{Block, List, ListItem, ListState, Paragraph},
+};
+use tracing::error;
+
+use crate::{
+    action::{Action, Screen},
+    config::{Config, Theme},
+    keymap::{Command, Hint, KeyContext, Keymap},
+    profile::{Profile, Profiles},
+};
+
+use super::{title, utils};
+
+pub struct ProfileScreen {
+    profiles: Profiles,
+    list: Vec<Profile>,
+    state: ListState,
+    mode: Mode,
+    theme: Theme,
+    keymap: Keymap,
+    list_area: Rect,
+    command_bar: Vec<(Rect, Command)>,
+}
+
+#[derive(Clone)]
+enum Mode {
+    Select,
+    Create(String, Option<String>),
+}
+
+impl ProfileScreen {
+    pub fn new(profiles: Profiles, config: &Config) -> Self {
+        Self {
+            profiles,
+            list: Vec::new(),
+            state: ListState::default(),
+            mode: Mode::Select,
+            theme: config.theme,
+            keymap: config.keymap.clone(),
+            list_area: Rect::default(),
+            command_bar: Vec::new(),
+        }
+    }
+
+    pub fn key_context(&self) -> Option<KeyContext> {
+        match self.mode {
+            Mode::Select => Some(KeyContext::Profiles),
+            Mode::Create(_, _) => None,
+        }
+    }
+
+    pub fn update(&mut self, current: &Profile, action: Action) -> Result<Option<Action>> {
+        match self.mode.clone() {
+            Mode::Select => self.update_select(current, action),
+            Mode::Create(input, _) => self.update_create(action, input),
+        }
+    }
+
+    pub fn handle_mouse_event(&mut self, mouse: MouseEvent) -> Option<Action> {
+        if !matches!(self.mode, Mode::Select) {
+            return None;
+        }
+        let position = Position::new(mouse.column, mouse.row);
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if let Some(command) = utils::clicked_command(&self.command_bar, position) {
+                    return Some(command.into());
+                }
+                let row = utils::row_at(self.list_area, self.state.offset(), position).filter(|row| *row < self.list.len())?;
+                self.state.select(Some(row));
+                None
+            }
+            MouseEventKind::ScrollUp if self.list_area.contains(position) => Some(Action::Command(Command::Up)),
+            MouseEventKind::ScrollDown if self.list_area.contains(position) => Some(Action::Command(Command::Down)),
+            _ => None,
+        }
+    }
+
+    /// The commands offered in the current mode, unless a name is being typed.
+    pub fn hints(&self) -> Option<(KeyContext, Vec<Hint>)> {
+        match self.mode {
+            Mode::Select => Some((
+                KeyContext::Profiles,
+                vec![
+                    (Command::Up, "up"),
+                    (Command::Down, "down"),
+                    (Command::Open, "open"),
+                    (Command::NewProfile, "+profile"),
+                    (Command::Back, "collection"),
+                    (Command::Quit, "quit"),
+                ],
+            )),
+            Mode::Create(_, _) => None,
+        }
+    }
+
+    fn update_select(&mut self, current: &Profile, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::Screen(Screen::Profiles) => self.reset(current),
+            Action::Command(command) if command.moves_selection() => {
+                self.state.select(utils::move_selection(command, self.state.selected(), self.list.len()))
+            }
+            Action::Command(Command::Open) => {
+                if let Some(profile) = self.state.selected().and_then(|selected| self.list.get(selected)) {
+                    return Ok(Some(if profile == current { Action::Screen(Screen::Home) } else { Action::OpenProfile(profile.name.clone()) }));
+                }
+            }
+            Action::Command(Command::NewProfile) => self.mode = Mode::Create(String::new(), None),
+            Action::Command(Command::Back) => return Ok(Some(Action::Screen(Screen::Home))),
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn update_create(&mut self, action: Action, mut input: String) -> Result<Option<Action>> {
+        match action {
+            Action::Char(c) => {
+                input.push(c);
+                self.mode = Mode::Create(input, None);
+            }
+            Action::Paste(text) => {
+                input.extend(text.chars().filter(|c| !c.is_control()));
+                self.mode = Mode::Create(input, None);
+            }
+            Action::Backspace => {
+                input.pop();
+                self.mode = Mode::Create(input, None);
+            }
+            Action::Enter => match self.profiles.create(input.trim()) {
+                Ok(profile) => {
+                    self.mode = Mode::Select;
+                    return Ok(Some(Action::OpenProfile(profile.name)));
+                }
+                Err(err) => self.mode = Mode::Create(input, Some(err.to_string())),
+            },
+            Action::Esc => self.mode = Mode::Select,
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn reset(&mut self, current: &Profile) {
+        self.mode = Mode::Select;
+        self.list = match self.profiles.list() {
+            Ok(list) => list,
+            Err(err) => {
+                error!("Failed to list profiles: {err}");
+                Vec::new()
+            }
+        };
+        // A collection opened by path is not one of the stored profiles, but is still listed so it can be returned to.
+        if !self.list.contains(current) {
+            self.list.insert(0, current.clone());
+        }
+        self.state.select(self.list.iter().position(|profile| profile == current));
+    }
+
+    pub fn draw(&mut self, current: &Profile, frame: &mut Frame, area: Rect) -> Result<()> {
+        let chunks = Layout::vertical([Constraint::Length(7), Constraint::Min(0), Constraint::Length(3)]).split(area);
+        title::draw_title(frame, chunks[0])?;
+
+        let items: Vec<ListItem> = self
+            .list
+            .iter()
+            .map(|profile| ListItem::new(if profile == current { format!("{} (open)", profile.name) } else { profile.name.clone() }))
+            .collect();
+        let [list_area, input_area] = Layout::vertical([Constraint::Min(0), Constraint::Length(3)]).areas(chunks[1]);
+        let list = List::new(items).highlight_style(self.theme.selected());
+        frame.render_stateful_widget(list.block(Block::bordered().title("[profiles]")), list_area, &mut self.state);
+
+        if let Mode::Create(input, error) = &self.mode {
+            let block = Block::bordered().title("[new profile]");
+            let block = match error {
+                Some(error) => block.title_bottom(error.as_str()).border_style(Style::default().fg(self.theme.warning)),
+                None => block,
+            };
+            frame.render_widget(Paragraph::new(format!(">> {input}█")).block(block), input_area);
+        }
+
+        let commands = match self.hints() {
+            Some((context, hints)) => utils::command_bar_hints(&self.keymap, context, &hints),
+            None => utils::fixed_command_bar(&["<Esc> : cancel", "<CR> : submit"]),
+        };
+        self.list_area = list_area;
+        self.command_bar = utils::draw_command_bar(frame, chunks[2], &commands);
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+use color_eyre::Result;
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Layout, Position, Rect},
+    style::{Color, Style},
+    symbols::Marker,
+    text::{Line, Span, Text},
+    widgets::{Axis, Bar, BarChart, BarGroup, Block, Chart, Dataset, GraphType, Paragraph, Row, Sparkline, Table},
+};
+use uuid::Uuid;
+
+use crate::{
+    action::{Action, Screen},
+    config::{Config, Theme},
+    keymap::{Command, Hint, KeyContext, Keymap},
+    models::{
+        collection::Collection,
+        schedule::Scheduler,
+        stats::{Retention, Stats},
+    },
+    time::{self, Days},
+};
+
+use super::{title, utils};
+
+/// How far back the charts of reviews over time go, which `CyclePeriod` switches between.
+const PERIODS: [(usize, &str); 3] = [(30, "month"), (90, "3 months"), (365, "year")];
+
+/// Shades of the calendar, from days with few reviews to days with the most.
+const HEATMAP_COLORS: [Color; 4] = [Color::Indexed(22), Color::Indexed(28), Color::Indexed(34), Color::Indexed(40)];
+
+const HEATMAP_CELL: &str = "■ ";
+
+/// Charts of the review log of a deck and its subdecks, or of the whole collection.
+pub struct StatsScreen {
+    /// The deck the stats were opened for, if one was selected.
+    deck: Option<Uuid>,
+    /// Whether the whole collection is shown even though a deck was selected.
+    whole_collection: bool,
+    /// Index into `PERIODS`.
+    period: usize,
+    theme: Theme,
+    keymap: Keymap,
+    /// For where study days start.
+    scheduler: Scheduler,
+    /// Where each command in the command bar was drawn, to run it when clicked.
+    command_bar: Vec<(Rect, Command)>,
+}
+
+impl StatsScreen {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            deck: None,
+            whole_collection: false,
+            period: 0,
+            theme: config.theme,
+            keymap: config.keymap.clone(),
+            scheduler: config.scheduler.clone(),
+            command_bar: Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self, action: Action) -> Result<Option<Action>> {
+        match action {
+            Action::Screen(Screen::Stats(deck)) => {
+                self.deck = deck;
+                self.whole_collection = false;
+            }
+            Action::Command(Command::ToggleScope) if self.deck.is_some() => self.whole_collection = !self.whole_collection,
+            Action::Command(Command::CyclePeriod) => self.period = (self.period + 1) % PERIODS.len(),
+            Action::Command(Command::Back) => return Ok(Some(Action::Screen(Screen::Home))),
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    pub fn handle_mouse_event(&mut self, mouse: MouseEvent) -> Option<Action> {
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                utils::clicked_command(&self.command_bar, Position::new(mouse.column, mouse.row)).map(Action::from)
+            }
+            _ => None,
+        }
+    }
+
+    /// The commands offered on the screen.
+    pub fn hints(&self) -> (KeyContext, Vec<Hint>) {
+        (KeyContext::Stats, hints(self.deck.is_some()))
+    }
+
+    pub fn draw(&mut self, collection: &Collection, frame: &mut Frame, area: Rect) -> Result<()> {
+        let chunks = Layout::vertical([Constraint::Length(7), Constraint::Min(0), Constraint::Length(3)]).split(area);
+        title::draw_title(frame, chunks[0])?;
+
+        let deck = self.deck.filter(|_| !self.whole_collection).and_then(|deck| collection.find_deck(deck));
+        let scope = deck.map_or_else(|| "collection".to_string(), |deck| deck.qualified_name());
+        let study_days = self.scheduler.days();
+        let stats = Stats::new(collection, deck.map(|deck| deck.uuid), study_days, time::now());
+        let (days, period) = PERIODS[self.period];
+
+        let [over_time, calendar, distributions] =
+            Layout::vertical([Constraint::Percentage(45), Constraint::Length(9), Constraint::Min(0)]).areas(chunks[1]);
+        let [reviews_area, time_area] = Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(over_time);
+        let [retention_area, intervals_area, eases_area, hours_area] = Layout::horizontal([Constraint::Ratio(1, 4); 4]).areas(distributions);
+
+        self.draw_reviews(frame, reviews_area, &stats.reviews_per_day(days), &format!("[{scope}][reviews][last {period}]"));
+        self.draw_time(frame, time_area, &stats.seconds_per_day(days), &stats.reviews_per_day(days));
+        self.draw_calendar(frame, calendar, &stats, &study_days);
+        self.draw_retention(frame, retention_area, stats.retention(days));
+        self.draw_distribution(frame, intervals_area, &stats.interval_distribution(), "[intervals]");
+        self.draw_distribution(frame, eases_area, &stats.ease_distribution(), "[ease]");
+        self.draw_hours(frame, hours_area, &stats.reviews_per_hour(days));
+
+        self.command_bar =
+            utils::draw_command_bar(frame, chunks[2], &utils::command_bar_hints(&self.keymap, KeyContext::Stats, &hints(self.deck.is_some())));
+        Ok(())
+    }
+
+    /// A bar per day, or per few days when there are more days than columns.
+    fn draw_reviews(&self, frame: &mut Frame, area: Rect, per_day: &[u64], title: &str) {
+        let days_per_bar = per_day.len().div_ceil(area.width.saturating_sub(2).max(1) as usize);
+        let bars: Vec<Bar> = per_day
+            .chunks(days_per_bar)
+            .map(|days| Bar::default().value(days.iter().sum()).text_value(String::new()).style(Style::default().fg(self.theme.highlight)))
+            .collect();
+        let title = match days_per_bar {
+            1 => format!("{title}[{} total]", per_day.iter().sum::<u64>()),
+            n => format!("{title}[{} total][{n} days per bar]", per_day.iter().sum::<u64>()),
+        };
+        let chart = BarChart::default().block(Block::bordered().title(title)).data(BarGroup::default().bars(&bars)).bar_width(1).bar_gap(0);
+        frame.render_widget(chart, area);
+    }
+
+    /// Minutes spent each day as a line, with the totals in the title.
+    fn draw_time(&self, frame: &mut Frame, area: Rect, seconds_per_day: &[u64], reviews_per_day: &[u64]) {
+        let seconds: u64 = seconds_per_day.iter().sum();
+        let reviews: u64 = reviews_per_day.iter().sum();
+        let title = match reviews {
+            0 => "[time]".to_string(),
+            _ => format!("[time][{}][{}s per review]", format_duration(seconds), seconds / reviews),
+        };
+        let points: Vec<(f64, f64)> = seconds_per_day.iter().enumerate().map(|(day, seconds)| (day as f64, *seconds as f64 / 60.0)).collect();
+        let most = points.iter().map(|(_, minutes)| *minutes).fold(1.0, f64::max).ceil();
+        let dataset =
+            Dataset::default().marker(Marker::Braille).graph_type(GraphType::Line).style(Style::default().fg(self.theme.highlight)).data(&points);
+        let last = seconds_per_day.len().saturating_sub(1) as f64;
+        let chart = Chart::new(vec![dataset])
+            .block(Block::bordered().title(title))
+            .x_axis(Axis::default().bounds([0.0, last]).labels([format!("-{}d", seconds_per_day.len()), "today".to_string()]))
+            .y_axis(Axis::default().bounds([0.0, most]).labels(["0m".to_string(), format!("{most}m")]));
+        frame.render_widget(chart, area);
+    }
+
+    /// A GitHub-style calendar of the last weeks that fit, a column per week from Monday to Sunday.
+    fn draw_calendar(&self, frame: &mut Frame, area: Rect, stats: &Stats, study_days: &Days) {
+        const WEEKDAYS: [&str; 7] = ["Mon ", "    ", "Wed ", "    ", "Fri ", "    ", "Sun "];
+        let weeks = (area.width.saturating_sub(2 + 4) as usize / HEATMAP_CELL.chars().count()).clamp(1, 53);
+        let weekday = study_days.weekday(study_days.day(time::now()));
+        let days = weeks * 7;
+        let per_day = stats.reviews_per_day(days);
+        let most = per_day.iter().copied().max().unwrap_or_default();
+        let lines: Vec<Line> = WEEKDAYS
+            .iter()
+            .enumerate()
+            .map(|(row, label)| {
+                let cells = (0..weeks).map(|week| {
+                    // Days after today in the current week are left blank.
+                    let back = (weeks - 1 - week) * 7 + weekday;
+                    match back.checked_sub(row) {
+                        None => Span::raw("  "),
+                        Some(back) => match per_day[days - 1 - back] {
+                            0 => Span::styled(HEATMAP_CELL, Style::default().fg(self.theme.muted)),
+                            count => {
+                                let level = ((count * HEATMAP_COLORS.len() as u64).div_ceil(most) as usize).clamp(1, HEATMAP_COLORS.len());
+                                Span::styled(HEATMAP_CELL, Style::default().fg(HEATMAP_COLORS[level - 1]))
+                            }
+                        },
+                    }
+                });
+                Line::from([Span::styled(*label, Style::default().fg(self.theme.muted))].into_iter().chain(cells).collect::<Vec<_>>())
+            })
+            .collect();
+        let studied = per_day.iter().filter(|count| **count > 0).count();
+        let title = format!("[calendar][studied on {studied} of the last {days} days]");
+        frame.render_widget(Paragraph::new(Text::from(lines)).block(Block::bordered().title(title)), area);
+    }
+
+    /// How many reviews were recalled, for young and mature cards and both together.
+    fn draw_retention(&self, frame: &mut Frame, area: Rect, (young, mature): (Retention, Retention)) {
+        let all = Retention { passed: young.passed + mature.passed, total: young.total + mature.total };
+        let percent = |retention: Retention| retention.percent().map_or_else(|| "-".to_string(), |percent| format!("{percent:.1}%"));
+        let rows = [
+            Row::new(["retention".to_string(), percent(young), percent(mature), percent(all)]),
+            Row::new(["recalled".to_string(), young.passed.to_string(), mature.passed.to_string(), all.passed.to_string()]),
+            Row::new(["reviews".to_string(), young.total.to_string(), mature.total.to_string(), all.total.to_string()]),
+        ];
+        let table = Table::new(rows, Constraint::from_ratios([(2, 5), (1, 5), (1, 5), (1, 5)]))
+            .header(Row::new(["", "young", "mature", "all"]).style(Style::default().fg(self.theme.muted)))
+            .block(Block::bordered().title("[retention]"));
+        frame.render_widget(table, area);
+    }
+
+    /// A labelled bar per bucket, as wide as fits.
+    fn draw_distribution(&self, frame: &mut Frame, area: Rect, buckets: &[(&str, u64)], title: &str) {
+        let bar_width = (area.width.saturating_sub(2) / buckets.len().max(1) as u16).saturating_sub(1).max(1);
+        let chart = BarChart::default()
+            .block(Block::bordered().title(title))
+            .data(buckets)
+            .bar_width(bar_width)
+            .bar_gap(1)
+            .bar_style(Style::default().fg(self.theme.highlight))
+            .value_style(Style::default().fg(Color::Black).bg(self.theme.highlight));
+        frame.render_widget(chart, area);
+    }
+
+    /// Reviews by the hour of the day, stretched over the width.
+    fn draw_hours(&self, frame: &mut Frame, area: Rect, per_hour: &[u64; 24]) {
+        let columns_per_hour = (area.width.saturating_sub(2) / 24).max(1) as usize;
+        let data: Vec<u64> = per_hour.iter().flat_map(|count| std::iter::repeat_n(*count, columns_per_hour)).collect();
+        let block = Block::bordered().title("[hours]").title_bottom(Line::from("0h").left_aligned()).title_bottom(Line::from("23h").right_aligned());
+        frame.render_widget(Sparkline::default().block(block).data(&data).style(Style::default().fg(self.theme.highlight)), area);
+    }
+}
+
+/// A duration like `2h 5m` or `40s`.
+fn format_duration(seconds: u64) -> String {
+    match seconds {
+        seconds if seconds < 60 => format!("{seconds}s"),
+        seconds if seconds < 3600 => format!("{}m", seconds / 60),
+        seconds => format!("{}h {}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+/// The commands offered, switching between the deck and the collection only when there is a deck.
+fn hints(has_deck: bool) -> Vec<Hint> {
+    let mut hints = vec![(Command::CyclePeriod, "period"), (Command::Back, "collection")];
+    if has_deck {
+        hints.insert(0, (Command::ToggleScope, "deck/collection"));
+    }
+    hints
+}
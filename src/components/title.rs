@@ -1,14 +1,55 @@
 use color_eyre::eyre::Result;
 use ratatui::{
-    text::Text,
-    widgets::{Block, Paragraph},
+    layout::{Constraint, Layout, Margin},
+    style::{Modifier, Style},
+    text::{Line, Text},
+    widgets::{Block, LineGauge, Paragraph},
 };
 
+use crate::{
+    config::Theme,
+    models::streak::{Goal, Progress},
+};
+
+/// Width of the daily goal beside the logo.
+const GOAL_WIDTH: u16 = 26;
+
 pub fn draw_title(frame: &mut ratatui::Frame, area: ratatui::prelude::Rect) -> Result<()> {
     frame.render_widget(Paragraph::new(create_small_logo_text()).block(Block::bordered()).centered(), area);
     Ok(())
 }
 
+/// The logo with today's progress towards the daily goal and the streaks on the right.
+pub fn draw_title_with_goal(frame: &mut ratatui::Frame, area: ratatui::prelude::Rect, goal: &Goal, progress: Progress, theme: &Theme) -> Result<()> {
+    draw_title(frame, area)?;
+    let inner = area.inner(Margin::new(2, 1));
+    let [_, goal_area] = Layout::horizontal([Constraint::Min(0), Constraint::Length(GOAL_WIDTH)]).areas(inner);
+    let [today_area, gauge_area, streak_area] =
+        Layout::vertical([Constraint::Length(1), Constraint::Length(1), Constraint::Length(2)]).areas(goal_area);
+
+    let reached = progress.done >= goal.daily;
+    let style = if reached { Style::default().fg(theme.highlight).add_modifier(Modifier::BOLD) } else { Style::default() };
+    frame.render_widget(Line::styled(format!("today {}/{} {}", progress.done, goal.daily, goal.unit), style).right_aligned(), today_area);
+    let ratio = (progress.done as f64 / goal.daily as f64).min(1.0);
+    frame.render_widget(
+        LineGauge::default().ratio(ratio).filled_style(Style::default().fg(theme.highlight)).unfilled_style(Style::default().fg(theme.muted)),
+        gauge_area,
+    );
+    let streak = Text::from(vec![
+        Line::from(format!("streak {}", days(progress.streak))).right_aligned(),
+        Line::styled(format!("longest {}", days(progress.longest_streak)), Style::default().fg(theme.muted)).right_aligned(),
+    ]);
+    frame.render_widget(streak, streak_area);
+    Ok(())
+}
+
+fn days(count: u32) -> String {
+    match count {
+        1 => "1 day".to_string(),
+        count => format!("{count} days"),
+    }
+}
+
 fn _create_logo_lext() -> Text<'static> {
     Text::from(
         "████████╗ █████╗ ███╗  ██╗██╗  ██╗██╗
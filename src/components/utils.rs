@@ -1,6 +1,101 @@
-use std::path::PathBuf;
+use ratatui::{
+    layout::{Constraint, Flex, Layout, Margin, Position, Rect},
+    style::Color,
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+};
 
+use crate::{
+    keymap::{Command, Hint, KeyContext, Keymap},
+    models::schedule::Flag,
+};
 
-pub fn save_file_location() -> PathBuf {
-    dirs::data_dir().unwrap().join("tanki").join("collection.json")
-} 
+/// A rect of the given size centered in `area`, clamped to fit inside it.
+pub fn popup_area(area: Rect, width: u16, height: u16) -> Rect {
+    let [area] = Layout::vertical([Constraint::Length(height.min(area.height))]).flex(Flex::Center).areas(area);
+    let [area] = Layout::horizontal([Constraint::Length(width.min(area.width))]).flex(Flex::Center).areas(area);
+    area
+}
+
+/// The color a flag is drawn in.
+pub fn flag_color(flag: Flag) -> Color {
+    match flag {
+        Flag::Red => Color::Red,
+        Flag::Orange => Color::Indexed(208),
+        Flag::Green => Color::Green,
+        Flag::Blue => Color::Blue,
+    }
+}
+
+/// The selection after moving it with `command` in a list of `len` rows, wrapping around at the ends.
+pub fn move_selection(command: Command, selected: Option<usize>, len: usize) -> Option<usize> {
+    if len == 0 {
+        return None;
+    }
+    Some(match (command, selected.map(|selected| selected.min(len - 1))) {
+        (Command::Top, _) => 0,
+        (Command::Bottom, _) => len - 1,
+        (_, None) => 0,
+        (Command::Up, Some(0)) => len - 1,
+        (Command::Up, Some(selected)) => selected - 1,
+        (Command::Down, Some(selected)) => (selected + 1) % len,
+        (_, Some(selected)) => selected,
+    })
+}
+
+/// Entries of a command bar, each with the command it runs when clicked.
+pub type CommandBar = Vec<(Option<Command>, String)>;
+
+/// The command bar entries for `hints`, followed by the key of the help that lists them all.
+pub fn command_bar_hints(keymap: &Keymap, context: KeyContext, hints: &[Hint]) -> CommandBar {
+    hints.iter().chain([&(Command::Help, "help")]).filter_map(|hint| keymap.hint(context, hint).map(|text| (Some(hint.0), text))).collect()
+}
+
+/// Command bar entries for keys that are not in the keymap, like those used while typing.
+pub fn fixed_command_bar(entries: &[&str]) -> CommandBar {
+    entries.iter().map(|entry| (None, entry.to_string())).collect()
+}
+
+/// Draws `commands` in a single row and returns where each clickable one ended up. Entries that do
+/// not fit are dropped from the end, except for the last one, which the screens use to point to the
+/// help.
+pub fn draw_command_bar(frame: &mut ratatui::Frame, area: Rect, commands: &CommandBar) -> Vec<(Rect, Command)> {
+    const SEPARATOR: &str = "   ";
+    let width = area.width.saturating_sub(2) as usize;
+    let mut shown: Vec<&(Option<Command>, String)> = commands.iter().collect();
+    let line_width = |shown: &[&(Option<Command>, String)]| {
+        shown.iter().map(|(_, text)| Span::from(text.as_str()).width()).sum::<usize>() + SEPARATOR.len() * shown.len().saturating_sub(1)
+    };
+    while shown.len() > 1 && line_width(&shown) > width {
+        shown.remove(shown.len() - 2);
+    }
+    let text: Vec<&str> = shown.iter().map(|(_, text)| text.as_str()).collect();
+    frame.render_widget(
+        Paragraph::new(Line::from(text.join(SEPARATOR))).centered().block(Block::default().title("[commands]").borders(Borders::ALL)),
+        area,
+    );
+
+    // Centered the way the paragraph centers it, inside the border.
+    let mut x = area.x + 1 + ((width / 2).saturating_sub(line_width(&shown) / 2)) as u16;
+    let mut regions = Vec::new();
+    for (command, text) in shown {
+        let entry_width = Span::from(text.as_str()).width() as u16;
+        if let Some(command) = command {
+            regions.push((Rect::new(x, area.y + 1, entry_width, 1).intersection(area), *command));
+        }
+        x = x.saturating_add(entry_width + SEPARATOR.len() as u16);
+    }
+    regions
+}
+
+/// The command of the command bar entry at `position`.
+pub fn clicked_command(regions: &[(Rect, Command)], position: Position) -> Option<Command> {
+    regions.iter().find(|(region, _)| region.contains(position)).map(|(_, command)| *command)
+}
+
+/// The row of a bordered list or table drawn in `area` and scrolled down by `offset` rows, that
+/// `position` is on.
+pub fn row_at(area: Rect, offset: usize, position: Position) -> Option<usize> {
+    let inner = area.inner(Margin::new(1, 1));
+    inner.contains(position).then(|| offset + (position.y - inner.y) as usize)
+}
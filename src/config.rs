@@ -0,0 +1,316 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use color_eyre::eyre::{Result, eyre};
+use directories::ProjectDirs;
+use ratatui::style::{Color, Modifier, Style};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+
+use crate::keymap::{KeyBindings, Keymap};
+use crate::models::schedule::Scheduler;
+use crate::models::streak::Goal;
+use crate::profile::Profile;
+
+const DEFAULT_CONFIG: &str = include_str!("../.config/config.json5");
+const CONFIG_FILE: &str = "config.json5";
+
+/// Key binding presets selected with `app.keymap`, each a full set of bindings.
+const KEYMAP_PRESETS: [(&str, &str); 2] =
+    [("vim", include_str!("../.config/keymaps/vim.json5")), ("anki", include_str!("../.config/keymaps/anki.json5"))];
+
+/// Everything read from the configuration files: the built-in defaults, overridden by the user's
+/// config file, overridden by the open profile's config file.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub app: AppConfig,
+    pub theme: Theme,
+    pub scheduler: Scheduler,
+    pub goal: Goal,
+    pub keymap: Keymap,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AppConfig {
+    data_dir: Option<PathBuf>,
+    log_dir: Option<PathBuf>,
+    pub frame_rate: f64,
+    pub tick_rate: f64,
+    pub keymap: String,
+}
+
+impl AppConfig {
+    pub fn data_dir(&self) -> PathBuf {
+        self.data_dir.clone().unwrap_or_else(|| dirs::data_dir().unwrap_or_default().join("tanki"))
+    }
+
+    pub fn log_dir(&self) -> PathBuf {
+        self.log_dir.clone().unwrap_or_else(|| self.data_dir().join("logs"))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct Theme {
+    #[serde(deserialize_with = "deserialize_color")]
+    pub highlight: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub marked: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub muted: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub warning: Color,
+}
+
+impl Theme {
+    /// Style of the selected row in lists and tables.
+    pub fn selected(&self) -> Style {
+        Style::default().fg(self.highlight).add_modifier(Modifier::BOLD)
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme { highlight: Color::Yellow, marked: Color::Cyan, muted: Color::DarkGray, warning: Color::Red }
+    }
+}
+
+fn deserialize_color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    Color::from_str(&name).map_err(|_| serde::de::Error::custom(format!("unknown color {name:?}")))
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    app: AppConfig,
+    theme: Theme,
+    scheduler: Scheduler,
+    goal: Goal,
+    keybindings: KeyBindings,
+}
+
+impl Config {
+    /// The user's config file in the platform config directory, e.g. `~/.config/tanki/config.json5`.
+    pub fn user_path() -> Option<PathBuf> {
+        ProjectDirs::from("", "", "tanki").map(|dirs| dirs.config_dir().join(CONFIG_FILE))
+    }
+
+    /// The defaults overridden by the user's config file.
+    pub fn user() -> Result<Self> {
+        Config::load(&Config::user_path().into_iter().collect::<Vec<_>>())
+    }
+
+    /// The user's configuration overridden by the profile's own config file. The profile is found
+    /// in the data directory, so its file cannot move that or the logs.
+    pub fn for_profile(profile: &Profile) -> Result<Self> {
+        let path = profile.config_path();
+        if path.exists() {
+            let file = parse(&fs::read_to_string(&path)?, &path)?;
+            let errors: Vec<String> = ["data_dir", "log_dir"]
+                .into_iter()
+                .filter(|key| file.pointer(&format!("/app/{key}")).is_some_and(|value| !value.is_null()))
+                .map(|key| format!("app.{key} can only be set in the user's config file, not in a profile's"))
+                .collect();
+            if !errors.is_empty() {
+                return Err(invalid(&[path], errors));
+            }
+        }
+        Config::load(&Config::user_path().into_iter().chain([path]).collect::<Vec<_>>())
+    }
+
+    /// Loads the defaults overridden by each of `paths` that exists, in order, and reports every
+    /// problem found in them at once.
+    pub fn load(paths: &[PathBuf]) -> Result<Self> {
+        let mut merged = parse(DEFAULT_CONFIG, Path::new("default config"))?;
+        let mut layers = Vec::new();
+        for path in paths.iter().filter(|path| path.exists()) {
+            layers.push(parse(&fs::read_to_string(path)?, path)?);
+        }
+
+        // The preset goes between the defaults and the files, so that the files' own bindings win.
+        let mut errors = Vec::new();
+        let preset = layers.iter().rev().chain([&merged]).find_map(|layer| layer.pointer("/app/keymap")).cloned();
+        match KEYMAP_PRESETS.iter().find(|(name, _)| preset.as_ref().and_then(Value::as_str) == Some(*name)) {
+            Some((name, bindings)) => merge(&mut merged, parse(bindings, Path::new(name))?),
+            None => errors.push(format!("unknown keymap preset {}, expected one of: vim, anki", preset.unwrap_or_default())),
+        }
+        for layer in layers {
+            merge(&mut merged, layer);
+        }
+        let file: ConfigFile = match serde_json::from_value(merged) {
+            Ok(file) => file,
+            Err(err) => {
+                errors.push(err.to_string());
+                return Err(invalid(paths, errors));
+            }
+        };
+
+        if !(file.app.frame_rate > 0.0 && file.app.tick_rate > 0.0) {
+            errors.push("app.frame_rate and app.tick_rate must be positive".to_string());
+        }
+        errors.extend(file.scheduler.validate().into_iter().map(|err| format!("scheduler.{err}")));
+        errors.extend(file.goal.validate().into_iter().map(|err| format!("goal.{err}")));
+        let keymap = Keymap::new(&file.keybindings).unwrap_or_else(|keymap_errors| {
+            errors.extend(keymap_errors);
+            Keymap::default()
+        });
+        if !errors.is_empty() {
+            return Err(invalid(paths, errors));
+        }
+        Ok(Config { app: file.app, theme: file.theme, scheduler: file.scheduler, goal: file.goal, keymap })
+    }
+}
+
+fn parse(content: &str, path: &Path) -> Result<Value> {
+    json5::from_str(content).map_err(|err| eyre!("could not parse {}: {err}", path.display()))
+}
+
+/// Merges `overrides` into `base`, key by key for objects and wholesale for anything else.
+fn merge(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+fn invalid(paths: &[PathBuf], errors: Vec<String>) -> color_eyre::Report {
+    let files: Vec<String> = paths.iter().filter(|path| path.exists()).map(|path| path.display().to_string()).collect();
+    let source = if files.is_empty() { "the default config".to_string() } else { files.join(", ") };
+    eyre!("invalid configuration in {source}:\n  - {}", errors.join("\n  - "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keymap::{Command, Key, KeyContext, Resolution};
+    use crate::profile::Profiles;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use strum::IntoEnumIterator;
+
+    fn write_config(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_default_config_is_valid() {
+        let config = Config::load(&[]).unwrap();
+
+        assert_eq!(config.app.frame_rate, 60.0);
+        assert_eq!(config.scheduler, Scheduler::default());
+        assert_eq!(config.goal, Goal::default());
+        assert_eq!(config.keymap.command(Some(KeyContext::Decks), KeyEvent::new(KeyCode::Char('n'), KeyModifiers::NONE)), Some(Command::NewNote));
+    }
+
+    #[test]
+    fn test_later_files_override_earlier_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let user = write_config(
+            dir.path(),
+            "user.json5",
+            "{ app: { frame_rate: 30 }, keybindings: { decks: { j: 'down' } }, theme: { highlight: 'blue' } }",
+        );
+        let profile = write_config(dir.path(), "profile.json5", "{ app: { frame_rate: 20 }, scheduler: { learning_steps: [30] } }");
+
+        let config = Config::load(&[user, profile, dir.path().join("missing.json5")]).unwrap();
+
+        assert_eq!(config.app.frame_rate, 20.0);
+        assert_eq!(config.app.tick_rate, 60.0);
+        assert_eq!(config.theme.highlight, Color::Blue);
+        assert_eq!(config.scheduler.learning_steps, vec![30]);
+        let decks = Some(KeyContext::Decks);
+        assert_eq!(config.keymap.command(decks, KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE)), Some(Command::Down));
+        assert_eq!(config.keymap.command(decks, KeyEvent::new(KeyCode::Down, KeyModifiers::NONE)), Some(Command::Down));
+    }
+
+    #[test]
+    fn test_keymap_preset_applies_below_the_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let user = write_config(dir.path(), "user.json5", "{ app: { keymap: 'anki' }, keybindings: { decks: { s: 'new_subdeck' } } }");
+
+        let config = Config::load(&[user]).unwrap();
+
+        let decks = Some(KeyContext::Decks);
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        assert_eq!(config.keymap.command(decks, key('a')), Some(Command::NewNote));
+        assert_eq!(config.keymap.command(decks, key('s')), Some(Command::NewSubdeck));
+        assert_eq!(config.keymap.command(decks, key('n')), None);
+        // The vim keys are not part of the anki preset.
+        assert_eq!(config.keymap.command(decks, key('j')), None);
+        assert_eq!(config.keymap.command(decks, KeyEvent::new(KeyCode::Down, KeyModifiers::NONE)), Some(Command::Down));
+        assert_eq!(config.keymap.resolve(Some(KeyContext::Notes), &Key::parse_sequence("dd").unwrap()), Resolution::Unbound);
+    }
+
+    #[test]
+    fn test_every_command_has_a_key_in_each_preset() {
+        for (name, _) in KEYMAP_PRESETS {
+            let dir = tempfile::tempdir().unwrap();
+            let path = write_config(dir.path(), "config.json5", &format!("{{ app: {{ keymap: '{name}' }} }}"));
+            let keymap = Config::load(&[path]).unwrap().keymap;
+
+            for command in Command::iter() {
+                let bound = KeyContext::iter().any(|context| !keymap.keys_for(context, command).is_empty());
+                assert!(bound, "{command} has no key in the {name} preset");
+            }
+        }
+    }
+
+    #[test]
+    fn test_all_problems_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_config(
+            dir.path(),
+            "config.json5",
+            "{ app: { frame_rate: 0 }, scheduler: { starting_ease: 10, rollover_hour: 30 }, goal: { daily: 0 }, keybindings: { decks: { '<Hyper-x>': 'quit' } } }",
+        );
+
+        let message = Config::load(&[path]).unwrap_err().to_string();
+
+        assert!(message.contains("frame_rate"));
+        assert!(message.contains("starting_ease"));
+        assert!(message.contains("rollover_hour"));
+        assert!(message.contains("goal.daily"));
+        assert!(message.contains("<Hyper-x>"));
+    }
+
+    #[test]
+    fn test_profile_config_cannot_move_the_data_or_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        let profile = Profiles::new(dir.path().to_path_buf()).create("work").unwrap();
+
+        fs::write(profile.config_path(), "{ app: { data_dir: null, tick_rate: 30 } }").unwrap();
+        assert_eq!(Config::for_profile(&profile).unwrap().app.tick_rate, 30.0);
+
+        fs::write(profile.config_path(), "{ app: { data_dir: '/elsewhere', log_dir: '/logs' } }").unwrap();
+        let message = Config::for_profile(&profile).unwrap_err().to_string();
+        assert!(message.contains("app.data_dir"));
+        assert!(message.contains("app.log_dir"));
+    }
+
+    #[test]
+    fn test_unknown_commands_and_colors_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let command = write_config(dir.path(), "command.json5", "{ keybindings: { decks: { n: 'new_nope' } } }");
+        let color = write_config(dir.path(), "color.json5", "{ theme: { marked: 'ultraviolet' } }");
+        let syntax = write_config(dir.path(), "syntax.json5", "{ app: ");
+        let preset = write_config(dir.path(), "preset.json5", "{ app: { keymap: 'emacs' } }");
+
+        assert!(Config::load(&[command]).unwrap_err().to_string().contains("new_nope"));
+        assert!(Config::load(&[color]).unwrap_err().to_string().contains("ultraviolet"));
+        assert!(Config::load(&[syntax]).is_err());
+        assert!(Config::load(&[preset]).unwrap_err().to_string().contains("emacs"));
+    }
+}
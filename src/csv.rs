@@ -0,0 +1,173 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{Result, WrapErr, eyre};
+
+use crate::models::{
+    deck::Deck,
+    note::{Note, NoteType},
+    tag::parse_tags,
+};
+
+/// Headers understood by Anki's text importer, so exported files can be imported there as well.
+const HEADER: &str = "#separator:comma\n#html:false\n#columns:front,back,tags\n#tags column:3\n";
+
+/// Writes the notes of `deck`, without its subdecks, as rows of front, back and tags separated by spaces.
+pub fn export_deck(deck: &Deck, path: &Path) -> Result<()> {
+    let mut text = HEADER.to_string();
+    for note in deck.get_notes() {
+        let fields = [note.front.as_str(), note.back.as_str(), &note.tags.join(" ")].map(quote);
+        text.push_str(&fields.join(","));
+        text.push('\n');
+    }
+    fs::write(path, text).wrap_err_with(|| format!("Failed to write {}", path.display()))
+}
+
+/// Reads a deck named after the file from rows of front, back and tags. Lines starting with `#`
+/// before the first row are headers, of which only `#separator:` is used, and missing columns are
+/// left empty.
+pub fn load_from_csv(path: PathBuf) -> Result<Deck> {
+    let text = fs::read_to_string(&path).wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+    let rows = parse(&text).map_err(|err| eyre!("Failed to parse {}: {err}", path.display()))?;
+    let mut deck = Deck::new(path.file_stem().and_then(|s| s.to_str()).unwrap_or("Imported").to_string());
+    for row in rows {
+        let field = |index: usize| row.get(index).map_or("", String::as_str);
+        if field(0).trim().is_empty() {
+            continue;
+        }
+        let note = Note::new(field(0).to_string(), field(1).to_string(), NoteType::Basic);
+        deck.add_note(Note { tags: parse_tags(field(2)), ..note });
+    }
+    Ok(deck)
+}
+
+fn quote(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) || field.starts_with('#') {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+/// The separator named by a `#separator:` header, in Anki's spelling or as the character itself.
+fn separator(value: &str) -> Result<char, String> {
+    match value.trim_end_matches('\r') {
+        "\t" => Ok('\t'),
+        value => match value.trim().to_ascii_lowercase().as_str() {
+            "comma" | "," => Ok(','),
+            "semicolon" | ";" => Ok(';'),
+            "tab" => Ok('\t'),
+            _ => Err(format!("unsupported separator {value:?}, expected comma, semicolon or tab")),
+        },
+    }
+}
+
+/// Splits `text` into rows of fields, where quoted fields may contain separators, newlines and
+/// doubled quotes. Fields are separated by commas unless a `#separator:` header says otherwise.
+fn parse(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut lines = text.trim_start_matches('\u{feff}');
+    let mut separator_char = ',';
+    while lines.starts_with('#') {
+        let (header, rest) = lines.split_once('\n').unwrap_or((lines, ""));
+        if let Some(value) = header.strip_prefix("#separator:") {
+            separator_char = separator(value)?;
+        }
+        lines = rest;
+    }
+
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut chars = lines.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, c) if c == separator_char => row.push(std::mem::take(&mut field)),
+            (false, '\r') if chars.peek() == Some(&'\n') => {}
+            (false, '\n') => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("unterminated quoted field".to_string());
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_handles_quotes_and_headers() {
+        let text = "#separator:comma\n#tags column:3\nuno,one,numbers\r\n\"dos, two\",\"say \"\"two\"\"\nplease\"\nsolo\n";
+
+        assert_eq!(
+            parse(text).unwrap(),
+            vec![
+                vec!["uno".to_string(), "one".to_string(), "numbers".to_string()],
+                vec!["dos, two".to_string(), "say \"two\"\nplease".to_string()],
+                vec!["solo".to_string()],
+            ]
+        );
+        assert!(parse("\"open").is_err());
+    }
+
+    #[test]
+    fn test_parse_honors_separator_header() {
+        let tab = "#separator:tab\n#html:false\nuno, más\tone\tnumbers\n\"dos\tdoce\"\ttwo\n";
+        let semicolon = "#separator:Semicolon\r\nuno;one, first\r\n";
+
+        assert_eq!(
+            parse(tab).unwrap(),
+            vec![vec!["uno, más".to_string(), "one".to_string(), "numbers".to_string()], vec!["dos\tdoce".to_string(), "two".to_string()],]
+        );
+        assert_eq!(parse(semicolon).unwrap(), vec![vec!["uno".to_string(), "one, first".to_string()]]);
+        assert!(parse("#separator:pipe\nuno|one\n").unwrap_err().contains("pipe"));
+    }
+
+    #[test]
+    fn test_imports_anki_tab_separated_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Spanish.txt");
+        fs::write(&path, "#separator:tab\n#html:true\n#tags column:3\nuno\tone\tnumbers basics\ndos\ttwo\t\n").unwrap();
+
+        let deck = load_from_csv(path).unwrap();
+
+        let notes: Vec<_> = deck.get_notes().iter().map(|note| (note.front.as_str(), note.back.as_str(), note.tags.len())).collect();
+        assert_eq!(notes, vec![("uno", "one", 2), ("dos", "two", 0)]);
+    }
+
+    #[test]
+    fn test_export_and_import_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Spanish.csv");
+        let mut deck = Deck::new("Spanish".to_string());
+        deck.add_note(Note {
+            tags: vec!["numbers".to_string(), "grammar::nouns".to_string()],
+            ..Note::new("uno, \"one\"".to_string(), "one\nfirst".to_string(), NoteType::Basic)
+        });
+        deck.add_note(Note::new("#hash".to_string(), String::new(), NoteType::Basic));
+
+        export_deck(&deck, &path).unwrap();
+        let imported = load_from_csv(path).unwrap();
+
+        assert_eq!(imported.name, "Spanish");
+        let notes: Vec<_> = imported.get_notes().iter().map(|note| (&note.front, &note.back, &note.tags)).collect();
+        let expected: Vec<_> = deck.get_notes().iter().map(|note| (&note.front, &note.back, &note.tags)).collect();
+        assert_eq!(notes, expected);
+    }
+}
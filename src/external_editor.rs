@@ -0,0 +1,120 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::process::Command;
+
+use color_eyre::eyre::{Result, WrapErr, eyre};
+
+const FRONT_HEADER: &str = "== front ==";
+const BACK_HEADER: &str = "== back ==";
+const INSTRUCTIONS: &str = "# Edit the note below, then save and close the editor. Lines starting with # are ignored\n\
+# above the first header. Keep both headers; everything after a header, up to the next one,\n\
+# is that side of the note. A line of the note that reads like a header has a \\ put before it.\n";
+
+/// The text fields of a note, as edited outside the app.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoteFields {
+    pub front: String,
+    pub back: String,
+}
+
+/// Opens `fields` in the user's `$VISUAL` or `$EDITOR`, falling back to `vi`, and parses what was
+/// saved. Must only be called while the terminal is out of raw mode.
+pub fn edit(fields: &NoteFields) -> Result<NoteFields> {
+    let mut file = tempfile::Builder::new().prefix("tanki-note-").suffix(".txt").tempfile()?;
+    file.write_all(format(fields).as_bytes())?;
+    file.flush()?;
+
+    let editor = env::var("VISUAL").or_else(|_| env::var("EDITOR")).unwrap_or_else(|_| "vi".to_string());
+    // Editors are often configured with arguments, like `code --wait`.
+    let mut words = editor.split_whitespace();
+    let program = words.next().ok_or_else(|| eyre!("$EDITOR is empty"))?;
+    let status = Command::new(program).args(words).arg(file.path()).status().wrap_err_with(|| format!("could not run {editor}"))?;
+    if !status.success() {
+        return Err(eyre!("{editor} exited with {status}, the note was left unchanged"));
+    }
+    parse(&fs::read_to_string(file.path())?).map_err(|err| eyre!(err))
+}
+
+/// The file a note is edited in: a short explanation, then each side under its header.
+pub fn format(fields: &NoteFields) -> String {
+    format!("{INSTRUCTIONS}{FRONT_HEADER}\n{}\n{BACK_HEADER}\n{}\n", escape(&fields.front), escape(&fields.back))
+}
+
+/// Whether `line` is a header after any number of backslashes, which `escape` adds one more of.
+fn is_header_like(line: &str) -> bool {
+    matches!(line.trim_start_matches('\\').trim_end(), FRONT_HEADER | BACK_HEADER)
+}
+
+/// Puts a backslash before each line that would otherwise be read back as a header.
+fn escape(field: &str) -> String {
+    field.split('\n').map(|line| if is_header_like(line) { format!("\\{line}") } else { line.to_string() }).collect::<Vec<_>>().join("\n")
+}
+
+/// Reads the fields back from a file written by [`format`], with trailing blank lines removed.
+pub fn parse(content: &str) -> Result<NoteFields, String> {
+    let mut front: Option<Vec<&str>> = None;
+    let mut back: Option<Vec<&str>> = None;
+    for (number, line) in content.lines().enumerate() {
+        match line.trim_end() {
+            FRONT_HEADER if front.is_some() => return Err(format!("line {}: {FRONT_HEADER} appears twice", number + 1)),
+            BACK_HEADER if back.is_some() => return Err(format!("line {}: {BACK_HEADER} appears twice", number + 1)),
+            FRONT_HEADER if back.is_some() => return Err(format!("line {}: {FRONT_HEADER} must come before {BACK_HEADER}", number + 1)),
+            FRONT_HEADER => front = Some(Vec::new()),
+            BACK_HEADER if front.is_none() => return Err(format!("line {}: {FRONT_HEADER} must come before {BACK_HEADER}", number + 1)),
+            BACK_HEADER => back = Some(Vec::new()),
+            _ => match (&mut front, &mut back) {
+                (_, Some(lines)) | (Some(lines), None) if is_header_like(line) => lines.push(&line[1..]),
+                (_, Some(lines)) | (Some(lines), None) => lines.push(line),
+                (None, None) if line.trim().is_empty() || line.starts_with('#') => {}
+                (None, None) => return Err(format!("line {}: text before {FRONT_HEADER}", number + 1)),
+            },
+        }
+    }
+    let (Some(front), Some(back)) = (front, back) else {
+        return Err(format!("the file must contain {FRONT_HEADER} and {BACK_HEADER}"));
+    };
+    let front = front.join("\n").trim_end().to_string();
+    if front.is_empty() {
+        return Err("the front of the note is empty".to_string());
+    }
+    Ok(NoteFields { front, back: back.join("\n").trim_end().to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_round_trips() {
+        let fields = NoteFields { front: "# heading\n\nfn main() {}".to_string(), back: "== not a header\n日本".to_string() };
+
+        assert_eq!(parse(&format(&fields)), Ok(fields));
+    }
+
+    #[test]
+    fn test_header_lines_in_fields_round_trip() {
+        let fields = NoteFields { front: "== back ==\nthe front".to_string(), back: "== front ==  \n\\== back ==\n\\\\== front ==\nend".to_string() };
+
+        let content = format(&fields);
+
+        assert!(content.contains("\\== back ==\nthe front"));
+        assert_eq!(parse(&content), Ok(fields));
+    }
+
+    #[test]
+    fn test_parse_ignores_comments_and_trailing_blank_lines() {
+        let content = "# comment\n\n== front ==\nquestion\n\n\n== back ==  \nanswer\n\n";
+
+        assert_eq!(parse(content), Ok(NoteFields { front: "question".to_string(), back: "answer".to_string() }));
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_files() {
+        assert!(parse("== front ==\nquestion\n").unwrap_err().contains("must contain"));
+        assert!(parse("stray\n== front ==\nq\n== back ==\na").unwrap_err().starts_with("line 1"));
+        assert!(parse("== back ==\na\n== front ==\nq").unwrap_err().contains("must come before"));
+        assert!(parse("== front ==\nq\n== back ==\na\n== back ==\nb").unwrap_err().contains("twice"));
+        assert!(parse("== front ==\n\n== back ==\na").unwrap_err().contains("empty"));
+    }
+}
@@ -0,0 +1,511 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter, EnumMessage, EnumString};
+
+/// What a key does, independent of which key it is bound to. The message describes it in the help.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString, EnumMessage, EnumIter)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Command {
+    #[strum(message = "quit tanki")]
+    Quit,
+    #[strum(message = "undo the last change")]
+    Undo,
+    #[strum(message = "redo the last undone change")]
+    Redo,
+    #[strum(message = "show the keys available here")]
+    Help,
+    #[strum(message = "move the selection up")]
+    Up,
+    #[strum(message = "move the selection down")]
+    Down,
+    #[strum(message = "move the selection to the first row")]
+    Top,
+    #[strum(message = "move the selection to the last row")]
+    Bottom,
+    #[strum(message = "go back, cancel or close")]
+    Back,
+    #[strum(message = "practice the selected deck")]
+    Practice,
+    #[strum(message = "show or hide the selected deck's subdecks")]
+    ToggleExpand,
+    #[strum(message = "move to the notes of the selected deck")]
+    FocusNotes,
+    #[strum(message = "add a note to the selected deck")]
+    NewNote,
+    #[strum(message = "add a top-level deck")]
+    NewDeck,
+    #[strum(message = "add a deck inside the selected deck")]
+    NewSubdeck,
+    #[strum(message = "import an Anki package or a CSV file")]
+    Import,
+    #[strum(message = "export the selected deck's notes as a CSV file")]
+    Export,
+    #[strum(message = "add a filtered deck of the cards matching a search")]
+    NewFilteredDeck,
+    #[strum(message = "gather the cards of the selected filtered deck again")]
+    Rebuild,
+    #[strum(message = "return the selected filtered deck's cards to their home decks")]
+    Empty,
+    #[strum(message = "switch whether the selected filtered deck reschedules its cards")]
+    ToggleReschedule,
+    #[strum(message = "rename the selected deck")]
+    Rename,
+    #[strum(message = "cut the selected deck or the marked notes")]
+    Cut,
+    #[strum(message = "move the cut deck or notes into the selected deck")]
+    Paste,
+    #[strum(message = "move the cut deck to the top level")]
+    PasteTopLevel,
+    #[strum(message = "delete the selected deck or note")]
+    Delete,
+    #[strum(message = "show recently deleted decks")]
+    Trash,
+    #[strum(message = "show backups of the collection")]
+    Backups,
+    #[strum(message = "switch to another profile")]
+    Profiles,
+    #[strum(message = "edit the selected note")]
+    Edit,
+    #[strum(message = "mark or unmark the selected note")]
+    Mark,
+    #[strum(message = "change the selected note's type")]
+    CycleType,
+    #[strum(message = "confirm")]
+    Confirm,
+    #[strum(message = "restore the selected entry")]
+    Restore,
+    #[strum(message = "open the selected profile")]
+    Open,
+    #[strum(message = "create a profile")]
+    NewProfile,
+    #[strum(message = "show the answer, then answer good")]
+    Flip,
+    #[strum(message = "answer again: forgotten")]
+    Again,
+    #[strum(message = "answer hard: recalled with difficulty")]
+    Hard,
+    #[strum(message = "answer good: recalled")]
+    Good,
+    #[strum(message = "answer easy: recalled effortlessly")]
+    Easy,
+    #[strum(message = "suspend the card, or suspend or unsuspend the selected notes' cards")]
+    Suspend,
+    #[strum(message = "bury the card until tomorrow")]
+    Bury,
+    #[strum(message = "cycle the flag of the card or the selected notes through red, orange, green, blue and none")]
+    Flag,
+    #[strum(message = "practice the cards that are due again")]
+    Restart,
+    #[strum(message = "reload the collection from disk")]
+    Reload,
+    #[strum(message = "overwrite the collection on disk")]
+    Overwrite,
+    #[strum(message = "edit the note in $VISUAL or $EDITOR")]
+    OpenInEditor,
+    #[strum(message = "browse and search all notes")]
+    Browse,
+    #[strum(message = "type a search, like deck:Spanish is:due")]
+    Search,
+    #[strum(message = "show the notes tagged leech, most forgotten first")]
+    Leeches,
+    #[strum(message = "show statistics of the selected deck or the collection")]
+    Stats,
+    #[strum(message = "switch between the deck's statistics and the collection's")]
+    ToggleScope,
+    #[strum(message = "show the last month, three months or year")]
+    CyclePeriod,
+    #[strum(message = "sort by the next column")]
+    SortBy,
+    #[strum(message = "reverse the sort order")]
+    ReverseSort,
+    #[strum(message = "move to the tags, to filter notes by one")]
+    Tags,
+    #[strum(message = "show only notes with the selected tag, or all notes again")]
+    FilterByTag,
+}
+
+impl Command {
+    /// Whether the command moves the selection in a list or table.
+    pub fn moves_selection(self) -> bool {
+        matches!(self, Command::Up | Command::Down | Command::Top | Command::Bottom)
+    }
+}
+
+/// Where a key is pressed. Bindings in `Global` apply everywhere unless the context rebinds or unbinds the key.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Display, EnumString, EnumIter)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum KeyContext {
+    Global,
+    Decks,
+    Notes,
+    ConfirmDelete,
+    Trash,
+    Backups,
+    Practice,
+    Profiles,
+    Browser,
+    Tags,
+    Stats,
+    Conflict,
+    Help,
+}
+
+/// A key with its modifiers, written like `n`, `D`, `<CR>` or `<C-r>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        let mut modifiers = event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        // Shift is already part of the character, and terminals disagree on whether to report it.
+        if let KeyCode::Char(_) = event.code {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Key { code: event.code, modifiers }
+    }
+}
+
+const NAMED_KEYS: [(&str, KeyCode); 15] = [
+    ("CR", KeyCode::Enter),
+    ("Esc", KeyCode::Esc),
+    ("Space", KeyCode::Char(' ')),
+    ("Tab", KeyCode::Tab),
+    ("BS", KeyCode::Backspace),
+    ("Del", KeyCode::Delete),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("lt", KeyCode::Char('<')),
+];
+
+impl Key {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let Some(inner) = text.strip_prefix('<').and_then(|text| text.strip_suffix('>')).filter(|inner| !inner.is_empty()) else {
+            let mut chars = text.chars();
+            return match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(Key { code: KeyCode::Char(c), modifiers: KeyModifiers::NONE }),
+                _ => Err(format!("invalid key {text:?}: write a single character or a key like <CR> or <C-r>")),
+            };
+        };
+        let mut modifiers = KeyModifiers::NONE;
+        let mut name = inner;
+        while let Some((modifier, rest)) = name.split_once('-').filter(|(_, rest)| !rest.is_empty()) {
+            modifiers |= match modifier {
+                "C" => KeyModifiers::CONTROL,
+                "A" | "M" => KeyModifiers::ALT,
+                "S" => KeyModifiers::SHIFT,
+                _ => return Err(format!("invalid modifier {modifier:?} in key {text:?}")),
+            };
+            name = rest;
+        }
+        let code = match NAMED_KEYS.iter().find(|(key_name, _)| key_name.eq_ignore_ascii_case(name)) {
+            Some((_, code)) => *code,
+            None if name.chars().count() == 1 => KeyCode::Char(name.chars().next().unwrap()),
+            None => match name.strip_prefix('F').and_then(|number| number.parse().ok()) {
+                Some(number) => KeyCode::F(number),
+                None => return Err(format!("unknown key {name:?} in {text:?}")),
+            },
+        };
+        Ok(Key::from(KeyEvent::new(code, modifiers)))
+    }
+
+    /// Parses a sequence of keys pressed one after the other, like `gg`, `dd` or `g<Tab>`.
+    pub fn parse_sequence(text: &str) -> Result<Vec<Self>, String> {
+        let mut keys = Vec::new();
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            let len = match rest.find('>') {
+                Some(end) if c == '<' && end > 1 => end + 1,
+                _ => c.len_utf8(),
+            };
+            let key = &rest[..len];
+            keys.push(Key::parse(key).map_err(|err| if key == text { err } else { format!("{err} in {text:?}") })?);
+            rest = &rest[len..];
+        }
+        if keys.is_empty() {
+            return Err("empty key".to_string());
+        }
+        Ok(keys)
+    }
+}
+
+/// Formats a key sequence for the command bar and help: `<CR>` and `<C-r>` as they are, anything
+/// else in brackets, like `<n>` or `<gg>`.
+fn format_sequence(keys: &[Key]) -> String {
+    let text: String = keys.iter().map(Key::to_string).collect();
+    if keys.len() == 1 && text.starts_with('<') { text } else { format!("<{text}>") }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.code {
+            KeyCode::Char(c) if self.modifiers.is_empty() && c != ' ' && c != '<' => return write!(f, "{c}"),
+            KeyCode::Char(c) if c != ' ' && c != '<' => c.to_string(),
+            KeyCode::F(number) => format!("F{number}"),
+            code => NAMED_KEYS.iter().find(|(_, named)| *named == code).map(|(name, _)| name.to_string()).unwrap_or_else(|| format!("{code:?}")),
+        };
+        let modifiers: String = [(KeyModifiers::CONTROL, "C-"), (KeyModifiers::ALT, "A-"), (KeyModifiers::SHIFT, "S-")]
+            .iter()
+            .filter(|(modifier, _)| self.modifiers.contains(*modifier))
+            .map(|(_, prefix)| *prefix)
+            .collect();
+        write!(f, "<{modifiers}{name}>")
+    }
+}
+
+/// Key bindings as written in the configuration file; binding a key to `null` unbinds it.
+pub type KeyBindings = BTreeMap<KeyContext, BTreeMap<String, Option<Command>>>;
+
+/// A command offered in some screen or mode, with the label shown next to its keys.
+pub type Hint = (Command, &'static str);
+
+/// What the keys pressed so far amount to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Command(Command),
+    /// The keys start one or more longer sequences.
+    Pending,
+    Unbound,
+}
+
+/// Resolves keys and key sequences to commands for the context they are pressed in.
+#[derive(Debug, Clone, Default)]
+pub struct Keymap {
+    bindings: BTreeMap<KeyContext, Vec<(Vec<Key>, Command)>>,
+    /// Sequences bound to `null` in a context, which keep the global bindings of them from applying.
+    unbound: BTreeMap<KeyContext, Vec<Vec<Key>>>,
+}
+
+impl Keymap {
+    /// Parses every binding, describing each key that could not be parsed and each sequence that
+    /// could never be completed because a shorter one in the same context starts it.
+    pub fn new(bindings: &KeyBindings) -> Result<Self, Vec<String>> {
+        let mut keymap = Keymap::default();
+        let mut errors = Vec::new();
+        for (context, keys) in bindings {
+            let mut sequences: Vec<(Vec<Key>, Command)> = Vec::new();
+            let mut unbound = Vec::new();
+            for (text, command) in keys {
+                match (Key::parse_sequence(text), command) {
+                    (Ok(keys), Some(command)) => sequences.push((keys, *command)),
+                    (Ok(keys), None) => unbound.push(keys),
+                    (Err(err), _) => errors.push(format!("keybindings.{context}: {err}")),
+                }
+            }
+            for (keys, _) in &sequences {
+                if let Some((longer, _)) = sequences.iter().find(|(other, _)| other.len() > keys.len() && other.starts_with(keys)) {
+                    errors.push(format!("keybindings.{context}: {} hides {}", format_sequence(keys), format_sequence(longer)));
+                }
+            }
+            keymap.bindings.insert(*context, sequences);
+            keymap.unbound.insert(*context, unbound);
+        }
+        if errors.is_empty() { Ok(keymap) } else { Err(errors) }
+    }
+
+    /// Resolves the keys pressed so far in `context`, preferring its own bindings over the global
+    /// ones, which do not apply to keys the context unbinds. Without a context text is being typed,
+    /// so only single global keys using Ctrl or Alt apply.
+    pub fn resolve(&self, context: Option<KeyContext>, keys: &[Key]) -> Resolution {
+        let Some(context) = context else {
+            return match keys {
+                [key] if key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => self.resolve_in(KeyContext::Global, keys),
+                _ => Resolution::Unbound,
+            };
+        };
+        match self.resolve_in(context, keys) {
+            Resolution::Unbound if self.unbound.get(&context).is_some_and(|unbound| unbound.iter().any(|sequence| sequence == keys)) => {
+                Resolution::Unbound
+            }
+            Resolution::Unbound => self.resolve_in(KeyContext::Global, keys),
+            resolution => resolution,
+        }
+    }
+
+    /// The command bound to the single `key` in `context`.
+    pub fn command(&self, context: Option<KeyContext>, key: KeyEvent) -> Option<Command> {
+        match self.resolve(context, &[Key::from(key)]) {
+            Resolution::Command(command) => Some(command),
+            _ => None,
+        }
+    }
+
+    fn resolve_in(&self, context: KeyContext, keys: &[Key]) -> Resolution {
+        let sequences = self.bindings.get(&context).into_iter().flatten();
+        let mut resolution = Resolution::Unbound;
+        for (sequence, command) in sequences {
+            if sequence == keys {
+                return Resolution::Command(*command);
+            } else if sequence.starts_with(keys) {
+                resolution = Resolution::Pending;
+            }
+        }
+        resolution
+    }
+
+    /// Every key sequence running `command` in `context`, formatted like `<CR>` or `<gg>`.
+    pub fn keys_for(&self, context: KeyContext, command: Command) -> Vec<String> {
+        [context, KeyContext::Global]
+            .iter()
+            .flat_map(|context| self.bindings.get(context).into_iter().flatten())
+            .filter(|(keys, bound)| *bound == command && self.resolve(Some(context), keys) == Resolution::Command(command))
+            .map(|(keys, _)| format_sequence(keys))
+            .collect()
+    }
+
+    /// Command bar entries for `hints`, each showing the first key bound to its command. Commands
+    /// without a key are left out.
+    pub fn hints(&self, context: KeyContext, hints: &[Hint]) -> Vec<String> {
+        hints.iter().filter_map(|hint| self.hint(context, hint)).collect()
+    }
+
+    /// The command bar entry for `hint`, if its command has a key.
+    pub fn hint(&self, context: KeyContext, (command, label): &Hint) -> Option<String> {
+        self.keys_for(context, *command).first().map(|keys| format!("{keys} : {label}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn test_parse_and_display_keys() {
+        for text in ["n", "D", "?", "<CR>", "<Esc>", "<Space>", "<Tab>", "<Up>", "<C-r>", "<A-x>", "<F5>", "<lt>"] {
+            assert_eq!(Key::parse(text).unwrap().to_string(), text);
+        }
+        assert_eq!(Key::parse("<cr>").unwrap(), Key::parse("<CR>").unwrap());
+        assert_eq!(Key::parse("<C-->").unwrap(), Key { code: KeyCode::Char('-'), modifiers: KeyModifiers::CONTROL });
+        assert!(Key::parse("nn").is_err());
+        assert!(Key::parse("<X-r>").is_err());
+        assert!(Key::parse("<Enterr>").is_err());
+    }
+
+    #[test]
+    fn test_shift_is_ignored_for_characters() {
+        assert_eq!(Key::from(key(KeyCode::Char('D'), KeyModifiers::SHIFT)), Key::parse("D").unwrap());
+    }
+
+    fn bindings(bindings: &[(KeyContext, &str, Command)]) -> KeyBindings {
+        let mut result = KeyBindings::new();
+        for (context, key, command) in bindings {
+            result.entry(*context).or_default().insert(key.to_string(), Some(*command));
+        }
+        result
+    }
+
+    fn keys(text: &str) -> Vec<Key> {
+        Key::parse_sequence(text).unwrap()
+    }
+
+    #[test]
+    fn test_context_bindings_override_global_ones() {
+        let keymap = Keymap::new(&bindings(&[
+            (KeyContext::Global, "<C-r>", Command::Redo),
+            (KeyContext::Global, "q", Command::Quit),
+            (KeyContext::Decks, "q", Command::Back),
+        ]))
+        .unwrap();
+
+        assert_eq!(keymap.command(Some(KeyContext::Decks), key(KeyCode::Char('q'), KeyModifiers::NONE)), Some(Command::Back));
+        assert_eq!(keymap.command(Some(KeyContext::Practice), key(KeyCode::Char('q'), KeyModifiers::NONE)), Some(Command::Quit));
+        assert_eq!(keymap.command(None, key(KeyCode::Char('q'), KeyModifiers::NONE)), None);
+        assert_eq!(keymap.command(None, key(KeyCode::Char('r'), KeyModifiers::CONTROL)), Some(Command::Redo));
+    }
+
+    #[test]
+    fn test_parse_sequences() {
+        assert_eq!(keys("gg").len(), 2);
+        assert_eq!(keys("g<Tab>"), vec![Key::parse("g").unwrap(), Key::parse("<Tab>").unwrap()]);
+        assert_eq!(keys("<>"), vec![Key::parse("<lt>").unwrap(), Key::parse(">").unwrap()]);
+        assert_eq!(format_sequence(&keys("dd")), "<dd>");
+        assert_eq!(format_sequence(&keys("<C-r>")), "<C-r>");
+        assert!(Key::parse_sequence("").is_err());
+        assert!(Key::parse_sequence("g<Nope>").is_err());
+    }
+
+    #[test]
+    fn test_resolve_sequences() {
+        let keymap = Keymap::new(&bindings(&[
+            (KeyContext::Notes, "dd", Command::Delete),
+            (KeyContext::Notes, "D", Command::Delete),
+            (KeyContext::Global, "gg", Command::Top),
+            (KeyContext::Global, "q", Command::Quit),
+        ]))
+        .unwrap();
+        let notes = Some(KeyContext::Notes);
+
+        assert_eq!(keymap.resolve(notes, &keys("d")), Resolution::Pending);
+        assert_eq!(keymap.resolve(notes, &keys("dd")), Resolution::Command(Command::Delete));
+        assert_eq!(keymap.resolve(notes, &keys("dq")), Resolution::Unbound);
+        assert_eq!(keymap.resolve(notes, &keys("g")), Resolution::Pending);
+        assert_eq!(keymap.resolve(notes, &keys("gg")), Resolution::Command(Command::Top));
+        assert_eq!(keymap.resolve(None, &keys("g")), Resolution::Unbound);
+        assert_eq!(keymap.keys_for(KeyContext::Notes, Command::Delete), vec!["<D>", "<dd>"]);
+        assert_eq!(
+            keymap.hints(KeyContext::Notes, &[(Command::Delete, "delete"), (Command::Edit, "edit"), (Command::Quit, "quit")]),
+            vec!["<D> : delete", "<q> : quit"]
+        );
+    }
+
+    #[test]
+    fn test_shadowed_global_keys_are_not_listed() {
+        let keymap = Keymap::new(&bindings(&[(KeyContext::Global, "q", Command::Quit), (KeyContext::Decks, "q", Command::Back)])).unwrap();
+
+        assert!(keymap.keys_for(KeyContext::Decks, Command::Quit).is_empty());
+        assert_eq!(keymap.keys_for(KeyContext::Trash, Command::Quit), vec!["<q>"]);
+    }
+
+    #[test]
+    fn test_unbound_keys_are_skipped() {
+        let mut bindings = bindings(&[(KeyContext::Decks, "n", Command::NewNote)]);
+        bindings.get_mut(&KeyContext::Decks).unwrap().insert("q".to_string(), None);
+
+        let keymap = Keymap::new(&bindings).unwrap();
+
+        assert_eq!(keymap.command(Some(KeyContext::Decks), key(KeyCode::Char('q'), KeyModifiers::NONE)), None);
+    }
+
+    #[test]
+    fn test_unbinding_a_key_hides_its_global_binding() {
+        let mut bindings = bindings(&[(KeyContext::Global, "q", Command::Quit), (KeyContext::Global, "gg", Command::Top)]);
+        bindings.entry(KeyContext::Decks).or_default().extend([("q".to_string(), None), ("gg".to_string(), None)]);
+
+        let keymap = Keymap::new(&bindings).unwrap();
+
+        assert_eq!(keymap.command(Some(KeyContext::Decks), key(KeyCode::Char('q'), KeyModifiers::NONE)), None);
+        assert_eq!(keymap.resolve(Some(KeyContext::Decks), &keys("gg")), Resolution::Unbound);
+        assert!(keymap.keys_for(KeyContext::Decks, Command::Quit).is_empty());
+        assert_eq!(keymap.command(Some(KeyContext::Notes), key(KeyCode::Char('q'), KeyModifiers::NONE)), Some(Command::Quit));
+    }
+
+    #[test]
+    fn test_invalid_keys_are_reported() {
+        let mut bindings = bindings(&[(KeyContext::Decks, "g", Command::Top), (KeyContext::Decks, "gg", Command::Top)]);
+        bindings.entry(KeyContext::Notes).or_default().insert("<Nope>".to_string(), Some(Command::Edit));
+
+        let errors = Keymap::new(&bindings).unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec!["keybindings.decks: <g> hides <gg>".to_string(), "keybindings.notes: unknown key \"Nope\" in \"<Nope>\"".to_string()]
+        );
+    }
+}
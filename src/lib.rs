@@ -1,9 +1,19 @@
 pub mod action;
+pub mod cli;
 pub mod components;
+pub mod config;
+pub mod csv;
+pub mod external_editor;
+pub mod keymap;
+pub mod lock;
 
 pub mod models;
+pub mod profile;
+pub mod storage;
+pub mod time;
 pub mod tui;
 
 pub mod app;
 
 pub mod anki_importer;
+pub mod backup;
@@ -0,0 +1,131 @@
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{Result, bail};
+use uuid::Uuid;
+
+/// A lock file next to a collection, held for as long as the collection is open so that a second
+/// instance does not write to it concurrently. Removed again when dropped.
+#[derive(Debug)]
+pub struct Lock {
+    path: PathBuf,
+    contents: String,
+}
+
+impl Lock {
+    /// Takes the lock for `collection`, replacing a lock left behind by a process that is no longer
+    /// running. Fails if another running process holds it.
+    pub fn acquire(collection: &Path) -> Result<Self> {
+        let path = lock_path(collection);
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    let contents = contents();
+                    file.write_all(contents.as_bytes())?;
+                    return Ok(Lock { path, contents });
+                }
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => match holder(&path) {
+                    Some(pid) if is_running(pid) => {
+                        bail!("{} is already open in another tanki instance (pid {pid}); pass --ignore-lock to open it anyway", collection.display())
+                    }
+                    // Stale: the process that took it is gone, or never finished writing its pid.
+                    _ => fs::remove_file(&path)?,
+                },
+                Err(err) => return Err(err.into()),
+            }
+        }
+        bail!("could not take the lock {}", path.display())
+    }
+
+    /// Takes the lock for `collection` even if another process holds it.
+    pub fn force(collection: &Path) -> Result<Self> {
+        let path = lock_path(collection);
+        let contents = contents();
+        fs::write(&path, &contents)?;
+        Ok(Lock { path, contents })
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        // Leave the file alone if another instance has since taken over the lock.
+        if fs::read_to_string(&self.path).is_ok_and(|contents| contents == self.contents) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn lock_path(collection: &Path) -> PathBuf {
+    let mut path = collection.as_os_str().to_owned();
+    path.push(".lock");
+    PathBuf::from(path)
+}
+
+/// The holder's pid followed by a token unique to this lock, so that locks taken by the same
+/// process can be told apart.
+fn contents() -> String {
+    format!("{} {}", std::process::id(), Uuid::new_v4())
+}
+
+fn holder(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.split_whitespace().next()?.parse().ok()
+}
+
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Signal 0 only checks whether the process exists; EPERM means it does but belongs to someone else.
+    unsafe { libc::kill(pid, 0) == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM) }
+}
+
+#[cfg(not(unix))]
+fn is_running(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_is_exclusive_and_released_on_drop() {
+        let dir = tempfile::tempdir().unwrap();
+        let collection = dir.path().join("collection.sqlite");
+
+        let lock = Lock::acquire(&collection).unwrap();
+        assert!(dir.path().join("collection.sqlite.lock").exists());
+        assert!(Lock::acquire(&collection).is_err());
+
+        drop(lock);
+        assert!(!dir.path().join("collection.sqlite.lock").exists());
+        assert!(Lock::acquire(&collection).is_ok());
+    }
+
+    #[test]
+    fn test_stale_lock_is_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let collection = dir.path().join("collection.sqlite");
+        fs::write(dir.path().join("collection.sqlite.lock"), i32::MAX.to_string()).unwrap();
+
+        let _lock = Lock::acquire(&collection).unwrap();
+
+        assert_eq!(holder(&lock_path(&collection)), Some(std::process::id()));
+    }
+
+    #[test]
+    fn test_forced_lock_takes_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let collection = dir.path().join("collection.sqlite");
+        let first = Lock::acquire(&collection).unwrap();
+
+        let second = Lock::force(&collection).unwrap();
+        drop(first);
+
+        assert!(lock_path(&collection).exists());
+        drop(second);
+        assert!(!lock_path(&collection).exists());
+    }
+}
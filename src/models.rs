@@ -1,4 +1,16 @@
 pub mod card;
 pub mod collection;
 pub mod deck;
+pub mod due;
+pub mod filtered_deck;
+pub mod history;
+pub mod migration;
 pub mod note;
+pub mod operation;
+pub mod review;
+pub mod schedule;
+pub mod search;
+pub mod stats;
+pub mod streak;
+pub mod tag;
+pub mod trash;
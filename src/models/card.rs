@@ -1,5 +1,12 @@
+use uuid::Uuid;
+
+use crate::models::schedule::Schedule;
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Card {
+    pub note: Uuid,
+    pub ordinal: usize,
     pub front: String,
     pub back: String,
+    pub schedule: Schedule,
 }
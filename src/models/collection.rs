@@ -1,38 +1,57 @@
+use std::collections::{BTreeSet, HashSet};
 use std::fs;
 use std::path::PathBuf;
 
+use color_eyre::eyre::{Result, WrapErr};
+
 use serde::Deserialize;
 use serde::Serialize;
 use uuid::Uuid;
 
 use crate::models::deck::Deck;
+use crate::models::filtered_deck::FilteredDeck;
+use crate::models::migration;
+use crate::models::note::Note;
+use crate::models::review::Review;
+use crate::models::schedule::Schedule;
+use crate::models::trash::{DeletedDeck, TRASH_CAPACITY};
 
 #[derive(Serialize, Deserialize, Eq, PartialEq, Hash)]
 pub struct Collection {
     pub uuid: Uuid,
     pub decks: Vec<Deck>,
+    #[serde(default)]
+    trash: Vec<DeletedDeck>,
+    #[serde(default)]
+    review_log: Vec<Review>,
+    #[serde(default)]
+    filtered_decks: Vec<FilteredDeck>,
 }
 
 impl Collection {
     pub fn new() -> Self {
-        Collection { decks: Vec::new(), uuid: uuid::Uuid::new_v4() }
+        Collection { decks: Vec::new(), uuid: uuid::Uuid::new_v4(), trash: Vec::new(), review_log: Vec::new(), filtered_decks: Vec::new() }
     }
 
-    pub fn load_from_file(path: PathBuf) -> Self {
-        if let Ok(content) = fs::read_to_string(path) {
-            if let Ok(collection) = serde_json::from_str::<Collection>(&content) {
-                return collection;
-            }
-        }
-        Collection { decks: Vec::new(), uuid: uuid::Uuid::new_v4() }
+    pub(crate) fn from_parts(
+        uuid: Uuid,
+        decks: Vec<Deck>,
+        trash: Vec<DeletedDeck>,
+        review_log: Vec<Review>,
+        filtered_decks: Vec<FilteredDeck>,
+    ) -> Self {
+        Collection { uuid, decks, trash, review_log, filtered_decks }
     }
 
-    pub fn save_to_file(&self, path: PathBuf) {
-        fs::create_dir_all(path.parent().unwrap()).unwrap();
-        fs::write(path, serde_json::to_string_pretty(&self).unwrap()).unwrap();
+    pub fn load_from_file(path: PathBuf) -> Result<Self> {
+        let content = fs::read_to_string(&path).wrap_err_with(|| format!("failed to read {}", path.display()))?;
+        let value = serde_json::from_str(&content).wrap_err_with(|| format!("failed to parse {}", path.display()))?;
+        let value = migration::migrate(value).wrap_err_with(|| format!("failed to upgrade {}", path.display()))?;
+        serde_json::from_value(value).wrap_err_with(|| format!("failed to load {}", path.display()))
     }
 
-    pub fn add_deck(&mut self, deck: Deck) {
+    pub fn add_deck(&mut self, mut deck: Deck) {
+        deck.clear_parent();
         self.decks.push(deck);
     }
 
@@ -44,14 +63,140 @@ impl Collection {
         }
     }
 
-    pub fn remove_deck(&mut self, uuid: Uuid) {
+    pub fn remove_deck(&mut self, uuid: Uuid) -> Option<Deck> {
         if let Some(pos) = self.decks.iter().position(|deck| deck.uuid == uuid) {
-            self.decks.remove(pos);
+            Some(self.decks.remove(pos))
+        } else {
+            self.decks.iter_mut().find_map(|deck| deck.remove_deck(uuid))
+        }
+    }
+
+    /// Returns the uuid of the deck containing `uuid`, or the collection's uuid for top-level decks.
+    pub fn find_parent(&self, uuid: Uuid) -> Option<Uuid> {
+        self.deck_position(uuid).map(|(parent, _)| parent)
+    }
+
+    /// Returns the parent of a deck (see [`Collection::find_parent`]) and its index among its siblings.
+    pub fn deck_position(&self, uuid: Uuid) -> Option<(Uuid, usize)> {
+        if let Some(position) = self.decks.iter().position(|deck| deck.uuid == uuid) {
+            return Some((self.uuid, position));
+        }
+        self.get_all_decks()
+            .into_iter()
+            .find_map(|deck| deck.get_subdecks().iter().position(|d| d.uuid == uuid).map(|position| (deck.uuid, position)))
+    }
+
+    /// Inserts a deck at `position` among the children of `parent`, which may be the collection's
+    /// uuid for the top level. Returns `false` if the parent does not exist.
+    pub fn insert_deck(&mut self, parent: Uuid, position: usize, mut deck: Deck) -> bool {
+        if parent == self.uuid {
+            deck.clear_parent();
+            self.decks.insert(position.min(self.decks.len()), deck);
+            return true;
+        }
+        match self.find_deck_mut(parent) {
+            Some(parent) => {
+                parent.insert_subdeck(position, deck);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get_subdecks_of(&self, parent: Uuid) -> &[Deck] {
+        match self.find_deck(parent) {
+            Some(deck) => deck.get_subdecks(),
+            None if parent == self.uuid => &self.decks,
+            None => &[],
+        }
+    }
+
+    /// Removes a deck and moves it, with its whole subtree, into the trash.
+    pub fn delete_deck(&mut self, uuid: Uuid) -> bool {
+        let Some((parent, position)) = self.deck_position(uuid) else {
+            return false;
+        };
+        let deck = self.remove_deck(uuid).unwrap();
+        self.trash.push(DeletedDeck::new(deck, parent, position));
+        if self.trash.len() > TRASH_CAPACITY {
+            self.trash.remove(0);
+        }
+        true
+    }
+
+    /// Puts a deck from the trash back where it was deleted from, falling back to the top level
+    /// when its old parent no longer exists.
+    pub fn restore_deck(&mut self, uuid: Uuid) -> bool {
+        let Some(pos) = self.trash.iter().position(|deleted| deleted.deck.uuid == uuid) else {
+            return false;
+        };
+        let deleted = self.trash.remove(pos);
+        if self.find_deck(deleted.parent).is_some() || deleted.parent == self.uuid {
+            self.insert_deck(deleted.parent, deleted.position, deleted.deck)
         } else {
-            for deck in &mut self.decks {
-                deck.remove_deck(uuid);
+            self.insert_deck(self.uuid, self.decks.len(), deleted.deck)
+        }
+    }
+
+    pub fn get_trash(&self) -> &[DeletedDeck] {
+        &self.trash
+    }
+
+    /// Renames a deck and refreshes the qualified names stored throughout its subtree.
+    pub fn rename_deck(&mut self, uuid: Uuid, name: String) -> bool {
+        match self.find_deck_mut(uuid) {
+            Some(deck) => {
+                deck.name = name;
+                deck.refresh_parents();
+                true
             }
+            None => false,
+        }
+    }
+
+    /// Moves a deck and its subtree under `parent`, or to the top level when `parent` is the
+    /// collection's uuid. Returns `false` if the move would place the deck inside itself.
+    pub fn move_deck(&mut self, uuid: Uuid, parent: Uuid) -> bool {
+        match self.find_deck(uuid) {
+            Some(deck) if deck.find_deck(parent).is_none() => {}
+            _ => return false,
+        }
+        if parent != self.uuid && self.find_deck(parent).is_none() {
+            return false;
         }
+        let deck = self.remove_deck(uuid).unwrap();
+        self.add_deck_to(parent, deck);
+        true
+    }
+
+    pub fn find_note(&self, note_uuid: Uuid) -> Option<&Note> {
+        self.get_all_decks().into_iter().find_map(|deck| deck.find_note(note_uuid))
+    }
+
+    pub fn find_note_mut(&mut self, note_uuid: Uuid) -> Option<&mut Note> {
+        let deck_uuid = self.find_note_deck(note_uuid)?.uuid;
+        self.find_deck_mut(deck_uuid)?.find_note_mut(note_uuid)
+    }
+
+    pub fn find_note_deck(&self, note_uuid: Uuid) -> Option<&Deck> {
+        self.get_all_decks().into_iter().find(|deck| deck.find_note(note_uuid).is_some())
+    }
+
+    pub fn remove_note(&mut self, note_uuid: Uuid) -> Option<Note> {
+        let deck_uuid = self.find_note_deck(note_uuid)?.uuid;
+        self.find_deck_mut(deck_uuid)?.remove_note(note_uuid)
+    }
+
+    /// Moves the given notes into the deck `target`, returning how many were moved.
+    pub fn move_notes(&mut self, note_uuids: &[Uuid], target: Uuid) -> usize {
+        if self.find_deck(target).is_none() {
+            return 0;
+        }
+        let notes: Vec<Note> = note_uuids.iter().filter_map(|uuid| self.remove_note(*uuid)).collect();
+        let moved = notes.len();
+        let deck = self.find_deck_mut(target).unwrap();
+        notes.into_iter().for_each(|note| deck.add_note(note));
+        moved
     }
 
     pub fn find_deck_mut(&mut self, uuid: Uuid) -> Option<&mut Deck> {
@@ -72,6 +217,35 @@ impl Collection {
         None
     }
 
+    pub fn set_schedule(&mut self, note_uuid: Uuid, ordinal: usize, schedule: Schedule) -> bool {
+        match self.find_note_mut(note_uuid) {
+            Some(note) => {
+                note.set_schedule(ordinal, schedule);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn log_review(&mut self, review: Review) {
+        self.review_log.push(review);
+    }
+
+    /// Removes the most recent log entry equal to `review`, used when an answer is undone.
+    pub fn remove_review(&mut self, review: &Review) -> bool {
+        match self.review_log.iter().rposition(|r| r == review) {
+            Some(pos) => {
+                self.review_log.remove(pos);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get_review_log(&self) -> &[Review] {
+        &self.review_log
+    }
+
     pub fn get_decks(&self) -> &[Deck] {
         &self.decks
     }
@@ -79,6 +253,48 @@ impl Collection {
     pub fn get_all_decks(&self) -> Vec<&Deck> {
         self.decks.iter().flat_map(|d| d.get_all_subdecks()).chain(self.get_decks()).collect()
     }
+
+    pub fn get_filtered_decks(&self) -> &[FilteredDeck] {
+        &self.filtered_decks
+    }
+
+    pub fn find_filtered_deck(&self, uuid: Uuid) -> Option<&FilteredDeck> {
+        self.filtered_decks.iter().find(|deck| deck.uuid == uuid)
+    }
+
+    pub fn add_filtered_deck(&mut self, deck: FilteredDeck) {
+        self.filtered_decks.push(deck);
+    }
+
+    pub fn insert_filtered_deck(&mut self, position: usize, deck: FilteredDeck) {
+        self.filtered_decks.insert(position.min(self.filtered_decks.len()), deck);
+    }
+
+    pub fn remove_filtered_deck(&mut self, uuid: Uuid) -> Option<FilteredDeck> {
+        let position = self.filtered_decks.iter().position(|deck| deck.uuid == uuid)?;
+        Some(self.filtered_decks.remove(position))
+    }
+
+    pub fn update_filtered_deck(&mut self, deck: FilteredDeck) -> bool {
+        match self.filtered_decks.iter_mut().find(|existing| existing.uuid == deck.uuid) {
+            Some(existing) => {
+                *existing = deck;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The cards held by a filtered deck, as note and ordinal, which are practiced there rather than in
+    /// their home decks.
+    pub fn get_filtered_cards(&self) -> HashSet<(Uuid, usize)> {
+        self.filtered_decks.iter().flat_map(|deck| deck.cards.iter().copied()).collect()
+    }
+
+    /// Every tag used by a note in the collection.
+    pub fn get_tags(&self) -> BTreeSet<String> {
+        self.get_all_decks().into_iter().flat_map(|deck| deck.get_notes()).flat_map(|note| note.tags.iter().cloned()).collect()
+    }
 }
 
 impl Default for Collection {
@@ -91,6 +307,7 @@ impl Default for Collection {
 mod tests {
     use super::*;
     use crate::models::deck::Deck;
+    use crate::models::note::NoteType;
 
     #[test]
     fn test_collection_creation() {
@@ -105,4 +322,140 @@ mod tests {
         collection.add_deck(deck);
         assert_eq!(collection.decks.len(), 1);
     }
+
+    #[test]
+    fn test_load_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("collection.json");
+        let mut collection = Collection::new();
+        collection.add_deck(Deck::new("My Deck".to_string()));
+
+        fs::write(&path, serde_json::to_string(&collection).unwrap()).unwrap();
+
+        assert!(Collection::load_from_file(path).unwrap() == collection);
+    }
+
+    #[test]
+    fn test_load_corrupt_file_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("collection.json");
+        fs::write(&path, "{\"uuid\": ").unwrap();
+
+        assert!(Collection::load_from_file(path).is_err());
+    }
+
+    #[test]
+    fn test_move_deck_updates_qualified_names() {
+        let mut collection = Collection::new();
+        let a = Deck::new("A".to_string());
+        let b = Deck::new("B".to_string());
+        let mut child = Deck::new("Child".to_string());
+        child.add_subdeck(Deck::new("Leaf".to_string()));
+        let (a_uuid, b_uuid, child_uuid) = (a.uuid, b.uuid, child.uuid);
+        collection.add_deck(a);
+        collection.add_deck(b);
+        collection.add_deck_to(a_uuid, child);
+
+        assert!(collection.move_deck(child_uuid, b_uuid));
+
+        let child = collection.find_deck(child_uuid).unwrap();
+        assert_eq!(child.qualified_name(), "B:Child");
+        assert_eq!(child.get_subdecks()[0].qualified_name(), "B:Child:Leaf");
+        assert!(collection.find_deck(a_uuid).unwrap().get_subdecks().is_empty());
+
+        assert!(collection.move_deck(child_uuid, collection.uuid));
+        assert_eq!(collection.find_deck(child_uuid).unwrap().get_subdecks()[0].qualified_name(), "Child:Leaf");
+    }
+
+    #[test]
+    fn test_delete_and_restore_deck_in_place() {
+        let mut collection = Collection::new();
+        let mut parent = Deck::new("Parent".to_string());
+        let mut child = Deck::new("Child".to_string());
+        child.add_note(Note::new("Q".to_string(), "A".to_string(), NoteType::Basic));
+        let (parent_uuid, child_uuid) = (parent.uuid, child.uuid);
+        parent.add_subdeck(Deck::new("First".to_string()));
+        parent.add_subdeck(child);
+        parent.add_subdeck(Deck::new("Last".to_string()));
+        collection.add_deck(parent);
+
+        assert!(collection.delete_deck(child_uuid));
+        assert!(collection.find_deck(child_uuid).is_none());
+        assert_eq!(collection.get_trash().len(), 1);
+        assert_eq!(collection.get_trash()[0].parent, parent_uuid);
+
+        assert!(collection.restore_deck(child_uuid));
+        let subdecks = collection.find_deck(parent_uuid).unwrap().get_subdecks();
+        assert_eq!(subdecks.iter().map(|d| d.name.as_str()).collect::<Vec<_>>(), vec!["First", "Child", "Last"]);
+        assert_eq!(subdecks[1].get_notes().len(), 1);
+        assert!(collection.get_trash().is_empty());
+    }
+
+    #[test]
+    fn test_restore_deck_without_parent_goes_to_top_level() {
+        let mut collection = Collection::new();
+        let mut parent = Deck::new("Parent".to_string());
+        let child = Deck::new("Child".to_string());
+        let (parent_uuid, child_uuid) = (parent.uuid, child.uuid);
+        parent.add_subdeck(child);
+        collection.add_deck(parent);
+
+        collection.delete_deck(child_uuid);
+        collection.delete_deck(parent_uuid);
+        collection.trash.retain(|deleted| deleted.deck.uuid != parent_uuid);
+
+        assert!(collection.restore_deck(child_uuid));
+        assert_eq!(collection.decks.len(), 1);
+        assert_eq!(collection.decks[0].qualified_name(), "Child");
+    }
+
+    #[test]
+    fn test_rename_deck_updates_descendants() {
+        let mut collection = Collection::new();
+        let mut parent = Deck::new("Parent".to_string());
+        let mut child = Deck::new("Child".to_string());
+        let leaf = Deck::new("Leaf".to_string());
+        let (parent_uuid, leaf_uuid) = (parent.uuid, leaf.uuid);
+        child.add_subdeck(leaf);
+        parent.add_subdeck(child);
+        collection.add_deck(parent);
+
+        assert!(collection.rename_deck(parent_uuid, "Renamed".to_string()));
+
+        assert_eq!(collection.find_deck(parent_uuid).unwrap().qualified_name(), "Renamed");
+        assert_eq!(collection.find_deck(leaf_uuid).unwrap().qualified_name(), "Renamed:Child:Leaf");
+        assert!(!collection.rename_deck(Uuid::new_v4(), "Missing".to_string()));
+    }
+
+    #[test]
+    fn test_move_deck_into_own_subtree_is_rejected() {
+        let mut collection = Collection::new();
+        let mut parent = Deck::new("Parent".to_string());
+        let child = Deck::new("Child".to_string());
+        let (parent_uuid, child_uuid) = (parent.uuid, child.uuid);
+        parent.add_subdeck(child);
+        collection.add_deck(parent);
+
+        assert!(!collection.move_deck(parent_uuid, child_uuid));
+        assert!(!collection.move_deck(parent_uuid, parent_uuid));
+        assert_eq!(collection.find_deck(child_uuid).unwrap().qualified_name(), "Parent:Child");
+    }
+
+    #[test]
+    fn test_move_notes() {
+        let mut collection = Collection::new();
+        let mut source = Deck::new("Source".to_string());
+        let target = Deck::new("Target".to_string());
+        let note = Note::new("Q".to_string(), "A".to_string(), NoteType::Basic);
+        let (note_uuid, target_uuid) = (note.uuid, target.uuid);
+        source.add_note(note);
+        source.add_note(Note::new("Q2".to_string(), "A2".to_string(), NoteType::Basic));
+        collection.add_deck(source);
+        collection.add_deck(target);
+
+        assert_eq!(collection.move_notes(&[note_uuid], target_uuid), 1);
+
+        assert_eq!(collection.find_note_deck(note_uuid).unwrap().uuid, target_uuid);
+        assert_eq!(collection.decks[0].get_notes().len(), 1);
+    }
 }
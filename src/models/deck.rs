@@ -28,9 +28,30 @@ impl Deck {
 
     pub fn add_subdeck(&mut self, mut deck: Deck) {
         deck.parent = Some(self.qualified_name());
+        deck.refresh_parents();
         self.subdecks.push(deck);
     }
 
+    pub(crate) fn clear_parent(&mut self) {
+        self.parent = None;
+        self.refresh_parents();
+    }
+
+    /// Recomputes the stored parent names of the whole subtree from this deck's qualified name.
+    pub fn refresh_parents(&mut self) {
+        let qualified_name = self.qualified_name();
+        for subdeck in &mut self.subdecks {
+            subdeck.parent = Some(qualified_name.clone());
+            subdeck.refresh_parents();
+        }
+    }
+
+    pub(crate) fn insert_subdeck(&mut self, position: usize, mut deck: Deck) {
+        deck.parent = Some(self.qualified_name());
+        deck.refresh_parents();
+        self.subdecks.insert(position.min(self.subdecks.len()), deck);
+    }
+
     pub fn add_note(&mut self, card: Note) {
         self.notes.push(card);
     }
@@ -47,6 +68,41 @@ impl Deck {
         &self.notes
     }
 
+    pub fn get_all_notes(&self) -> Vec<&Note> {
+        self.subdecks.iter().flat_map(|d| d.get_all_notes()).chain(self.notes.iter()).collect()
+    }
+
+    pub fn insert_note(&mut self, position: usize, note: Note) {
+        self.notes.insert(position.min(self.notes.len()), note);
+    }
+
+    pub fn note_position(&self, uuid: Uuid) -> Option<usize> {
+        self.notes.iter().position(|note| note.uuid == uuid)
+    }
+
+    pub fn find_note(&self, uuid: Uuid) -> Option<&Note> {
+        self.notes.iter().find(|note| note.uuid == uuid)
+    }
+
+    pub fn find_note_mut(&mut self, uuid: Uuid) -> Option<&mut Note> {
+        self.notes.iter_mut().find(|note| note.uuid == uuid)
+    }
+
+    pub fn update_note(&mut self, note: Note) -> bool {
+        match self.find_note_mut(note.uuid) {
+            Some(existing) => {
+                *existing = note;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove_note(&mut self, uuid: Uuid) -> Option<Note> {
+        let pos = self.notes.iter().position(|note| note.uuid == uuid)?;
+        Some(self.notes.remove(pos))
+    }
+
     pub fn get_subdecks(&self) -> &[Deck] {
         &self.subdecks
     }
@@ -55,13 +111,11 @@ impl Deck {
         self.subdecks.iter().flat_map(|d| d.get_all_subdecks()).chain(self.get_subdecks()).collect()
     }
 
-    pub fn remove_deck(&mut self, uuid: Uuid) {
+    pub fn remove_deck(&mut self, uuid: Uuid) -> Option<Deck> {
         if let Some(pos) = self.subdecks.iter().position(|deck| deck.uuid == uuid) {
-            self.subdecks.remove(pos);
+            Some(self.subdecks.remove(pos))
         } else {
-            for deck in &mut self.subdecks {
-                deck.remove_deck(uuid);
-            }
+            self.subdecks.iter_mut().find_map(|deck| deck.remove_deck(uuid))
         }
     }
 
@@ -151,4 +205,60 @@ mod tests {
 
         assert_eq!(all_cards.len(), 2);
     }
+
+    #[test]
+    fn test_add_subdeck_refreshes_nested_parents() {
+        let mut root = Deck::new("Root".to_string());
+        let mut child = Deck::new("Child".to_string());
+        child.add_subdeck(Deck::new("Grandchild".to_string()));
+
+        root.add_subdeck(child);
+
+        assert_eq!(root.subdecks[0].qualified_name(), "Root:Child");
+        assert_eq!(root.subdecks[0].subdecks[0].qualified_name(), "Root:Child:Grandchild");
+    }
+
+    #[test]
+    fn test_remove_deck_returns_subtree() {
+        let mut root = Deck::new("Root".to_string());
+        let mut child = Deck::new("Child".to_string());
+        let grandchild = Deck::new("Grandchild".to_string());
+        let grandchild_uuid = grandchild.uuid;
+        child.add_subdeck(grandchild);
+        root.add_subdeck(child);
+
+        let removed = root.remove_deck(grandchild_uuid).unwrap();
+
+        assert_eq!(removed.name, "Grandchild");
+        assert!(root.find_deck(grandchild_uuid).is_none());
+        assert!(root.remove_deck(grandchild_uuid).is_none());
+    }
+
+    #[test]
+    fn test_update_note_keeps_identity() {
+        let mut deck = Deck::new("Test Deck".to_string());
+        let note = Note::new("Q".to_string(), "A".to_string(), NoteType::Basic);
+        let uuid = note.uuid;
+        deck.add_note(note);
+
+        let edited = Note { front: "Q2".to_string(), ..deck.find_note(uuid).unwrap().clone() };
+        assert!(deck.update_note(edited));
+
+        assert_eq!(deck.notes.len(), 1);
+        assert_eq!(deck.notes[0].uuid, uuid);
+        assert_eq!(deck.notes[0].front, "Q2");
+        assert!(!deck.update_note(Note::new("X".to_string(), "Y".to_string(), NoteType::Basic)));
+    }
+
+    #[test]
+    fn test_remove_note() {
+        let mut deck = Deck::new("Test Deck".to_string());
+        let note = Note::new("Q".to_string(), "A".to_string(), NoteType::Basic);
+        let uuid = note.uuid;
+        deck.add_note(note);
+
+        assert_eq!(deck.remove_note(uuid).map(|n| n.front), Some("Q".to_string()));
+        assert!(deck.remove_note(uuid).is_none());
+        assert!(deck.notes.is_empty());
+    }
 }
@@ -0,0 +1,315 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Add;
+
+use uuid::Uuid;
+
+use crate::models::card::Card;
+use crate::models::collection::Collection;
+use crate::models::deck::Deck;
+use crate::models::note::Note;
+use crate::models::review::Review;
+use crate::models::schedule::{CardState, Scheduler};
+use crate::time::Days;
+
+/// How many cards are due in each state, like the three counts of Anki's deck list.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DueCounts {
+    pub new: usize,
+    pub learning: usize,
+    pub review: usize,
+}
+
+impl DueCounts {
+    /// Counts the cards by state, without applying any limit.
+    pub fn count<'a>(cards: impl IntoIterator<Item = &'a Card>) -> Self {
+        cards.into_iter().fold(DueCounts::default(), |counts, card| counts + DueCounts::of(card.schedule.state))
+    }
+
+    /// Counts the reviews done on the study day `now` falls in by the state the card was in.
+    pub fn answered<'a>(reviews: impl IntoIterator<Item = &'a Review>, days: &Days, now: i64) -> Self {
+        let today = days.day(now);
+        reviews
+            .into_iter()
+            .filter(|review| days.day(review.time) == today)
+            .fold(DueCounts::default(), |counts, review| counts + DueCounts::of(review.state))
+    }
+
+    fn of(state: CardState) -> Self {
+        match state {
+            CardState::New => DueCounts { new: 1, ..DueCounts::default() },
+            CardState::Learning | CardState::Relearning => DueCounts { learning: 1, ..DueCounts::default() },
+            CardState::Review => DueCounts { review: 1, ..DueCounts::default() },
+        }
+    }
+
+    /// Caps the new and review counts at what is left of the daily limits after `answered`.
+    pub fn limit(self, scheduler: &Scheduler, answered: DueCounts) -> Self {
+        DueCounts {
+            new: self.new.min((scheduler.new_per_day as usize).saturating_sub(answered.new)),
+            learning: self.learning,
+            review: self.review.min((scheduler.reviews_per_day as usize).saturating_sub(answered.review)),
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.new + self.learning + self.review
+    }
+}
+
+impl Add for DueCounts {
+    type Output = DueCounts;
+
+    fn add(self, other: DueCounts) -> DueCounts {
+        DueCounts { new: self.new + other.new, learning: self.learning + other.learning, review: self.review + other.review }
+    }
+}
+
+/// Keeps the cards that fit in what is left of today's limits after `answered`, in order.
+pub fn due_today(cards: Vec<Card>, scheduler: &Scheduler, answered: DueCounts) -> Vec<Card> {
+    let mut left = DueCounts { new: usize::MAX, learning: usize::MAX, review: usize::MAX }.limit(scheduler, answered);
+    cards
+        .into_iter()
+        .filter(|card| {
+            let count = match card.schedule.state {
+                CardState::New => &mut left.new,
+                CardState::Learning | CardState::Relearning => &mut left.learning,
+                CardState::Review => &mut left.review,
+            };
+            let fits = *count > 0;
+            *count = count.saturating_sub(1);
+            fits
+        })
+        .collect()
+}
+
+/// The cards of `deck` and its subdecks that can be practiced today, of the notes `keep` accepts.
+/// The limits apply to each subdeck and then to `deck` as a whole, like in Anki. Cards held by a
+/// filtered deck are left out, and so are suspended and buried ones.
+pub fn deck_due_cards(collection: &Collection, deck: &Deck, scheduler: &Scheduler, days: &Days, now: i64, keep: &dyn Fn(&Note) -> bool) -> Vec<Card> {
+    let today = Today::new(collection, days, now);
+    subtree_due_cards(deck, scheduler, &today, days, now, keep, &mut HashMap::new()).0
+}
+
+/// How many cards of each deck and its subdecks can be practiced today, as `deck_due_cards` finds
+/// them, found in one pass over the collection.
+pub fn deck_counts(collection: &Collection, scheduler: &Scheduler, days: &Days, now: i64) -> HashMap<Uuid, DueCounts> {
+    let today = Today::new(collection, days, now);
+    let mut counts = HashMap::new();
+    for deck in collection.get_decks() {
+        subtree_due_cards(deck, scheduler, &today, days, now, &|_| true, &mut counts);
+    }
+    counts
+}
+
+/// When the due counts found at `now` next change without the collection changing: when a learning
+/// step comes due, or else when the next study day starts.
+pub fn counts_change_at(collection: &Collection, days: &Days, now: i64) -> i64 {
+    collection
+        .get_decks()
+        .iter()
+        .flat_map(|deck| deck.get_all_cards())
+        .filter(|card| matches!(card.schedule.state, CardState::Learning | CardState::Relearning) && card.schedule.due > now)
+        .fold(days.next_day(now), |at, card| at.min(card.schedule.due))
+}
+
+/// What is looked up for every card when finding the due ones: the reviews done today of each note,
+/// and the cards held by a filtered deck.
+struct Today {
+    answered: HashMap<Uuid, DueCounts>,
+    filtered: HashSet<(Uuid, usize)>,
+}
+
+impl Today {
+    fn new(collection: &Collection, days: &Days, now: i64) -> Self {
+        let today = days.day(now);
+        let mut answered: HashMap<Uuid, DueCounts> = HashMap::new();
+        for review in collection.get_review_log().iter().filter(|review| days.day(review.time) == today) {
+            let counts = answered.entry(review.note).or_default();
+            *counts = *counts + DueCounts::of(review.state);
+        }
+        Today { answered, filtered: collection.get_filtered_cards() }
+    }
+}
+
+/// The due cards of `deck` and its subdecks within the limits, with the reviews done today of all their
+/// notes, recording how many there are for each deck of the subtree in `counts`.
+fn subtree_due_cards(
+    deck: &Deck,
+    scheduler: &Scheduler,
+    today: &Today,
+    days: &Days,
+    now: i64,
+    keep: &dyn Fn(&Note) -> bool,
+    counts: &mut HashMap<Uuid, DueCounts>,
+) -> (Vec<Card>, DueCounts) {
+    let mut answered =
+        deck.get_notes().iter().filter_map(|note| today.answered.get(&note.uuid)).fold(DueCounts::default(), |sum, counts| sum + *counts);
+    let mut cards: Vec<Card> = deck
+        .get_notes()
+        .iter()
+        .filter(|note| keep(note))
+        .flat_map(|note| note.get_cards())
+        .filter(|card| card.schedule.is_due_today(days, now) && !card.schedule.is_hidden(now) && !today.filtered.contains(&(card.note, card.ordinal)))
+        .collect();
+    for subdeck in deck.get_subdecks() {
+        let (subdeck_cards, subdeck_answered) = subtree_due_cards(subdeck, scheduler, today, days, now, keep, counts);
+        cards.extend(subdeck_cards);
+        answered = answered + subdeck_answered;
+    }
+    let cards = due_today(cards, scheduler, answered);
+    counts.insert(deck.uuid, DueCounts::count(&cards));
+    (cards, answered)
+}
+
+/// How many cards out of new come due on each of `length` study days from the one `now` falls in,
+/// with overdue cards counted today. Suspended cards are left out; buried ones come back by their due day.
+pub fn forecast<'a>(cards: impl IntoIterator<Item = &'a Card>, days: &Days, now: i64, length: usize) -> Vec<u64> {
+    let today = days.day(now);
+    let mut counts = vec![0; length];
+    for card in cards.into_iter().filter(|card| card.schedule.state != CardState::New && !card.schedule.suspended) {
+        let day = (days.day(card.schedule.due) - today).max(0) as usize;
+        if let Some(count) = counts.get_mut(day) {
+            *count += 1;
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::filtered_deck::FilteredDeck;
+    use crate::models::note::NoteType;
+    use crate::models::schedule::{Grade, Schedule};
+    use crate::time::{DAY, HOUR, MINUTE};
+
+    const UTC: Days = Days::new(0, 0);
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn card(state: CardState, due: i64) -> Card {
+        Card { note: Uuid::new_v4(), ordinal: 0, front: String::new(), back: String::new(), schedule: Schedule { state, due, ..Schedule::default() } }
+    }
+
+    fn review(note: Uuid, state: CardState, time: i64) -> Review {
+        Review { note, ordinal: 0, time, grade: Grade::Good, state, interval: 1, last_interval: 0, ease: 2500, duration_ms: 1000 }
+    }
+
+    fn note(state: CardState, due: i64) -> Note {
+        let mut note = Note::new("front".to_string(), "back".to_string(), NoteType::Basic);
+        note.set_schedule(0, Schedule { state, due, ..Schedule::default() });
+        note
+    }
+
+    #[test]
+    fn test_due_today_applies_what_is_left_of_the_limits() {
+        let scheduler = Scheduler { new_per_day: 2, reviews_per_day: 1, ..Scheduler::default() };
+        let cards = vec![
+            card(CardState::New, 0),
+            card(CardState::Review, NOW),
+            card(CardState::New, 0),
+            card(CardState::Learning, NOW),
+            card(CardState::Review, NOW),
+            card(CardState::New, 0),
+        ];
+
+        let due = due_today(cards.clone(), &scheduler, DueCounts::default());
+        assert_eq!(DueCounts::count(&due), DueCounts { new: 2, learning: 1, review: 1 });
+        assert_eq!(due[1], cards[1]);
+
+        let answered = DueCounts { new: 1, learning: 5, review: 1 };
+        assert_eq!(DueCounts::count(&due_today(cards, &scheduler, answered)), DueCounts { new: 1, learning: 1, review: 0 });
+    }
+
+    #[test]
+    fn test_answered_counts_only_today() {
+        let note = Uuid::new_v4();
+        let reviews = [review(note, CardState::New, NOW), review(note, CardState::Learning, NOW), review(note, CardState::Review, NOW - DAY)];
+
+        assert_eq!(DueCounts::answered(&reviews, &UTC, NOW), DueCounts { new: 1, learning: 1, review: 0 });
+    }
+
+    #[test]
+    fn test_deck_counts_aggregate_subdecks_within_limits() {
+        let scheduler = Scheduler { new_per_day: 3, ..Scheduler::default() };
+        let mut collection = Collection::new();
+        let mut spanish = Deck::new("Spanish".to_string());
+        let mut verbs = Deck::new("Verbs".to_string());
+        for _ in 0..2 {
+            spanish.add_note(note(CardState::New, 0));
+            verbs.add_note(note(CardState::New, 0));
+        }
+        spanish.add_note(note(CardState::Review, NOW - DAY));
+        spanish.add_note(note(CardState::Review, NOW + DAY));
+        let answered = note(CardState::Learning, NOW + 60);
+        collection.log_review(review(answered.uuid, CardState::New, NOW));
+        verbs.add_note(answered);
+        spanish.add_subdeck(verbs);
+        collection.add_deck(spanish);
+        let spanish = &collection.get_decks()[0];
+
+        let counts = deck_counts(&collection, &scheduler, &UTC, NOW);
+        assert_eq!(counts[&spanish.get_subdecks()[0].uuid], DueCounts { new: 2, learning: 0, review: 0 });
+        assert_eq!(counts[&spanish.uuid], DueCounts { new: 2, learning: 0, review: 1 });
+        let cards = deck_due_cards(&collection, spanish, &scheduler, &UTC, NOW, &|_| true);
+        assert_eq!(DueCounts::count(&cards), counts[&spanish.uuid]);
+        assert!(cards.iter().all(|card| spanish.get_all_notes().iter().any(|note| note.uuid == card.note)));
+        let none = deck_due_cards(&collection, spanish, &scheduler, &UTC, NOW, &|note| note.has_tag("verb"));
+        assert!(none.is_empty());
+
+        let (uuid, overdue) = (spanish.uuid, spanish.get_notes()[2].uuid);
+        let mut filtered = FilteredDeck::new("Overdue".to_string(), "is:due".to_string());
+        filtered.cards = vec![(overdue, 0)];
+        collection.add_filtered_deck(filtered);
+        assert_eq!(deck_counts(&collection, &scheduler, &UTC, NOW)[&uuid], DueCounts { new: 2, learning: 0, review: 0 });
+    }
+
+    #[test]
+    fn test_reviews_are_due_for_their_whole_study_day() {
+        let scheduler = Scheduler::default();
+        let mut collection = Collection::new();
+        let mut deck = Deck::new("Spanish".to_string());
+        deck.add_note(note(CardState::Review, NOW + 30 * MINUTE));
+        deck.add_note(note(CardState::Learning, NOW + 30 * MINUTE));
+        deck.add_note(note(CardState::Review, NOW + 2 * HOUR));
+        collection.add_deck(deck);
+        let deck = &collection.get_decks()[0];
+
+        assert_eq!(deck_counts(&collection, &scheduler, &UTC, NOW)[&deck.uuid], DueCounts { new: 0, learning: 0, review: 1 });
+        let cards =
+            [card(CardState::Review, NOW + 30 * MINUTE), card(CardState::Learning, NOW + 30 * MINUTE), card(CardState::Review, NOW + 2 * HOUR)];
+        assert_eq!(forecast(&cards, &UTC, NOW, 2), vec![2, 1]);
+    }
+
+    #[test]
+    fn test_counts_change_when_a_learning_step_comes_due() {
+        let mut collection = Collection::new();
+        let mut deck = Deck::new("Spanish".to_string());
+        deck.add_note(note(CardState::Review, NOW + 30 * MINUTE));
+        collection.add_deck(deck);
+        assert_eq!(counts_change_at(&collection, &UTC, NOW), UTC.next_day(NOW));
+
+        let mut deck = Deck::new("German".to_string());
+        deck.add_note(note(CardState::Learning, NOW - MINUTE));
+        deck.add_note(note(CardState::Relearning, NOW + 10 * MINUTE));
+        collection.add_deck(deck);
+        assert_eq!(counts_change_at(&collection, &UTC, NOW), NOW + 10 * MINUTE);
+    }
+
+    #[test]
+    fn test_forecast_counts_overdue_cards_today() {
+        let mut suspended = card(CardState::Review, NOW + DAY);
+        suspended.schedule.suspended = true;
+        let cards = [
+            card(CardState::Review, NOW - 3 * DAY),
+            card(CardState::Learning, NOW + 60),
+            card(CardState::Review, NOW + DAY),
+            card(CardState::Review, NOW + 2 * DAY),
+            card(CardState::Review, NOW + 30 * DAY),
+            card(CardState::New, 0),
+            suspended,
+        ];
+
+        assert_eq!(forecast(&cards, &UTC, NOW, 3), vec![2, 1, 1]);
+    }
+}
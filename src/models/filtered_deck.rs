@@ -0,0 +1,105 @@
+use serde::Deserialize;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::models::card::Card;
+use crate::models::collection::Collection;
+use crate::models::schedule::CardState;
+use crate::models::search::{Query, search_cards};
+
+/// How many cards a filtered deck gathers unless told otherwise.
+pub const DEFAULT_LIMIT: usize = 100;
+
+/// A temporary deck of cards pulled out of their home decks by a search, for cramming. While a card
+/// is in a filtered deck it is practiced there instead of in its home deck; emptying the deck
+/// returns it.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Debug)]
+pub struct FilteredDeck {
+    pub uuid: Uuid,
+    pub name: String,
+    /// The search that picks the cards, in the browser's syntax.
+    pub query: String,
+    pub limit: usize,
+    /// Whether answers reschedule the cards as in their home decks, or leave their schedules alone.
+    pub reschedule: bool,
+    /// The cards in the deck by note and ordinal, in the order they are practiced.
+    pub cards: Vec<(Uuid, usize)>,
+}
+
+impl FilteredDeck {
+    pub fn new(name: String, query: String) -> Self {
+        FilteredDeck { uuid: Uuid::new_v4(), name, query, limit: DEFAULT_LIMIT, reschedule: true, cards: Vec::new() }
+    }
+
+    pub fn contains(&self, card: &Card) -> bool {
+        self.cards.contains(&(card.note, card.ordinal))
+    }
+
+    /// The cards matching the query at `now` that are not suspended or buried and that no other
+    /// filtered deck holds, up to the limit: cards due first, in order of when they were due, then
+    /// new cards.
+    pub fn gather(&self, collection: &Collection, now: i64) -> Result<Vec<(Uuid, usize)>, String> {
+        let query = Query::parse(&self.query)?;
+        let others: Vec<&FilteredDeck> = collection.get_filtered_decks().iter().filter(|deck| deck.uuid != self.uuid).collect();
+        let mut cards: Vec<Card> = search_cards(collection, &query, now)
+            .into_iter()
+            .filter(|card| !card.schedule.is_hidden(now) && !others.iter().any(|deck| deck.contains(card)))
+            .collect();
+        cards.sort_by_key(|card| (card.schedule.state == CardState::New, card.schedule.due));
+        Ok(cards.into_iter().take(self.limit).map(|card| (card.note, card.ordinal)).collect())
+    }
+
+    /// The cards of the deck as they are in `collection` now, skipping notes deleted since it was built.
+    pub fn get_cards(&self, collection: &Collection) -> Vec<Card> {
+        self.cards
+            .iter()
+            .filter_map(|(note, ordinal)| collection.find_note(*note)?.get_cards().into_iter().find(|card| card.ordinal == *ordinal))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::deck::Deck;
+    use crate::models::note::{Note, NoteType};
+    use crate::models::schedule::Schedule;
+
+    fn collection() -> Collection {
+        let mut collection = Collection::new();
+        let mut deck = Deck::new("Spanish".to_string());
+        for (front, due) in [("uno", 300), ("dos", 100), ("tres", 200)] {
+            let mut note = Note::new(front.to_string(), "back".to_string(), NoteType::BasicAndReverse);
+            note.tags = vec!["exam".to_string()];
+            note.set_schedule(0, Schedule { state: CardState::Review, due, ..Schedule::default() });
+            deck.add_note(note);
+        }
+        deck.add_note(Note::new("cuatro".to_string(), "four".to_string(), NoteType::Basic));
+        collection.add_deck(deck);
+        collection
+    }
+
+    fn fronts(deck: &FilteredDeck, collection: &Collection) -> Vec<String> {
+        deck.get_cards(collection).into_iter().map(|card| card.front).collect()
+    }
+
+    #[test]
+    fn test_gather_orders_due_cards_first_and_respects_limit() {
+        let mut collection = collection();
+        let mut deck = FilteredDeck::new("Exam".to_string(), "tag:exam".to_string());
+        deck.limit = 4;
+
+        deck.cards = deck.gather(&collection, 1000).unwrap();
+
+        assert_eq!(fronts(&deck, &collection), vec!["dos", "tres", "uno", "back"]);
+        collection.add_filtered_deck(deck.clone());
+        let other = FilteredDeck::new("All".to_string(), String::new());
+        let gathered = FilteredDeck { cards: other.gather(&collection, 1000).unwrap(), ..other };
+        assert_eq!(fronts(&gathered, &collection), vec!["back", "back", "cuatro"]);
+    }
+
+    #[test]
+    fn test_gather_rejects_invalid_searches() {
+        assert!(FilteredDeck::new("Broken".to_string(), "(exam".to_string()).gather(&collection(), 0).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use color_eyre::eyre::Result;
+
+use crate::models::{collection::Collection, operation::Operation};
+
+const HISTORY_CAPACITY: usize = 500;
+
+/// Undo/redo stacks of the operations applied to the collection during this session.
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Operation>,
+    redo: Vec<Operation>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies `operation` to the collection and records it, dropping anything that could be redone.
+    pub fn commit(&mut self, collection: &mut Collection, operation: Operation) -> Result<()> {
+        operation.apply(collection)?;
+        self.undo.push(operation);
+        self.redo.clear();
+        if self.undo.len() > HISTORY_CAPACITY {
+            self.undo.remove(0);
+        }
+        Ok(())
+    }
+
+    /// Reverts the most recent operation, returning it, or `None` if there is nothing to undo. An
+    /// operation that cannot be reverted stays the one to undo.
+    pub fn undo(&mut self, collection: &mut Collection) -> Result<Option<Operation>> {
+        let Some(operation) = self.undo.pop() else {
+            return Ok(None);
+        };
+        if let Err(err) = operation.inverse().apply(collection) {
+            self.undo.push(operation);
+            return Err(err);
+        }
+        self.redo.push(operation.clone());
+        Ok(Some(operation))
+    }
+
+    /// Re-applies the most recently undone operation, returning it, or `None` if there is nothing to redo.
+    /// An operation that cannot be re-applied stays the one to redo.
+    pub fn redo(&mut self, collection: &mut Collection) -> Result<Option<Operation>> {
+        let Some(operation) = self.redo.pop() else {
+            return Ok(None);
+        };
+        if let Err(err) = operation.apply(collection) {
+            self.redo.push(operation);
+            return Err(err);
+        }
+        self.undo.push(operation.clone());
+        Ok(Some(operation))
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::deck::Deck;
+
+    fn deck_names(collection: &Collection) -> Vec<String> {
+        collection.get_decks().iter().map(|deck| deck.name.clone()).collect()
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut collection = Collection::new();
+        let mut history = History::new();
+        let add = Operation::add_deck(&collection, collection.uuid, Deck::new("A".to_string())).unwrap();
+        history.commit(&mut collection, add).unwrap();
+        let uuid = collection.get_decks()[0].uuid;
+        let rename = Operation::rename_deck(&collection, uuid, "B".to_string()).unwrap();
+        history.commit(&mut collection, rename).unwrap();
+
+        history.undo(&mut collection).unwrap();
+        assert_eq!(deck_names(&collection), vec!["A"]);
+        history.undo(&mut collection).unwrap();
+        assert!(deck_names(&collection).is_empty());
+        assert!(history.undo(&mut collection).unwrap().is_none());
+
+        history.redo(&mut collection).unwrap();
+        history.redo(&mut collection).unwrap();
+        assert_eq!(deck_names(&collection), vec!["B"]);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn test_commit_clears_redo() {
+        let mut collection = Collection::new();
+        let mut history = History::new();
+        let add = Operation::add_deck(&collection, collection.uuid, Deck::new("A".to_string())).unwrap();
+        history.commit(&mut collection, add).unwrap();
+        history.undo(&mut collection).unwrap();
+        assert!(history.can_redo());
+
+        let add = Operation::add_deck(&collection, collection.uuid, Deck::new("C".to_string())).unwrap();
+        history.commit(&mut collection, add).unwrap();
+
+        assert!(!history.can_redo());
+        assert_eq!(deck_names(&collection), vec!["C"]);
+    }
+
+    #[test]
+    fn test_failed_commit_is_not_recorded() {
+        let mut collection = Collection::new();
+        let mut history = History::new();
+
+        let missing = Operation::DeleteDeck { deck: collection.uuid };
+        assert!(history.commit(&mut collection, missing).is_err());
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn test_failed_undo_and_redo_keep_the_operation() {
+        let mut collection = Collection::new();
+        let mut history = History::new();
+        let add = Operation::add_deck(&collection, collection.uuid, Deck::new("A".to_string())).unwrap();
+        history.commit(&mut collection, add).unwrap();
+        let uuid = collection.get_decks()[0].uuid;
+        let rename = Operation::rename_deck(&collection, uuid, "B".to_string()).unwrap();
+        history.commit(&mut collection, rename).unwrap();
+
+        let delete = Operation::DeleteDeck { deck: uuid };
+        delete.apply(&mut collection).unwrap();
+        assert!(history.undo(&mut collection).is_err());
+        delete.inverse().apply(&mut collection).unwrap();
+        history.undo(&mut collection).unwrap();
+        assert_eq!(deck_names(&collection), vec!["A"]);
+
+        delete.apply(&mut collection).unwrap();
+        assert!(history.redo(&mut collection).is_err());
+        delete.inverse().apply(&mut collection).unwrap();
+        history.redo(&mut collection).unwrap();
+        assert_eq!(deck_names(&collection), vec!["B"]);
+    }
+}
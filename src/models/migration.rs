@@ -0,0 +1,98 @@
+use color_eyre::eyre::{Result, bail, eyre};
+use serde_json::{Map, Value, json};
+use uuid::Uuid;
+
+/// Schema version written into every saved collection file.
+pub const CURRENT_VERSION: u64 = 1;
+
+type Migration = fn(&mut Map<String, Value>) -> Result<()>;
+
+/// Upgrades, in order, a collection file of version `i` to version `i + 1`.
+const MIGRATIONS: [Migration; CURRENT_VERSION as usize] = [unversioned_to_v1];
+
+/// Upgrades a serialized collection of any known version to [`CURRENT_VERSION`], one step at a time.
+pub fn migrate(mut value: Value) -> Result<Value> {
+    let collection = value.as_object_mut().ok_or_else(|| eyre!("collection is not a JSON object"))?;
+    let version = match collection.get("version") {
+        Some(version) => version.as_u64().ok_or_else(|| eyre!("invalid collection version {version}"))?,
+        None => 0,
+    };
+    if version > CURRENT_VERSION {
+        bail!("collection has schema version {version}, but this version of tanki only supports up to {CURRENT_VERSION}");
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(collection)?;
+    }
+    collection.insert("version".to_string(), json!(CURRENT_VERSION));
+    Ok(value)
+}
+
+/// Files written before versioning: notes may lack ids and schedules, and the collection may lack
+/// the trash and review log.
+fn unversioned_to_v1(collection: &mut Map<String, Value>) -> Result<()> {
+    collection.entry("trash").or_insert_with(|| json!([]));
+    collection.entry("review_log").or_insert_with(|| json!([]));
+    let decks = collection.get_mut("decks").and_then(Value::as_array_mut).ok_or_else(|| eyre!("collection has no decks"))?;
+    decks.iter_mut().try_for_each(add_note_ids_and_schedules)?;
+    let trash = collection.get_mut("trash").and_then(Value::as_array_mut).ok_or_else(|| eyre!("invalid trash"))?;
+    trash.iter_mut().filter_map(|deleted| deleted.get_mut("deck")).try_for_each(add_note_ids_and_schedules)
+}
+
+fn add_note_ids_and_schedules(deck: &mut Value) -> Result<()> {
+    let deck = deck.as_object_mut().ok_or_else(|| eyre!("deck is not a JSON object"))?;
+    for note in deck.get_mut("notes").and_then(Value::as_array_mut).into_iter().flatten() {
+        let note = note.as_object_mut().ok_or_else(|| eyre!("note is not a JSON object"))?;
+        note.entry("uuid").or_insert_with(|| json!(Uuid::new_v4()));
+        note.entry("schedules").or_insert_with(|| json!([]));
+    }
+    deck.get_mut("subdecks").and_then(Value::as_array_mut).into_iter().flatten().try_for_each(add_note_ids_and_schedules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::collection::Collection;
+    use crate::models::schedule::CardState;
+
+    fn load_fixture(content: &str) -> Collection {
+        serde_json::from_value(migrate(serde_json::from_str(content).unwrap()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_load_unversioned_baseline_fixture() {
+        let collection = load_fixture(include_str!("fixtures/collection_unversioned.json"));
+
+        let spanish = &collection.get_decks()[0];
+        assert_eq!(spanish.get_notes().len(), 2);
+        assert_eq!(spanish.get_subdecks()[0].qualified_name(), "Spanish:Verbs");
+        assert_ne!(spanish.get_notes()[0].uuid, spanish.get_notes()[1].uuid);
+        assert!(spanish.get_notes().iter().all(|note| note.schedules.is_empty()));
+        assert!(collection.get_trash().is_empty());
+        assert!(collection.get_review_log().is_empty());
+    }
+
+    #[test]
+    fn test_load_unversioned_scheduled_fixture() {
+        let collection = load_fixture(include_str!("fixtures/collection_unversioned_scheduled.json"));
+
+        let note = &collection.get_decks()[0].get_notes()[0];
+        assert_eq!(note.schedule(0).state, CardState::Review);
+        assert_eq!(collection.get_review_log().len(), 1);
+        assert_eq!(collection.get_trash().len(), 1);
+        assert!(!collection.get_trash()[0].deck.get_notes()[0].uuid.is_nil());
+    }
+
+    #[test]
+    fn test_load_v1_fixture() {
+        let content = include_str!("fixtures/collection_v1.json");
+        let collection = load_fixture(content);
+
+        assert_eq!(collection.get_decks()[0].get_notes()[0].uuid.to_string(), "7d1c3f5e-8a1b-4c3e-9f1a-2b3c4d5e6f70");
+        assert_eq!(migrate(serde_json::from_str(content).unwrap()).unwrap(), serde_json::from_str::<Value>(content).unwrap());
+    }
+
+    #[test]
+    fn test_newer_version_is_rejected() {
+        assert!(migrate(json!({ "version": CURRENT_VERSION + 1, "uuid": Uuid::nil(), "decks": [] })).is_err());
+    }
+}
@@ -1,36 +1,80 @@
 use serde::Deserialize;
 use serde::Serialize;
+use strum::{Display, EnumString};
+use uuid::Uuid;
 
 use crate::models::card::Card;
+use crate::models::schedule::Schedule;
+use crate::models::tag::tag_matches;
+use crate::time;
 
 #[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Debug)]
 pub struct Note {
+    #[serde(default = "Uuid::new_v4")]
+    pub uuid: Uuid,
     pub front: String,
     pub back: String,
     pub note_type: NoteType,
+    #[serde(default)]
+    pub schedules: Vec<Schedule>,
+    /// Unix time in seconds when the note was added, or 0 if it was added before this was recorded.
+    #[serde(default)]
+    pub created: i64,
+    /// Hierarchical tags, like `grammar::verbs`, in the order they were written.
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 impl Note {
     pub fn new(front: String, back: String, note_type: NoteType) -> Self {
-        Note { front, back, note_type }
+        Note { uuid: Uuid::new_v4(), front, back, note_type, schedules: Vec::new(), created: time::now(), tags: Vec::new() }
+    }
+
+    /// Whether the note has `tag` or one of its descendants.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|own| tag_matches(own, tag))
     }
 
     pub fn get_cards(&self) -> Vec<Card> {
         match self.note_type {
-            NoteType::Basic => vec![Card { front: self.front.clone(), back: self.back.clone() }],
-            NoteType::BasicAndReverse => {
-                vec![Card { front: self.front.clone(), back: self.back.clone() }, Card { front: self.back.clone(), back: self.front.clone() }]
-            }
+            NoteType::Basic => vec![self.card(0, &self.front, &self.back)],
+            NoteType::BasicAndReverse => vec![self.card(0, &self.front, &self.back), self.card(1, &self.back, &self.front)],
+        }
+    }
+
+    fn card(&self, ordinal: usize, front: &str, back: &str) -> Card {
+        Card { note: self.uuid, ordinal, front: front.to_string(), back: back.to_string(), schedule: self.schedule(ordinal) }
+    }
+
+    pub fn schedule(&self, ordinal: usize) -> Schedule {
+        self.schedules.get(ordinal).cloned().unwrap_or_default()
+    }
+
+    pub fn set_schedule(&mut self, ordinal: usize, schedule: Schedule) {
+        if self.schedules.len() <= ordinal {
+            self.schedules.resize(ordinal + 1, Schedule::default());
         }
+        self.schedules[ordinal] = schedule;
     }
 }
 
-#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Debug)]
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Debug, Display, EnumString)]
 pub enum NoteType {
+    #[strum(to_string = "basic")]
     Basic,
+    #[strum(to_string = "basic+reverse")]
     BasicAndReverse,
 }
 
+impl NoteType {
+    pub fn next(&self) -> NoteType {
+        match self {
+            NoteType::Basic => NoteType::BasicAndReverse,
+            NoteType::BasicAndReverse => NoteType::Basic,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -65,4 +109,41 @@ mod tests {
         assert_eq!(cards[1].front, "Answer");
         assert_eq!(cards[1].back, "Question");
     }
+
+    #[test]
+    fn test_cards_carry_their_own_schedule() {
+        let mut note = Note::new("Question".to_string(), "Answer".to_string(), NoteType::BasicAndReverse);
+        let schedule = Schedule { interval: 3, ..Schedule::default() };
+
+        note.set_schedule(1, schedule.clone());
+
+        let cards = note.get_cards();
+        assert_eq!(cards[0].schedule, Schedule::default());
+        assert_eq!(cards[1].schedule, schedule);
+        assert_eq!(cards[1].note, note.uuid);
+        assert_eq!(cards[1].ordinal, 1);
+    }
+
+    #[test]
+    fn test_note_type_next_cycles() {
+        assert_eq!(NoteType::Basic.next(), NoteType::BasicAndReverse);
+        assert_eq!(NoteType::BasicAndReverse.next(), NoteType::Basic);
+    }
+
+    #[test]
+    fn test_has_tag_matches_parent_tags() {
+        let note = Note { tags: vec!["grammar::verbs".to_string()], ..Note::new("Q".to_string(), "A".to_string(), NoteType::Basic) };
+
+        assert!(note.has_tag("grammar"));
+        assert!(note.has_tag("grammar::verbs"));
+        assert!(!note.has_tag("grammar::nouns"));
+    }
+
+    #[test]
+    fn test_deserialize_note_without_uuid() {
+        let note: Note = serde_json::from_str(r#"{"front":"Q","back":"A","note_type":"Basic"}"#).unwrap();
+
+        assert_eq!(note.front, "Q");
+        assert!(!note.uuid.is_nil());
+    }
 }
@@ -0,0 +1,654 @@
+use color_eyre::eyre::{Result, eyre};
+use serde::Deserialize;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::models::{
+    card::Card, collection::Collection, deck::Deck, filtered_deck::FilteredDeck, note::Note, review::Review, schedule::Grade, schedule::Schedule,
+    schedule::Scheduler, tag::LEECH_TAG,
+};
+
+/// The operations `Operation::answer_card` built, in the order they apply.
+pub struct Answered {
+    pub operations: Vec<Operation>,
+    /// The new schedule of the answered card.
+    pub after: Schedule,
+    /// Whether the card just became a leech.
+    pub leech: bool,
+}
+
+/// A recorded mutation of the collection. Components describe changes as operations and send them
+/// through `Action::Commit`, so that every change can be inverted by the undo history.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Debug)]
+pub enum Operation {
+    AddNote {
+        deck: Uuid,
+        position: usize,
+        note: Note,
+    },
+    RemoveNote {
+        deck: Uuid,
+        position: usize,
+        note: Note,
+    },
+    EditNote {
+        deck: Uuid,
+        before: Box<Note>,
+        after: Box<Note>,
+    },
+    MoveNote {
+        note: Uuid,
+        from: Uuid,
+        from_position: usize,
+        to: Uuid,
+        to_position: usize,
+    },
+    InsertDeck {
+        parent: Uuid,
+        position: usize,
+        deck: Deck,
+    },
+    RemoveDeck {
+        parent: Uuid,
+        position: usize,
+        deck: Deck,
+    },
+    RenameDeck {
+        deck: Uuid,
+        before: String,
+        after: String,
+    },
+    MoveDeck {
+        deck: Uuid,
+        from: Uuid,
+        from_position: usize,
+        to: Uuid,
+        to_position: usize,
+    },
+    DeleteDeck {
+        deck: Uuid,
+    },
+    RestoreDeck {
+        deck: Uuid,
+    },
+    Answer {
+        before: Schedule,
+        after: Schedule,
+        review: Review,
+    },
+    UndoAnswer {
+        before: Schedule,
+        after: Schedule,
+        review: Review,
+    },
+    /// Changes a card's schedule without answering it, e.g. to suspend, bury or flag it.
+    SetSchedule {
+        note: Uuid,
+        ordinal: usize,
+        before: Schedule,
+        after: Schedule,
+    },
+    AddFilteredDeck {
+        position: usize,
+        deck: FilteredDeck,
+    },
+    RemoveFilteredDeck {
+        position: usize,
+        deck: FilteredDeck,
+    },
+    EditFilteredDeck {
+        before: Box<FilteredDeck>,
+        after: Box<FilteredDeck>,
+    },
+    Batch(Vec<Operation>),
+}
+
+impl Operation {
+    pub fn add_note(collection: &Collection, deck: Uuid, note: Note) -> Option<Operation> {
+        let position = collection.find_deck(deck)?.get_notes().len();
+        Some(Operation::AddNote { deck, position, note })
+    }
+
+    pub fn edit_note(collection: &Collection, note: Note) -> Option<Operation> {
+        let deck = collection.find_note_deck(note.uuid)?;
+        let before = deck.find_note(note.uuid)?.clone();
+        if before == note {
+            return None;
+        }
+        Some(Operation::EditNote { deck: deck.uuid, before: Box::new(before), after: Box::new(note) })
+    }
+
+    pub fn remove_note(collection: &Collection, note: Uuid) -> Option<Operation> {
+        let deck = collection.find_note_deck(note)?;
+        let position = deck.note_position(note)?;
+        Some(Operation::RemoveNote { deck: deck.uuid, position, note: deck.get_notes()[position].clone() })
+    }
+
+    /// Removes the given notes, wherever they are.
+    pub fn remove_notes(collection: &Collection, notes: &[Uuid]) -> Option<Operation> {
+        let mut removals: Vec<Operation> = notes.iter().filter_map(|note| Operation::remove_note(collection, *note)).collect();
+        // Removing from the back of each deck first keeps the recorded positions valid.
+        removals.sort_by_key(|removal| match removal {
+            Operation::RemoveNote { deck, position, .. } => std::cmp::Reverse((*deck, *position)),
+            _ => unreachable!(),
+        });
+        removals.dedup();
+        if removals.is_empty() { None } else { Some(Operation::Batch(removals)) }
+    }
+
+    /// Replaces each of `notes` with its edited version, skipping unchanged ones.
+    pub fn edit_notes(collection: &Collection, notes: Vec<Note>) -> Option<Operation> {
+        let edits: Vec<Operation> = notes.into_iter().filter_map(|note| Operation::edit_note(collection, note)).collect();
+        if edits.is_empty() { None } else { Some(Operation::Batch(edits)) }
+    }
+
+    /// Moves the given notes to the end of `target`, skipping notes that already live there.
+    pub fn move_notes(collection: &Collection, notes: &[Uuid], target: Uuid) -> Option<Operation> {
+        let target_len = collection.find_deck(target)?.get_notes().len();
+        let mut sources: Vec<(Uuid, usize, Uuid)> = notes
+            .iter()
+            .filter_map(|note| {
+                let deck = collection.find_note_deck(*note)?;
+                Some((deck.uuid, deck.note_position(*note)?, *note))
+            })
+            .filter(|(deck, _, _)| *deck != target)
+            .collect();
+        sources.sort();
+        sources.dedup();
+
+        // Positions are recorded as they will be when the moves are applied one after another.
+        let operations: Vec<Operation> = sources
+            .iter()
+            .enumerate()
+            .map(|(i, (from, position, note))| {
+                let already_moved = sources[..i].iter().filter(|(deck, _, _)| deck == from).count();
+                Operation::MoveNote { note: *note, from: *from, from_position: position - already_moved, to: target, to_position: target_len + i }
+            })
+            .collect();
+        if operations.is_empty() { None } else { Some(Operation::Batch(operations)) }
+    }
+
+    pub fn add_deck(collection: &Collection, parent: Uuid, deck: Deck) -> Option<Operation> {
+        if parent != collection.uuid && collection.find_deck(parent).is_none() {
+            return None;
+        }
+        let position = collection.get_subdecks_of(parent).len();
+        Some(Operation::InsertDeck { parent, position, deck })
+    }
+
+    pub fn rename_deck(collection: &Collection, deck: Uuid, name: String) -> Option<Operation> {
+        let before = collection.find_deck(deck)?.name.clone();
+        if before == name {
+            return None;
+        }
+        Some(Operation::RenameDeck { deck, before, after: name })
+    }
+
+    /// Moves a deck to the end of `target`'s subdecks, or to the top level when `target` is the
+    /// collection's uuid. Returns `None` if the move would place the deck inside itself.
+    pub fn move_deck(collection: &Collection, deck: Uuid, target: Uuid) -> Option<Operation> {
+        if collection.find_deck(deck)?.find_deck(target).is_some() {
+            return None;
+        }
+        if target != collection.uuid && collection.find_deck(target).is_none() {
+            return None;
+        }
+        let (from, from_position) = collection.deck_position(deck)?;
+        let siblings = collection.get_subdecks_of(target).len();
+        let to_position = if from == target { siblings - 1 } else { siblings };
+        Some(Operation::MoveDeck { deck, from, from_position, to: target, to_position })
+    }
+
+    pub fn delete_deck(collection: &Collection, deck: Uuid) -> Option<Operation> {
+        collection.find_deck(deck).map(|_| Operation::DeleteDeck { deck })
+    }
+
+    pub fn restore_deck(collection: &Collection, deck: Uuid) -> Option<Operation> {
+        collection.get_trash().iter().any(|deleted| deleted.deck.uuid == deck).then_some(Operation::RestoreDeck { deck })
+    }
+
+    pub fn add_filtered_deck(collection: &Collection, deck: FilteredDeck) -> Operation {
+        Operation::AddFilteredDeck { position: collection.get_filtered_decks().len(), deck }
+    }
+
+    /// Removes a filtered deck, which returns its cards to their home decks.
+    pub fn remove_filtered_deck(collection: &Collection, deck: Uuid) -> Option<Operation> {
+        let position = collection.get_filtered_decks().iter().position(|filtered| filtered.uuid == deck)?;
+        Some(Operation::RemoveFilteredDeck { position, deck: collection.get_filtered_decks()[position].clone() })
+    }
+
+    /// Replaces a filtered deck with its edited version, e.g. rebuilt or emptied.
+    pub fn edit_filtered_deck(collection: &Collection, deck: FilteredDeck) -> Option<Operation> {
+        let before = collection.find_filtered_deck(deck.uuid)?.clone();
+        if before == deck {
+            return None;
+        }
+        Some(Operation::EditFilteredDeck { before: Box::new(before), after: Box::new(deck) })
+    }
+
+    pub fn answer(card: &Card, grade: Grade, now: i64, duration_ms: u64, scheduler: &Scheduler) -> Operation {
+        let after = card.schedule.answer_with(grade, now, scheduler);
+        let review = Review {
+            note: card.note,
+            ordinal: card.ordinal,
+            time: now,
+            grade,
+            state: card.schedule.state,
+            interval: after.interval,
+            last_interval: card.schedule.interval,
+            ease: after.ease,
+            duration_ms,
+        };
+        Operation::Answer { before: card.schedule.clone(), after, review }
+    }
+
+    /// Answers `card` along with what follows from it: a card that became a leech gets its note
+    /// tagged, and is suspended too if the scheduler says so, and with `bury_siblings` the other
+    /// cards of the note are buried until `bury_until`.
+    pub fn answer_card(
+        collection: &Collection,
+        card: &Card,
+        grade: Grade,
+        now: i64,
+        duration_ms: u64,
+        scheduler: &Scheduler,
+        bury_until: i64,
+    ) -> Answered {
+        let answer = Operation::answer(card, grade, now, duration_ms, scheduler);
+        let Operation::Answer { after, .. } = &answer else { unreachable!() };
+        let after = after.clone();
+        let leech = scheduler.is_leech(&card.schedule, &after);
+        let mut operations = Vec::new();
+        if leech {
+            // Tagging replaces the whole note, so it goes before the answer changes its schedule.
+            operations.extend(
+                collection.find_note(card.note).filter(|note| !note.tags.iter().any(|tag| tag.eq_ignore_ascii_case(LEECH_TAG))).and_then(|note| {
+                    Operation::edit_note(
+                        collection,
+                        Note { tags: note.tags.iter().cloned().chain([LEECH_TAG.to_string()]).collect(), ..note.clone() },
+                    )
+                }),
+            );
+        }
+        operations.push(answer);
+        let suspended = leech && scheduler.suspend_leeches;
+        if suspended {
+            operations.extend(Operation::change_schedules(&[Card { schedule: after.clone(), ..card.clone() }], |schedule| Schedule {
+                suspended: true,
+                ..schedule.clone()
+            }));
+        }
+        if scheduler.bury_siblings {
+            let siblings: Vec<Card> = collection
+                .find_note(card.note)
+                .map(|note| note.get_cards().into_iter().filter(|sibling| sibling.ordinal != card.ordinal).collect())
+                .unwrap_or_default();
+            operations.extend(Operation::change_schedules(&siblings, |schedule| Schedule { buried_until: bury_until, ..schedule.clone() }));
+        }
+        Answered { operations, after: Schedule { suspended, ..after }, leech }
+    }
+
+    /// Changes the schedule of each of `cards` with `change`, skipping cards it leaves as they are.
+    pub fn change_schedules(cards: &[Card], change: impl Fn(&Schedule) -> Schedule) -> Option<Operation> {
+        let changes: Vec<Operation> = cards
+            .iter()
+            .map(|card| (card, change(&card.schedule)))
+            .filter(|(card, after)| card.schedule != *after)
+            .map(|(card, after)| Operation::SetSchedule { note: card.note, ordinal: card.ordinal, before: card.schedule.clone(), after })
+            .collect();
+        if changes.is_empty() { None } else { Some(Operation::Batch(changes)) }
+    }
+
+    pub fn apply(&self, collection: &mut Collection) -> Result<()> {
+        match self {
+            Operation::AddNote { deck, position, note } => find_deck_mut(collection, *deck)?.insert_note(*position, note.clone()),
+            Operation::RemoveNote { deck, note, .. } => {
+                find_deck_mut(collection, *deck)?.remove_note(note.uuid).ok_or_else(|| eyre!("note {} not found", note.uuid))?;
+            }
+            Operation::EditNote { deck, after, .. } => {
+                if !find_deck_mut(collection, *deck)?.update_note((**after).clone()) {
+                    return Err(eyre!("note {} not found", after.uuid));
+                }
+            }
+            Operation::MoveNote { note, from, to, to_position, .. } => {
+                find_deck_mut(collection, *to)?;
+                let note = find_deck_mut(collection, *from)?.remove_note(*note).ok_or_else(|| eyre!("note {} not found", note))?;
+                find_deck_mut(collection, *to)?.insert_note(*to_position, note);
+            }
+            Operation::InsertDeck { parent, position, deck } => {
+                if !collection.insert_deck(*parent, *position, deck.clone()) {
+                    return Err(eyre!("deck {} not found", parent));
+                }
+            }
+            Operation::RemoveDeck { deck, .. } => {
+                collection.remove_deck(deck.uuid).ok_or_else(|| eyre!("deck {} not found", deck.uuid))?;
+            }
+            Operation::RenameDeck { deck, after, .. } => {
+                if !collection.rename_deck(*deck, after.clone()) {
+                    return Err(eyre!("deck {} not found", deck));
+                }
+            }
+            Operation::MoveDeck { deck, to, to_position, .. } => {
+                if collection.find_deck(*to).is_none() && *to != collection.uuid {
+                    return Err(eyre!("deck {} not found", to));
+                }
+                let removed = collection.remove_deck(*deck).ok_or_else(|| eyre!("deck {} not found", deck))?;
+                collection.insert_deck(*to, *to_position, removed);
+            }
+            Operation::DeleteDeck { deck } => {
+                if !collection.delete_deck(*deck) {
+                    return Err(eyre!("deck {} not found", deck));
+                }
+            }
+            Operation::RestoreDeck { deck } => {
+                if !collection.restore_deck(*deck) {
+                    return Err(eyre!("deck {} is not in the trash", deck));
+                }
+            }
+            Operation::Answer { after, review, .. } => {
+                if !collection.set_schedule(review.note, review.ordinal, after.clone()) {
+                    return Err(eyre!("note {} not found", review.note));
+                }
+                collection.log_review(review.clone());
+            }
+            Operation::UndoAnswer { before, review, .. } => {
+                if !collection.set_schedule(review.note, review.ordinal, before.clone()) {
+                    return Err(eyre!("note {} not found", review.note));
+                }
+                collection.remove_review(review);
+            }
+            Operation::SetSchedule { note, ordinal, after, .. } => {
+                if !collection.set_schedule(*note, *ordinal, after.clone()) {
+                    return Err(eyre!("note {} not found", note));
+                }
+            }
+            Operation::AddFilteredDeck { position, deck } => collection.insert_filtered_deck(*position, deck.clone()),
+            Operation::RemoveFilteredDeck { deck, .. } => {
+                collection.remove_filtered_deck(deck.uuid).ok_or_else(|| eyre!("filtered deck {} not found", deck.uuid))?;
+            }
+            Operation::EditFilteredDeck { after, .. } => {
+                if !collection.update_filtered_deck((**after).clone()) {
+                    return Err(eyre!("filtered deck {} not found", after.uuid));
+                }
+            }
+            Operation::Batch(operations) => {
+                for (applied, operation) in operations.iter().enumerate() {
+                    if let Err(err) = operation.apply(collection) {
+                        for operation in operations[..applied].iter().rev() {
+                            let _ = operation.inverse().apply(collection);
+                        }
+                        return Err(err);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn inverse(&self) -> Operation {
+        match self.clone() {
+            Operation::AddNote { deck, position, note } => Operation::RemoveNote { deck, position, note },
+            Operation::RemoveNote { deck, position, note } => Operation::AddNote { deck, position, note },
+            Operation::EditNote { deck, before, after } => Operation::EditNote { deck, before: after, after: before },
+            Operation::MoveNote { note, from, from_position, to, to_position } => {
+                Operation::MoveNote { note, from: to, from_position: to_position, to: from, to_position: from_position }
+            }
+            Operation::InsertDeck { parent, position, deck } => Operation::RemoveDeck { parent, position, deck },
+            Operation::RemoveDeck { parent, position, deck } => Operation::InsertDeck { parent, position, deck },
+            Operation::RenameDeck { deck, before, after } => Operation::RenameDeck { deck, before: after, after: before },
+            Operation::MoveDeck { deck, from, from_position, to, to_position } => {
+                Operation::MoveDeck { deck, from: to, from_position: to_position, to: from, to_position: from_position }
+            }
+            Operation::DeleteDeck { deck } => Operation::RestoreDeck { deck },
+            Operation::RestoreDeck { deck } => Operation::DeleteDeck { deck },
+            Operation::Answer { before, after, review } => Operation::UndoAnswer { before, after, review },
+            Operation::UndoAnswer { before, after, review } => Operation::Answer { before, after, review },
+            Operation::SetSchedule { note, ordinal, before, after } => Operation::SetSchedule { note, ordinal, before: after, after: before },
+            Operation::AddFilteredDeck { position, deck } => Operation::RemoveFilteredDeck { position, deck },
+            Operation::RemoveFilteredDeck { position, deck } => Operation::AddFilteredDeck { position, deck },
+            Operation::EditFilteredDeck { before, after } => Operation::EditFilteredDeck { before: after, after: before },
+            Operation::Batch(operations) => Operation::Batch(operations.iter().rev().map(Operation::inverse).collect()),
+        }
+    }
+}
+
+fn find_deck_mut(collection: &mut Collection, uuid: Uuid) -> Result<&mut Deck> {
+    collection.find_deck_mut(uuid).ok_or_else(|| eyre!("deck {} not found", uuid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::note::NoteType;
+    use crate::models::schedule::CardState;
+
+    fn note(front: &str) -> Note {
+        Note::new(front.to_string(), "back".to_string(), NoteType::Basic)
+    }
+
+    fn names(decks: &[Deck]) -> Vec<&str> {
+        decks.iter().map(|deck| deck.name.as_str()).collect()
+    }
+
+    /// Applies the built operation, then its inverse, and checks the collection ends up where it started.
+    fn assert_round_trip(collection: &mut Collection, build: impl FnOnce(&Collection) -> Option<Operation>) {
+        let operation = build(collection).unwrap();
+        let before = serde_json::to_string(&collection).unwrap();
+        operation.apply(collection).unwrap();
+        assert_ne!(serde_json::to_string(&collection).unwrap(), before);
+        operation.inverse().apply(collection).unwrap();
+        assert_eq!(serde_json::to_string(&collection).unwrap(), before);
+    }
+
+    fn sample() -> (Collection, Uuid, Uuid) {
+        let mut collection = Collection::new();
+        let mut spanish = Deck::new("Spanish".to_string());
+        spanish.add_note(note("uno"));
+        spanish.add_note(note("dos"));
+        spanish.add_note(note("tres"));
+        spanish.add_subdeck(Deck::new("Verbs".to_string()));
+        let french = Deck::new("French".to_string());
+        let (spanish_uuid, french_uuid) = (spanish.uuid, french.uuid);
+        collection.add_deck(spanish);
+        collection.add_deck(french);
+        (collection, spanish_uuid, french_uuid)
+    }
+
+    #[test]
+    fn test_note_operations_round_trip() {
+        let (mut collection, spanish, _) = sample();
+        let dos = collection.find_deck(spanish).unwrap().get_notes()[1].clone();
+
+        assert_round_trip(&mut collection, |c| Operation::add_note(c, spanish, note("cuatro")));
+        assert_round_trip(&mut collection, |c| Operation::remove_note(c, dos.uuid));
+        let edited = Note { front: "DOS".to_string(), ..dos.clone() };
+        assert_round_trip(&mut collection, |c| Operation::edit_note(c, edited));
+        assert!(Operation::edit_note(&collection, dos).is_none());
+    }
+
+    #[test]
+    fn test_move_notes_round_trip() {
+        let (mut collection, spanish, french) = sample();
+        let notes: Vec<Uuid> = collection.find_deck(spanish).unwrap().get_notes().iter().map(|n| n.uuid).collect();
+
+        let operation = Operation::move_notes(&collection, &[notes[2], notes[0]], french).unwrap();
+        operation.apply(&mut collection).unwrap();
+        let fronts: Vec<&str> = collection.find_deck(french).unwrap().get_notes().iter().map(|n| n.front.as_str()).collect();
+        assert_eq!(fronts, vec!["uno", "tres"]);
+
+        operation.inverse().apply(&mut collection).unwrap();
+        let fronts: Vec<&str> = collection.find_deck(spanish).unwrap().get_notes().iter().map(|n| n.front.as_str()).collect();
+        assert_eq!(fronts, vec!["uno", "dos", "tres"]);
+        assert!(Operation::move_notes(&collection, &notes, spanish).is_none());
+    }
+
+    #[test]
+    fn test_bulk_note_operations_round_trip() {
+        let (mut collection, spanish, _) = sample();
+        let notes: Vec<Uuid> = collection.find_deck(spanish).unwrap().get_notes().iter().map(|n| n.uuid).collect();
+
+        let operation = Operation::remove_notes(&collection, &[notes[0], notes[2], notes[0]]).unwrap();
+        operation.apply(&mut collection).unwrap();
+        let fronts: Vec<&str> = collection.find_deck(spanish).unwrap().get_notes().iter().map(|n| n.front.as_str()).collect();
+        assert_eq!(fronts, vec!["dos"]);
+        operation.inverse().apply(&mut collection).unwrap();
+
+        let retyped =
+            notes.iter().map(|uuid| Note { note_type: NoteType::BasicAndReverse, ..collection.find_note(*uuid).unwrap().clone() }).collect();
+        assert_round_trip(&mut collection, |c| Operation::edit_notes(c, retyped));
+        assert!(Operation::remove_notes(&collection, &[Uuid::new_v4()]).is_none());
+    }
+
+    #[test]
+    fn test_deck_operations_round_trip() {
+        let (mut collection, spanish, french) = sample();
+
+        assert_round_trip(&mut collection, |c| Operation::add_deck(c, spanish, Deck::new("Nouns".to_string())));
+        assert_round_trip(&mut collection, |c| Operation::rename_deck(c, spanish, "Español".to_string()));
+        assert_round_trip(&mut collection, |c| Operation::move_deck(c, spanish, french));
+        assert_round_trip(&mut collection, |c| Operation::move_deck(c, spanish, c.uuid));
+        assert!(Operation::move_deck(&collection, spanish, spanish).is_none());
+    }
+
+    #[test]
+    fn test_move_deck_keeps_qualified_names() {
+        let (mut collection, spanish, french) = sample();
+
+        Operation::move_deck(&collection, spanish, french).unwrap().apply(&mut collection).unwrap();
+
+        assert_eq!(names(collection.get_decks()), vec!["French"]);
+        let verbs = &collection.find_deck(spanish).unwrap().get_subdecks()[0];
+        assert_eq!(verbs.qualified_name(), "French:Spanish:Verbs");
+    }
+
+    #[test]
+    fn test_delete_deck_is_undone_from_trash() {
+        let (mut collection, spanish, _) = sample();
+        let operation = Operation::delete_deck(&collection, spanish).unwrap();
+
+        operation.apply(&mut collection).unwrap();
+        assert_eq!(names(collection.get_decks()), vec!["French"]);
+        assert_eq!(collection.get_trash().len(), 1);
+
+        operation.inverse().apply(&mut collection).unwrap();
+        assert_eq!(names(collection.get_decks()), vec!["Spanish", "French"]);
+        assert!(collection.get_trash().is_empty());
+    }
+
+    #[test]
+    fn test_answer_round_trip() {
+        let (mut collection, spanish, _) = sample();
+        let card = collection.find_deck(spanish).unwrap().get_cards()[0].clone();
+
+        let operation = Operation::answer(&card, Grade::Good, 1_700_000_000, 1500, &Scheduler::default());
+        operation.apply(&mut collection).unwrap();
+        let answered = collection.find_deck(spanish).unwrap().get_cards()[0].clone();
+        assert_eq!(answered.schedule.state, CardState::Learning);
+        assert_eq!(collection.get_review_log().len(), 1);
+        assert_eq!(collection.get_review_log()[0].duration_ms, 1500);
+
+        operation.inverse().apply(&mut collection).unwrap();
+        assert_eq!(collection.find_deck(spanish).unwrap().get_cards()[0], card);
+        assert!(collection.get_review_log().is_empty());
+    }
+
+    /// A collection with a reversible note whose first card lapses for the eighth time when forgotten.
+    fn lapsing() -> (Collection, Card) {
+        let (mut collection, spanish, _) = sample();
+        let mut note = Note::new("casa".to_string(), "house".to_string(), NoteType::BasicAndReverse);
+        note.set_schedule(0, Schedule { state: CardState::Review, interval: 10, due: 1_700_000_000, lapses: 7, ..Schedule::default() });
+        Operation::add_note(&collection, spanish, note.clone()).unwrap().apply(&mut collection).unwrap();
+        (collection, note.get_cards()[0].clone())
+    }
+
+    fn apply_all(collection: &mut Collection, operations: &[Operation]) {
+        Operation::Batch(operations.to_vec()).apply(collection).unwrap();
+    }
+
+    #[test]
+    fn test_answer_card_tags_leeches_and_suspends_only_when_enabled() {
+        let (mut collection, card) = lapsing();
+        let before = serde_json::to_string(&collection).unwrap();
+
+        let good = Operation::answer_card(&collection, &card, Grade::Good, 1_700_000_000, 0, &Scheduler::default(), 0);
+        assert!(!good.leech);
+        assert_eq!(good.operations.len(), 1);
+
+        let answered = Operation::answer_card(&collection, &card, Grade::Again, 1_700_000_000, 0, &Scheduler::default(), 0);
+        assert!(answered.leech);
+        assert!(!answered.after.suspended);
+        apply_all(&mut collection, &answered.operations);
+        assert!(collection.find_note(card.note).unwrap().tags.contains(&LEECH_TAG.to_string()));
+        assert!(!collection.find_note(card.note).unwrap().get_cards()[0].schedule.suspended);
+        Operation::Batch(answered.operations).inverse().apply(&mut collection).unwrap();
+        assert_eq!(serde_json::to_string(&collection).unwrap(), before);
+
+        let scheduler = Scheduler { suspend_leeches: true, ..Scheduler::default() };
+        let answered = Operation::answer_card(&collection, &card, Grade::Again, 1_700_000_000, 0, &scheduler, 0);
+        assert!(answered.after.suspended);
+        apply_all(&mut collection, &answered.operations);
+        let note = collection.find_note(card.note).unwrap();
+        assert!(note.tags.contains(&LEECH_TAG.to_string()));
+        assert!(note.get_cards()[0].schedule.suspended);
+        assert_eq!(note.get_cards()[0].schedule.lapses, 8);
+
+        // Undoing takes back the tag and the suspension along with the answer.
+        Operation::Batch(answered.operations).inverse().apply(&mut collection).unwrap();
+        assert_eq!(serde_json::to_string(&collection).unwrap(), before);
+    }
+
+    #[test]
+    fn test_answer_card_buries_siblings() {
+        let (mut collection, card) = lapsing();
+        let scheduler = Scheduler { bury_siblings: true, ..Scheduler::default() };
+
+        let answered = Operation::answer_card(&collection, &card, Grade::Good, 1_700_000_000, 0, &scheduler, 1_700_050_000);
+        apply_all(&mut collection, &answered.operations);
+        let cards = collection.find_note(card.note).unwrap().get_cards();
+        assert_eq!(cards[0].schedule.buried_until, 0);
+        assert_eq!(cards[1].schedule.buried_until, 1_700_050_000);
+        assert_eq!(Operation::answer_card(&collection, &card, Grade::Good, 1_700_000_000, 0, &Scheduler::default(), 0).operations.len(), 1);
+    }
+
+    #[test]
+    fn test_filtered_deck_operations_round_trip() {
+        let (mut collection, _, _) = sample();
+        let deck = FilteredDeck::new("Cram".to_string(), "deck:spanish".to_string());
+
+        assert_round_trip(&mut collection, |c| Some(Operation::add_filtered_deck(c, deck.clone())));
+        Operation::add_filtered_deck(&collection, deck.clone()).apply(&mut collection).unwrap();
+        let rebuilt = FilteredDeck { cards: deck.gather(&collection, 0).unwrap(), ..deck.clone() };
+        assert_eq!(rebuilt.cards.len(), 3);
+        assert_round_trip(&mut collection, |c| Operation::edit_filtered_deck(c, rebuilt));
+        assert!(Operation::edit_filtered_deck(&collection, deck.clone()).is_none());
+        assert_round_trip(&mut collection, |c| Operation::remove_filtered_deck(c, deck.uuid));
+    }
+
+    #[test]
+    fn test_schedule_changes_round_trip() {
+        let (mut collection, spanish, _) = sample();
+        let cards = |c: &Collection| -> Vec<Card> { c.find_deck(spanish).unwrap().get_notes().iter().flat_map(|note| note.get_cards()).collect() };
+        let before = cards(&collection);
+
+        let operation = Operation::change_schedules(&before, |schedule| Schedule { buried_until: 100, ..schedule.clone() }).unwrap();
+        operation.apply(&mut collection).unwrap();
+        assert!(cards(&collection).iter().all(|card| card.schedule.buried_until == 100));
+        operation.inverse().apply(&mut collection).unwrap();
+        // Cards that had never been scheduled now store their default schedule, which reads the same.
+        assert_eq!(cards(&collection), before);
+        assert!(Operation::change_schedules(&before, Schedule::clone).is_none());
+    }
+
+    #[test]
+    fn test_failed_batch_is_rolled_back() {
+        let (mut collection, spanish, french) = sample();
+        let notes: Vec<Uuid> = collection.find_deck(spanish).unwrap().get_notes().iter().map(|n| n.uuid).collect();
+        let Operation::Batch(mut operations) = Operation::move_notes(&collection, &notes, french).unwrap() else { panic!() };
+        operations.push(Operation::RenameDeck { deck: Uuid::new_v4(), before: String::new(), after: String::new() });
+
+        assert!(Operation::Batch(operations).apply(&mut collection).is_err());
+
+        assert_eq!(collection.find_deck(spanish).unwrap().get_notes().len(), 3);
+        assert!(collection.find_deck(french).unwrap().get_notes().is_empty());
+    }
+}
@@ -0,0 +1,19 @@
+use serde::Deserialize;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::models::schedule::{CardState, Grade};
+
+/// One entry of the review log, written every time a card is answered.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Review {
+    pub note: Uuid,
+    pub ordinal: usize,
+    pub time: i64,
+    pub grade: Grade,
+    pub state: CardState,
+    pub interval: u32,
+    pub last_interval: u32,
+    pub ease: u32,
+    pub duration_ms: u64,
+}
@@ -0,0 +1,374 @@
+use serde::Deserialize;
+use serde::Serialize;
+use strum::{Display, EnumString};
+
+use crate::time::{DAY, Days, MINUTE};
+
+pub const DEFAULT_EASE: u32 = 2500;
+pub const MINIMUM_EASE: u32 = 1300;
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy, Debug, Default, Display, EnumString)]
+pub enum CardState {
+    #[default]
+    #[strum(to_string = "new")]
+    New,
+    #[strum(to_string = "learning")]
+    Learning,
+    #[strum(to_string = "review")]
+    Review,
+    #[strum(to_string = "relearning")]
+    Relearning,
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy, Debug, Display, EnumString)]
+pub enum Grade {
+    #[strum(to_string = "again")]
+    Again,
+    #[strum(to_string = "hard")]
+    Hard,
+    #[strum(to_string = "good")]
+    Good,
+    #[strum(to_string = "easy")]
+    Easy,
+}
+
+/// A colored flag set on a card to find it again later, numbered 1 to 4 like Anki's.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy, Debug, Display, EnumString)]
+pub enum Flag {
+    #[strum(to_string = "red")]
+    Red,
+    #[strum(to_string = "orange")]
+    Orange,
+    #[strum(to_string = "green")]
+    Green,
+    #[strum(to_string = "blue")]
+    Blue,
+}
+
+impl Flag {
+    pub const ALL: [Flag; 4] = [Flag::Red, Flag::Orange, Flag::Green, Flag::Blue];
+
+    /// The flag after `flag` when cycling through them, ending with no flag.
+    pub fn cycle(flag: Option<Flag>) -> Option<Flag> {
+        match flag {
+            None => Some(Flag::Red),
+            Some(Flag::Red) => Some(Flag::Orange),
+            Some(Flag::Orange) => Some(Flag::Green),
+            Some(Flag::Green) => Some(Flag::Blue),
+            Some(Flag::Blue) => None,
+        }
+    }
+}
+
+/// Scheduling state of a single card. Intervals are in days, ease is in permille (2500 = 250%)
+/// and `due` is a unix timestamp in seconds.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Schedule {
+    pub state: CardState,
+    pub due: i64,
+    pub interval: u32,
+    pub ease: u32,
+    pub reps: u32,
+    pub lapses: u32,
+    pub step: usize,
+    /// Suspended cards are never practiced until they are unsuspended.
+    #[serde(default)]
+    pub suspended: bool,
+    /// Buried cards are not practiced before this time.
+    #[serde(default)]
+    pub buried_until: i64,
+    #[serde(default)]
+    pub flag: Option<Flag>,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule {
+            state: CardState::New,
+            due: 0,
+            interval: 0,
+            ease: DEFAULT_EASE,
+            reps: 0,
+            lapses: 0,
+            step: 0,
+            suspended: false,
+            buried_until: 0,
+            flag: None,
+        }
+    }
+}
+
+/// Tunable parameters of the scheduler. Steps are in seconds, intervals in days and ease in permille.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scheduler {
+    pub learning_steps: Vec<i64>,
+    pub relearning_step: i64,
+    pub graduating_interval: u32,
+    pub easy_interval: u32,
+    pub starting_ease: u32,
+    /// Whether answering a card buries the other cards of its note until the next day.
+    pub bury_siblings: bool,
+    /// How many lapses make a card a leech. It is flagged again every half as many lapses after that.
+    pub leech_threshold: u32,
+    /// Whether leeches are suspended as well as tagged.
+    pub suspend_leeches: bool,
+    /// How many new cards of a deck are introduced each day.
+    pub new_per_day: u32,
+    /// How many review cards of a deck are shown each day. Learning cards are not limited.
+    pub reviews_per_day: u32,
+    /// The local hour a new day starts at, for the daily limits, burying, streaks and stats.
+    pub rollover_hour: i64,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Scheduler {
+            learning_steps: vec![MINUTE, 10 * MINUTE],
+            relearning_step: 10 * MINUTE,
+            graduating_interval: 1,
+            easy_interval: 4,
+            starting_ease: DEFAULT_EASE,
+            bury_siblings: false,
+            leech_threshold: 8,
+            suspend_leeches: false,
+            new_per_day: 20,
+            reviews_per_day: 200,
+            rollover_hour: 4,
+        }
+    }
+}
+
+impl Scheduler {
+    /// Describes every parameter that is out of range.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.learning_steps.is_empty() || self.learning_steps.iter().any(|step| *step <= 0) {
+            errors.push("learning_steps must be a non-empty list of positive durations".to_string());
+        }
+        if self.relearning_step <= 0 {
+            errors.push("relearning_step must be positive".to_string());
+        }
+        if self.graduating_interval == 0 || self.easy_interval < self.graduating_interval {
+            errors.push("graduating_interval must be at least 1 day and easy_interval at least graduating_interval".to_string());
+        }
+        if self.starting_ease < MINIMUM_EASE {
+            errors.push(format!("starting_ease must be at least {MINIMUM_EASE}"));
+        }
+        if self.leech_threshold == 0 {
+            errors.push("leech_threshold must be at least 1 lapse".to_string());
+        }
+        if !(0..24).contains(&self.rollover_hour) {
+            errors.push("rollover_hour must be an hour from 0 to 23".to_string());
+        }
+        errors
+    }
+
+    /// Study days starting at `rollover_hour` in the local time zone.
+    pub fn days(&self) -> Days {
+        Days::local(self.rollover_hour)
+    }
+
+    /// Whether the card lapsed from `before` to `after` and has lapsed often enough to be a leech,
+    /// like Anki: at the threshold, then every half threshold more.
+    pub fn is_leech(&self, before: &Schedule, after: &Schedule) -> bool {
+        let every = (self.leech_threshold / 2).max(1);
+        after.lapses > before.lapses && after.lapses >= self.leech_threshold && (after.lapses - self.leech_threshold).is_multiple_of(every)
+    }
+}
+
+impl Schedule {
+    pub fn is_due(&self, now: i64) -> bool {
+        self.due <= now
+    }
+
+    /// Whether the card can be practiced on the study day `now` falls in. Review cards are due for
+    /// their whole due day, while learning steps wait for their exact time.
+    pub fn is_due_today(&self, days: &Days, now: i64) -> bool {
+        match self.state {
+            CardState::Review => days.day(self.due) <= days.day(now),
+            _ => self.is_due(now),
+        }
+    }
+
+    /// Whether the card is kept out of practice at `now`, by being suspended or buried.
+    pub fn is_hidden(&self, now: i64) -> bool {
+        self.suspended || self.buried_until > now
+    }
+
+    /// Returns the schedule after answering the card with `grade` at `now`, using the default scheduler.
+    pub fn answer(&self, grade: Grade, now: i64) -> Schedule {
+        self.answer_with(grade, now, &Scheduler::default())
+    }
+
+    /// Returns the schedule after answering the card with `grade` at `now`.
+    pub fn answer_with(&self, grade: Grade, now: i64, scheduler: &Scheduler) -> Schedule {
+        let mut next = Schedule { reps: self.reps + 1, ..self.clone() };
+        if self.state == CardState::New {
+            next.ease = scheduler.starting_ease;
+        }
+        let steps = &scheduler.learning_steps;
+        match (self.state, grade) {
+            (CardState::New | CardState::Learning, Grade::Again) => next.learn(steps, 0, now),
+            (CardState::New | CardState::Learning, Grade::Hard) => next.learn(steps, self.step, now),
+            (CardState::New | CardState::Learning, Grade::Good) if self.step + 1 < steps.len() => next.learn(steps, self.step + 1, now),
+            (CardState::New | CardState::Learning, Grade::Good) => next.graduate(scheduler.graduating_interval, now),
+            (CardState::New | CardState::Learning, Grade::Easy) => next.graduate(scheduler.easy_interval, now),
+            (CardState::Review, Grade::Again) => {
+                next.lapses += 1;
+                next.ease = self.ease.saturating_sub(200).max(MINIMUM_EASE);
+                next.interval = 1;
+                next.state = CardState::Relearning;
+                next.step = 0;
+                next.due = now + scheduler.relearning_step;
+            }
+            (CardState::Review, Grade::Hard) => {
+                next.ease = self.ease.saturating_sub(150).max(MINIMUM_EASE);
+                next.review(scale(self.interval, 1200), now);
+            }
+            (CardState::Review, Grade::Good) => next.review(scale(self.interval, self.ease), now),
+            (CardState::Review, Grade::Easy) => {
+                next.ease = self.ease + 150;
+                next.review(scale(scale(self.interval, self.ease), 1300), now);
+            }
+            (CardState::Relearning, Grade::Again) => next.due = now + scheduler.relearning_step,
+            (CardState::Relearning, Grade::Hard | Grade::Good) => next.review(self.interval, now),
+            (CardState::Relearning, Grade::Easy) => next.review(self.interval + 1, now),
+        }
+        next
+    }
+
+    fn learn(&mut self, steps: &[i64], step: usize, now: i64) {
+        // The steps may have been shortened since the card entered learning.
+        let step = step.min(steps.len() - 1);
+        self.state = CardState::Learning;
+        self.step = step;
+        self.due = now + steps[step];
+    }
+
+    fn graduate(&mut self, interval: u32, now: i64) {
+        self.step = 0;
+        self.review(interval, now);
+    }
+
+    fn review(&mut self, interval: u32, now: i64) {
+        let interval = if self.state == CardState::Review { interval.max(self.interval + 1) } else { interval.max(1) };
+        self.state = CardState::Review;
+        self.interval = interval;
+        self.due = now + interval as i64 * DAY;
+    }
+}
+
+fn scale(interval: u32, permille: u32) -> u32 {
+    (interval as u64 * permille as u64 / 1000) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    #[test]
+    fn test_new_card_steps_through_learning() {
+        let schedule = Schedule::default();
+        assert!(schedule.is_due(NOW));
+
+        let learning = schedule.answer(Grade::Good, NOW);
+        assert_eq!(learning.state, CardState::Learning);
+        assert_eq!(learning.due, NOW + 10 * MINUTE);
+
+        let graduated = learning.answer(Grade::Good, NOW);
+        assert_eq!(graduated.state, CardState::Review);
+        assert_eq!(graduated.interval, 1);
+        assert_eq!(graduated.due, NOW + DAY);
+        assert_eq!(graduated.reps, 2);
+    }
+
+    #[test]
+    fn test_easy_graduates_immediately() {
+        let schedule = Schedule::default().answer(Grade::Easy, NOW);
+
+        assert_eq!(schedule.state, CardState::Review);
+        assert_eq!(schedule.interval, 4);
+    }
+
+    #[test]
+    fn test_review_good_grows_interval_by_ease() {
+        let schedule = Schedule { state: CardState::Review, interval: 10, ..Schedule::default() };
+
+        let next = schedule.answer(Grade::Good, NOW);
+
+        assert_eq!(next.interval, 25);
+        assert_eq!(next.due, NOW + 25 * DAY);
+    }
+
+    #[test]
+    fn test_review_again_lapses_and_relearns() {
+        let schedule = Schedule { state: CardState::Review, interval: 10, ..Schedule::default() };
+
+        let lapsed = schedule.answer(Grade::Again, NOW);
+        assert_eq!(lapsed.state, CardState::Relearning);
+        assert_eq!(lapsed.lapses, 1);
+        assert_eq!(lapsed.ease, 2300);
+
+        let relearned = lapsed.answer(Grade::Good, NOW);
+        assert_eq!(relearned.state, CardState::Review);
+        assert_eq!(relearned.interval, 1);
+    }
+
+    #[test]
+    fn test_ease_never_drops_below_minimum() {
+        let schedule = Schedule { state: CardState::Review, interval: 3, ease: MINIMUM_EASE, ..Schedule::default() };
+
+        assert_eq!(schedule.answer(Grade::Again, NOW).ease, MINIMUM_EASE);
+        assert_eq!(schedule.answer(Grade::Hard, NOW).ease, MINIMUM_EASE);
+    }
+
+    #[test]
+    fn test_custom_scheduler() {
+        let scheduler = Scheduler { learning_steps: vec![5 * MINUTE], graduating_interval: 2, starting_ease: 2000, ..Scheduler::default() };
+
+        let learning = Schedule::default().answer_with(Grade::Again, NOW, &scheduler);
+        assert_eq!(learning.due, NOW + 5 * MINUTE);
+        assert_eq!(learning.ease, 2000);
+
+        let graduated = learning.answer_with(Grade::Good, NOW, &scheduler);
+        assert_eq!(graduated.state, CardState::Review);
+        assert_eq!(graduated.interval, 2);
+    }
+
+    #[test]
+    fn test_suspended_and_buried_cards_are_hidden() {
+        let schedule = Schedule::default();
+        assert!(!schedule.is_hidden(NOW));
+        assert!(Schedule { suspended: true, ..schedule.clone() }.is_hidden(NOW));
+
+        let buried = Schedule { buried_until: NOW + DAY, ..schedule };
+        assert!(buried.is_hidden(NOW) && !buried.is_hidden(NOW + DAY));
+        assert_eq!(Flag::cycle(None), Some(Flag::Red));
+        assert_eq!(Flag::cycle(Some(Flag::Blue)), None);
+    }
+
+    #[test]
+    fn test_leeches_are_detected_at_the_threshold_and_every_half_after() {
+        let scheduler = Scheduler { leech_threshold: 4, ..Scheduler::default() };
+        let leeches: Vec<u32> = (1..=10)
+            .filter(|lapses| {
+                let before = Schedule { state: CardState::Review, interval: 3, lapses: lapses - 1, ..Schedule::default() };
+                scheduler.is_leech(&before, &before.answer(Grade::Again, NOW))
+            })
+            .collect();
+
+        assert_eq!(leeches, vec![4, 6, 8, 10]);
+        let lapsed = Schedule { lapses: 4, ..Schedule::default() };
+        assert!(!scheduler.is_leech(&lapsed, &lapsed.answer(Grade::Good, NOW)));
+    }
+
+    #[test]
+    fn test_scheduler_validation() {
+        assert!(Scheduler::default().validate().is_empty());
+        let scheduler = Scheduler { learning_steps: Vec::new(), starting_ease: 1000, ..Scheduler::default() };
+        assert_eq!(scheduler.validate().len(), 2);
+    }
+}
@@ -0,0 +1,496 @@
+use crate::models::card::Card;
+use crate::models::collection::Collection;
+use crate::models::deck::Deck;
+use crate::models::note::Note;
+use crate::models::review::Review;
+use crate::models::schedule::{CardState, Flag, Grade};
+use crate::time::DAY;
+
+/// A parsed search, in a subset of Anki's search syntax: terms separated by spaces must all match,
+/// `or` between terms matches either, `-` negates a term and parentheses group terms.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    /// Text in either side of the note, ignoring case.
+    Text(String),
+    /// Notes in the deck with this qualified name or one of its subdecks, ignoring case.
+    Deck(String),
+    /// Notes with this tag or one of its descendants, ignoring case.
+    Tag(String),
+    Is(CardFilter),
+    /// Cards with this flag, or with none.
+    Flag(Option<Flag>),
+    Prop(Property, Comparison, f64),
+    /// Notes added in the last this many days.
+    Added(i64),
+    /// Cards answered in the last this many days, with this grade when there is one.
+    Rated(i64, Option<Grade>),
+    Not(Box<Query>),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+}
+
+/// `is:` searches, by the state of a card.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardFilter {
+    Due,
+    New,
+    Learn,
+    Review,
+    Suspended,
+    Buried,
+}
+
+/// `prop:` searches, by a number in a card's schedule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Property {
+    /// Interval in days.
+    Interval,
+    /// Ease as a factor, like 2.5.
+    Ease,
+    /// Days until the card is due, negative when overdue.
+    Due,
+    Reps,
+    Lapses,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+/// A note found by [`search`], with the deck it is in.
+pub struct SearchResult<'a> {
+    pub deck: &'a Deck,
+    pub note: &'a Note,
+}
+
+/// The notes in `collection` with at least one card matching `query` at time `now`, deck by deck.
+pub fn search<'a>(collection: &'a Collection, query: &Query, now: i64) -> Vec<SearchResult<'a>> {
+    let mut results = Vec::new();
+    for deck in collection.get_all_decks() {
+        let name = deck.qualified_name().to_lowercase();
+        for note in deck.get_notes() {
+            if note.get_cards().iter().any(|card| query.matches(&name, note, card, now, collection.get_review_log())) {
+                results.push(SearchResult { deck, note });
+            }
+        }
+    }
+    results
+}
+
+/// The cards in `collection` matching `query` at time `now`, deck by deck.
+pub fn search_cards(collection: &Collection, query: &Query, now: i64) -> Vec<Card> {
+    let mut cards = Vec::new();
+    for deck in collection.get_all_decks() {
+        let name = deck.qualified_name().to_lowercase();
+        for note in deck.get_notes() {
+            cards.extend(note.get_cards().into_iter().filter(|card| query.matches(&name, note, card, now, collection.get_review_log())));
+        }
+    }
+    cards
+}
+
+impl Query {
+    /// Parses `input`, where an empty search matches everything.
+    pub fn parse(input: &str) -> Result<Query, String> {
+        let mut parser = Parser { tokens: tokenize(input)?, position: 0 };
+        if parser.tokens.is_empty() {
+            return Ok(Query::And(Vec::new()));
+        }
+        let query = parser.or()?;
+        match parser.tokens.get(parser.position) {
+            Some(_) => Err("unmatched )".to_string()),
+            None => Ok(query),
+        }
+    }
+
+    /// Whether `card` of `note`, in the deck with the lowercase qualified name `deck`, matches given
+    /// the collection's review log.
+    pub fn matches(&self, deck: &str, note: &Note, card: &Card, now: i64, reviews: &[Review]) -> bool {
+        let schedule = &card.schedule;
+        match self {
+            Query::Text(text) => note.front.to_lowercase().contains(text) || note.back.to_lowercase().contains(text),
+            Query::Deck(name) => deck == name || deck.strip_prefix(name.as_str()).is_some_and(|rest| rest.starts_with(':')),
+            Query::Tag(tag) => note.has_tag(tag),
+            Query::Is(CardFilter::Due) => schedule.state != CardState::New && schedule.is_due(now),
+            Query::Is(CardFilter::New) => schedule.state == CardState::New,
+            Query::Is(CardFilter::Learn) => matches!(schedule.state, CardState::Learning | CardState::Relearning),
+            Query::Is(CardFilter::Review) => schedule.state == CardState::Review,
+            Query::Is(CardFilter::Suspended) => schedule.suspended,
+            Query::Is(CardFilter::Buried) => schedule.buried_until > now,
+            Query::Flag(flag) => schedule.flag == *flag,
+            // New cards have no meaningful due date.
+            Query::Prop(Property::Due, _, _) if schedule.state == CardState::New => false,
+            Query::Prop(property, comparison, value) => {
+                let actual = match property {
+                    Property::Interval => schedule.interval as f64,
+                    Property::Ease => schedule.ease as f64 / 1000.0,
+                    Property::Due => (schedule.due - now).div_euclid(DAY) as f64,
+                    Property::Reps => schedule.reps as f64,
+                    Property::Lapses => schedule.lapses as f64,
+                };
+                comparison.compare(actual, *value)
+            }
+            Query::Added(days) => note.created > now - days * DAY,
+            Query::Rated(days, grade) => reviews.iter().any(|review| {
+                review.note == card.note
+                    && review.ordinal == card.ordinal
+                    && review.time > now - days * DAY
+                    && grade.is_none_or(|grade| review.grade == grade)
+            }),
+            Query::Not(query) => !query.matches(deck, note, card, now, reviews),
+            Query::And(queries) => queries.iter().all(|query| query.matches(deck, note, card, now, reviews)),
+            Query::Or(queries) => queries.iter().any(|query| query.matches(deck, note, card, now, reviews)),
+        }
+    }
+}
+
+impl Comparison {
+    fn compare(self, actual: f64, value: f64) -> bool {
+        match self {
+            Comparison::Less => actual < value,
+            Comparison::LessOrEqual => actual <= value,
+            Comparison::Greater => actual > value,
+            Comparison::GreaterOrEqual => actual >= value,
+            Comparison::Equal => actual == value,
+            Comparison::NotEqual => actual != value,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Not,
+    Or,
+    Word(String),
+}
+
+/// Splits `input` into words, parentheses, `-` and `or`. Quotes keep spaces and keywords in a word.
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | '-' => {
+                chars.next();
+                tokens.push(match c {
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    _ => Token::Not,
+                });
+            }
+            _ => {
+                let (mut word, mut quoted, mut in_quotes) = (String::new(), false, false);
+                while let Some(&c) = chars.peek() {
+                    if !in_quotes && (c.is_whitespace() || c == '(' || c == ')') {
+                        break;
+                    }
+                    chars.next();
+                    if c == '"' {
+                        in_quotes = !in_quotes;
+                        quoted = true;
+                    } else {
+                        word.push(c);
+                    }
+                }
+                if in_quotes {
+                    return Err("missing closing \"".to_string());
+                }
+                tokens.push(match word.to_lowercase().as_str() {
+                    "or" if !quoted => Token::Or,
+                    "and" if !quoted => continue,
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn or(&mut self) -> Result<Query, String> {
+        let mut queries = vec![self.and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.position += 1;
+            queries.push(self.and()?);
+        }
+        Ok(if queries.len() == 1 { queries.remove(0) } else { Query::Or(queries) })
+    }
+
+    fn and(&mut self) -> Result<Query, String> {
+        let mut queries = Vec::new();
+        while !matches!(self.peek(), None | Some(Token::Or | Token::Close)) {
+            queries.push(self.unary()?);
+        }
+        match queries.len() {
+            0 => Err("expected a search term".to_string()),
+            1 => Ok(queries.remove(0)),
+            _ => Ok(Query::And(queries)),
+        }
+    }
+
+    fn unary(&mut self) -> Result<Query, String> {
+        let token = self.tokens.get(self.position).ok_or("expected a search term")?;
+        self.position += 1;
+        match token {
+            Token::Not => match self.peek() {
+                None | Some(Token::Or | Token::Close) => Err("nothing to negate after -".to_string()),
+                _ => Ok(Query::Not(Box::new(self.unary()?))),
+            },
+            Token::Open => {
+                let query = self.or()?;
+                match self.peek() {
+                    Some(Token::Close) => {
+                        self.position += 1;
+                        Ok(query)
+                    }
+                    _ => Err("missing )".to_string()),
+                }
+            }
+            Token::Word(word) => term(word),
+            Token::Close | Token::Or => Err("expected a search term".to_string()),
+        }
+    }
+}
+
+fn term(word: &str) -> Result<Query, String> {
+    let Some((key, value)) = word.split_once(':') else {
+        return Ok(Query::Text(word.to_lowercase()));
+    };
+    match key.to_lowercase().as_str() {
+        "deck" if !value.is_empty() => Ok(Query::Deck(value.to_lowercase())),
+        "tag" if !value.is_empty() => Ok(Query::Tag(value.to_string())),
+        "is" => match value.to_lowercase().as_str() {
+            "due" => Ok(Query::Is(CardFilter::Due)),
+            "new" => Ok(Query::Is(CardFilter::New)),
+            "learn" => Ok(Query::Is(CardFilter::Learn)),
+            "review" => Ok(Query::Is(CardFilter::Review)),
+            "suspended" => Ok(Query::Is(CardFilter::Suspended)),
+            "buried" => Ok(Query::Is(CardFilter::Buried)),
+            _ => Err(format!("unknown is:{value}, expected one of: due, new, learn, review, suspended, buried")),
+        },
+        "flag" => match value.parse::<usize>() {
+            Ok(0) => Ok(Query::Flag(None)),
+            Ok(number) if number <= Flag::ALL.len() => Ok(Query::Flag(Some(Flag::ALL[number - 1]))),
+            _ => Err(format!("flag:{value} must be a number from 0 for no flag to 4")),
+        },
+        "prop" => property(value),
+        "added" => match value.parse() {
+            Ok(days) if days > 0 => Ok(Query::Added(days)),
+            _ => Err(format!("added:{value} must be a number of days")),
+        },
+        "rated" => rated(value),
+        _ => Ok(Query::Text(word.to_lowercase())),
+    }
+}
+
+/// Parses the part after `rated:`, a number of days optionally followed by the grade from 1 for
+/// again to 4 for easy, like `7:1`.
+fn rated(value: &str) -> Result<Query, String> {
+    let invalid = || format!("rated:{value} must look like rated:7 or rated:7:1");
+    let (days, grade) = match value.split_once(':') {
+        Some((days, grade)) => (days, Some(grade)),
+        None => (value, None),
+    };
+    let days = days.parse().ok().filter(|days| *days > 0).ok_or_else(invalid)?;
+    let grade = match grade {
+        None => None,
+        Some("1") => Some(Grade::Again),
+        Some("2") => Some(Grade::Hard),
+        Some("3") => Some(Grade::Good),
+        Some("4") => Some(Grade::Easy),
+        Some(_) => return Err(invalid()),
+    };
+    Ok(Query::Rated(days, grade))
+}
+
+/// Parses the part after `prop:`, like `ivl>=30`.
+fn property(value: &str) -> Result<Query, String> {
+    let invalid = || format!("prop:{value} must look like prop:ivl>30");
+    let start = value.find(['<', '>', '=', '!']).ok_or_else(invalid)?;
+    let (name, rest) = value.split_at(start);
+    let property = match name.to_lowercase().as_str() {
+        "ivl" => Property::Interval,
+        "ease" => Property::Ease,
+        "due" => Property::Due,
+        "reps" => Property::Reps,
+        "lapses" => Property::Lapses,
+        _ => return Err(format!("unknown prop:{name}, expected one of: ivl, ease, due, reps, lapses")),
+    };
+    let (comparison, number) = [
+        ("<=", Comparison::LessOrEqual),
+        (">=", Comparison::GreaterOrEqual),
+        ("!=", Comparison::NotEqual),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+        ("=", Comparison::Equal),
+    ]
+    .into_iter()
+    .find_map(|(operator, comparison)| rest.strip_prefix(operator).map(|number| (comparison, number)))
+    .ok_or_else(invalid)?;
+    Ok(Query::Prop(property, comparison, number.parse().map_err(|_| invalid())?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::note::NoteType;
+    use crate::models::schedule::Schedule;
+
+    const NOW: i64 = 1_000 * DAY;
+
+    fn note(front: &str, back: &str, schedule: Schedule) -> Note {
+        let mut note = Note::new(front.to_string(), back.to_string(), NoteType::Basic);
+        note.created = NOW - 3 * DAY;
+        note.set_schedule(0, schedule);
+        note
+    }
+
+    fn review(interval: u32, due: i64) -> Schedule {
+        Schedule { state: CardState::Review, interval, due, reps: 4, ..Schedule::default() }
+    }
+
+    fn matches(query: &str, deck: &str, note: &Note) -> bool {
+        Query::parse(query).unwrap().matches(deck, note, &note.get_cards()[0], NOW, &[])
+    }
+
+    #[test]
+    fn test_parses_boolean_structure() {
+        assert_eq!(Query::parse("").unwrap(), Query::And(Vec::new()));
+        assert_eq!(
+            Query::parse("a -b or (c \"d or e\")").unwrap(),
+            Query::Or(vec![
+                Query::And(vec![Query::Text("a".to_string()), Query::Not(Box::new(Query::Text("b".to_string())))]),
+                Query::And(vec![Query::Text("c".to_string()), Query::Text("d or e".to_string())]),
+            ])
+        );
+        assert_eq!(
+            Query::parse("deck:\"Spanish Verbs\" and is:due").unwrap(),
+            Query::And(vec![Query::Deck("spanish verbs".to_string()), Query::Is(CardFilter::Due)])
+        );
+        assert_eq!(Query::parse("prop:ivl>=30").unwrap(), Query::Prop(Property::Interval, Comparison::GreaterOrEqual, 30.0));
+    }
+
+    #[test]
+    fn test_rejects_invalid_searches() {
+        for query in ["(a", "a)", "a or", "-", "\"a", "is:nope", "prop:ivl", "prop:size>1", "prop:ivl>x", "added:-1"] {
+            assert!(Query::parse(query).is_err(), "{query}");
+        }
+    }
+
+    #[test]
+    fn test_matches_text_and_decks() {
+        let note = note("Hola", "Hello", Schedule::default());
+
+        assert!(matches("hello", "spanish:greetings", &note));
+        assert!(matches("deck:Spanish", "spanish:greetings", &note));
+        assert!(matches("deck:spanish:greetings", "spanish:greetings", &note));
+        assert!(!matches("deck:span", "spanish:greetings", &note));
+        assert!(!matches("-hola", "spanish", &note));
+        assert!(matches("bonjour or hola", "spanish", &note));
+    }
+
+    #[test]
+    fn test_matches_tags_with_their_children() {
+        let note = Note { tags: vec!["Grammar::Verbs".to_string()], ..note("a", "b", Schedule::default()) };
+
+        assert!(matches("tag:grammar", "d", &note));
+        assert!(matches("tag:grammar::verbs", "d", &note));
+        assert!(!matches("tag:gram", "d", &note));
+        assert!(!matches("-tag:grammar", "d", &note));
+    }
+
+    #[test]
+    fn test_matches_cards_rated_recently() {
+        let note = note("a", "b", review(10, NOW + DAY));
+        let answer = |grade, time| Review {
+            note: note.uuid,
+            ordinal: 0,
+            time,
+            grade,
+            state: CardState::Review,
+            interval: 10,
+            last_interval: 5,
+            ease: 2500,
+            duration_ms: 0,
+        };
+        let reviews = [answer(Grade::Good, NOW - 10 * DAY), answer(Grade::Again, NOW - 2 * DAY)];
+        let rated = |query: &str| Query::parse(query).unwrap().matches("d", &note, &note.get_cards()[0], NOW, &reviews);
+
+        assert!(rated("rated:7") && rated("rated:7:1"));
+        assert!(!rated("rated:7:3") && rated("rated:14:3"));
+        assert!(!rated("rated:1"));
+        for invalid in ["rated:0", "rated:x", "rated:7:5"] {
+            assert!(Query::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_matches_states_and_properties() {
+        let new = note("a", "b", Schedule::default());
+        let due = note("a", "b", review(40, NOW - DAY));
+        let later = note("a", "b", review(10, NOW + 2 * DAY));
+
+        assert!(matches("is:new", "d", &new) && !matches("is:due", "d", &new));
+        assert!(matches("is:due", "d", &due) && matches("is:review", "d", &due));
+        assert!(!matches("is:due", "d", &later));
+        assert!(matches("prop:ivl>30", "d", &due) && !matches("prop:ivl>30", "d", &later));
+        assert!(matches("prop:ease=2.5", "d", &due));
+        assert!(matches("prop:due=2", "d", &later) && matches("prop:due<0", "d", &due));
+        assert!(!matches("prop:due<5", "d", &new));
+        assert!(matches("added:7", "d", &new) && !matches("added:2", "d", &new));
+    }
+
+    #[test]
+    fn test_matches_suspended_buried_and_flagged_cards() {
+        let suspended = note("a", "b", Schedule { suspended: true, flag: Some(Flag::Green), ..Schedule::default() });
+        let buried = note("a", "b", Schedule { buried_until: NOW + DAY, ..Schedule::default() });
+
+        assert!(matches("is:suspended", "d", &suspended) && !matches("is:buried", "d", &suspended));
+        assert!(matches("is:buried", "d", &buried) && !matches("is:suspended", "d", &buried));
+        assert!(matches("flag:3", "d", &suspended) && !matches("flag:1", "d", &suspended));
+        assert!(matches("flag:0", "d", &buried) && !matches("flag:0", "d", &suspended));
+        assert!(Query::parse("flag:5").is_err());
+    }
+
+    #[test]
+    fn test_search_finds_notes_with_any_matching_card() {
+        let mut collection = Collection::new();
+        let mut deck = Deck::new("Spanish".to_string());
+        let mut note = Note::new("uno".to_string(), "one".to_string(), NoteType::BasicAndReverse);
+        note.set_schedule(1, review(5, NOW - DAY));
+        deck.add_note(note);
+        deck.add_note(Note::new("dos".to_string(), "two".to_string(), NoteType::Basic));
+        let mut subdeck = Deck::new("Verbs".to_string());
+        subdeck.add_note(Note::new("ser".to_string(), "to be".to_string(), NoteType::Basic));
+        deck.add_subdeck(subdeck);
+        collection.add_deck(deck);
+
+        let fronts = |query: &str| -> Vec<String> {
+            search(&collection, &Query::parse(query).unwrap(), NOW).iter().map(|result| result.note.front.clone()).collect()
+        };
+
+        assert_eq!(fronts("is:due"), vec!["uno"]);
+        assert_eq!(fronts("deck:spanish:verbs"), vec!["ser"]);
+        assert_eq!(fronts("deck:spanish").len(), 3);
+    }
+}
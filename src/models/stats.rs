@@ -0,0 +1,213 @@
+use std::collections::HashSet;
+
+use uuid::Uuid;
+
+use crate::models::card::Card;
+use crate::models::collection::Collection;
+use crate::models::review::Review;
+use crate::models::schedule::{CardState, Grade};
+use crate::time::Days;
+
+/// Cards with an interval of at least this many days are mature, like in Anki.
+pub const MATURE_INTERVAL: u32 = 21;
+
+/// Upper bounds in days of the interval distribution's buckets, each with its label.
+const INTERVAL_BUCKETS: [(u32, &str); 9] =
+    [(1, "1d"), (3, "2-3d"), (7, "4-7d"), (14, "1-2w"), (30, "2-4w"), (90, "1-3m"), (180, "3-6m"), (365, "6m-1y"), (u32::MAX, ">1y")];
+
+/// Lower bounds in permille of the ease distribution's buckets, each with its label.
+const EASE_BUCKETS: [(u32, &str); 8] =
+    [(0, "<150%"), (1500, "150%"), (1700, "170%"), (1900, "190%"), (2100, "210%"), (2300, "230%"), (2500, "250%"), (2700, "270%+")];
+
+/// How many reviews of cards of some maturity were recalled rather than forgotten.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Retention {
+    pub passed: u64,
+    pub total: u64,
+}
+
+impl Retention {
+    /// The share of reviews that were recalled, in percent, or `None` without reviews.
+    pub fn percent(&self) -> Option<f64> {
+        (self.total > 0).then(|| self.passed as f64 * 100.0 / self.total as f64)
+    }
+}
+
+/// The review log and cards of a deck and its subdecks, or of the whole collection, summarized for
+/// the stats screen. Study days are counted back from the one `now` falls in.
+pub struct Stats<'a> {
+    reviews: Vec<&'a Review>,
+    cards: Vec<Card>,
+    days: Days,
+    now: i64,
+}
+
+impl<'a> Stats<'a> {
+    pub fn new(collection: &'a Collection, deck: Option<Uuid>, days: Days, now: i64) -> Self {
+        let notes: Vec<_> = match deck.and_then(|deck| collection.find_deck(deck)) {
+            Some(deck) => deck.get_all_notes(),
+            None => collection.get_decks().iter().flat_map(|deck| deck.get_all_notes()).collect(),
+        };
+        let uuids: HashSet<Uuid> = notes.iter().map(|note| note.uuid).collect();
+        Stats {
+            reviews: collection.get_review_log().iter().filter(|review| uuids.contains(&review.note)).collect(),
+            cards: notes.iter().flat_map(|note| note.get_cards()).collect(),
+            days,
+            now,
+        }
+    }
+
+    /// The reviews in the last `days` days.
+    fn recent(&self, days: usize) -> impl Iterator<Item = &&'a Review> {
+        let today = self.days.day(self.now);
+        self.reviews.iter().filter(move |review| (0..days as i64).contains(&(today - self.days.day(review.time))))
+    }
+
+    /// Sums `value` of the reviews of each of the last `days` days, oldest first and today last.
+    fn per_day(&self, days: usize, value: impl Fn(&Review) -> u64) -> Vec<u64> {
+        let today = self.days.day(self.now);
+        let mut totals = vec![0; days];
+        for review in self.recent(days) {
+            totals[days - 1 - (today - self.days.day(review.time)) as usize] += value(review);
+        }
+        totals
+    }
+
+    pub fn reviews_per_day(&self, days: usize) -> Vec<u64> {
+        self.per_day(days, |_| 1)
+    }
+
+    /// Seconds spent answering on each of the last `days` days, oldest first.
+    pub fn seconds_per_day(&self, days: usize) -> Vec<u64> {
+        self.per_day(days, |review| review.duration_ms / 1000)
+    }
+
+    /// Of the reviews in the last `days` days of cards out of learning, how many were recalled,
+    /// for young cards and then for mature ones.
+    pub fn retention(&self, days: usize) -> (Retention, Retention) {
+        let (mut young, mut mature) = (Retention::default(), Retention::default());
+        for review in self.recent(days).filter(|review| review.state == CardState::Review) {
+            let retention = if review.last_interval >= MATURE_INTERVAL { &mut mature } else { &mut young };
+            retention.total += 1;
+            retention.passed += u64::from(review.grade != Grade::Again);
+        }
+        (young, mature)
+    }
+
+    /// Reviews in the last `days` days by the local hour of the day they were done at.
+    pub fn reviews_per_hour(&self, days: usize) -> [u64; 24] {
+        let mut hours = [0; 24];
+        for review in self.recent(days) {
+            hours[self.days.hour(review.time)] += 1;
+        }
+        hours
+    }
+
+    /// How many cards out of learning have intervals in each bucket.
+    pub fn interval_distribution(&self) -> Vec<(&'static str, u64)> {
+        let mut counts = INTERVAL_BUCKETS.map(|(_, label)| (label, 0));
+        for card in self.scheduled() {
+            let bucket = INTERVAL_BUCKETS.iter().position(|(max, _)| card.schedule.interval <= *max).unwrap_or_default();
+            counts[bucket].1 += 1;
+        }
+        counts.to_vec()
+    }
+
+    /// How many cards that were studied have eases in each bucket.
+    pub fn ease_distribution(&self) -> Vec<(&'static str, u64)> {
+        let mut counts = EASE_BUCKETS.map(|(_, label)| (label, 0));
+        for card in self.cards.iter().filter(|card| card.schedule.state != CardState::New) {
+            let bucket = EASE_BUCKETS.iter().rposition(|(min, _)| card.schedule.ease >= *min).unwrap_or_default();
+            counts[bucket].1 += 1;
+        }
+        counts.to_vec()
+    }
+
+    fn scheduled(&self) -> impl Iterator<Item = &Card> {
+        self.cards.iter().filter(|card| matches!(card.schedule.state, CardState::Review | CardState::Relearning))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::deck::Deck;
+    use crate::models::note::{Note, NoteType};
+    use crate::models::schedule::Schedule;
+    use crate::time::{DAY, HOUR};
+
+    const UTC: Days = Days::new(0, 0);
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn review(note: Uuid, time: i64, grade: Grade, last_interval: u32) -> Review {
+        Review { note, ordinal: 0, time, grade, state: CardState::Review, interval: last_interval * 2, last_interval, ease: 2500, duration_ms: 8000 }
+    }
+
+    /// A collection with a Spanish deck whose note was reviewed and a French one whose note was not.
+    fn collection() -> (Collection, Uuid) {
+        let mut collection = Collection::new();
+        let mut spanish = Deck::new("Spanish".to_string());
+        let mut note = Note::new("uno".to_string(), "one".to_string(), NoteType::Basic);
+        note.set_schedule(0, Schedule { state: CardState::Review, interval: 40, ease: 2300, ..Schedule::default() });
+        let uno = note.uuid;
+        spanish.add_note(note);
+        let mut french = Deck::new("French".to_string());
+        let mut note = Note::new("un".to_string(), "one".to_string(), NoteType::Basic);
+        note.set_schedule(0, Schedule { state: CardState::Review, interval: 2, ..Schedule::default() });
+        let un = note.uuid;
+        french.add_note(note);
+        let spanish_uuid = spanish.uuid;
+        collection.add_deck(spanish);
+        collection.add_deck(french);
+        for review in [
+            review(uno, NOW, Grade::Good, 30),
+            review(uno, NOW - DAY, Grade::Again, 30),
+            review(uno, NOW - DAY, Grade::Good, 3),
+            review(uno, NOW - 10 * DAY, Grade::Good, 3),
+            review(un, NOW, Grade::Hard, 1),
+        ] {
+            collection.log_review(review);
+        }
+        (collection, spanish_uuid)
+    }
+
+    #[test]
+    fn test_counts_reviews_per_day_of_the_deck() {
+        let (collection, spanish) = collection();
+
+        let stats = Stats::new(&collection, Some(spanish), UTC, NOW);
+        assert_eq!(stats.reviews_per_day(3), vec![0, 2, 1]);
+        assert_eq!(stats.seconds_per_day(2), vec![16, 8]);
+        assert_eq!(Stats::new(&collection, None, UTC, NOW).reviews_per_day(1), vec![2]);
+    }
+
+    #[test]
+    fn test_retention_by_maturity() {
+        let (collection, spanish) = collection();
+
+        let (young, mature) = Stats::new(&collection, Some(spanish), UTC, NOW).retention(7);
+
+        assert_eq!(young, Retention { passed: 1, total: 1 });
+        assert_eq!(mature, Retention { passed: 1, total: 2 });
+        assert_eq!(mature.percent(), Some(50.0));
+        assert_eq!(Retention::default().percent(), None);
+    }
+
+    #[test]
+    fn test_distributions_and_hours() {
+        let (collection, _) = collection();
+        let stats = Stats::new(&collection, None, UTC, NOW);
+
+        let intervals = stats.interval_distribution();
+        assert_eq!(intervals[1], ("2-3d", 1));
+        assert_eq!(intervals[5], ("1-3m", 1));
+        assert_eq!(intervals.iter().map(|(_, count)| count).sum::<u64>(), 2);
+        let eases = stats.ease_distribution();
+        assert_eq!((eases[5], eases[6]), (("230%", 1), ("250%", 1)));
+        let hour = (NOW.rem_euclid(DAY) / HOUR) as usize;
+        assert_eq!(stats.reviews_per_hour(1)[hour], 2);
+        let east = Stats::new(&collection, None, Days::new(0, 3 * HOUR), NOW);
+        assert_eq!(east.reviews_per_hour(1)[(hour + 3) % 24], 2);
+    }
+}
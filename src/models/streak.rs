@@ -0,0 +1,141 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Deserialize;
+use serde::Serialize;
+use strum::Display;
+
+use crate::models::review::Review;
+use crate::time::{Days, MINUTE};
+
+/// What the daily goal is counted in.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Copy, Debug, Default, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum GoalUnit {
+    #[default]
+    #[strum(to_string = "reviews")]
+    Reviews,
+    #[strum(to_string = "minutes")]
+    Minutes,
+}
+
+/// The daily study goal.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Goal {
+    pub unit: GoalUnit,
+    pub daily: u64,
+}
+
+impl Default for Goal {
+    fn default() -> Self {
+        Goal { unit: GoalUnit::Reviews, daily: 50 }
+    }
+}
+
+/// How much was studied on the current day and how many days in a row reached the goal.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Reviews or whole minutes studied on the current day.
+    pub done: u64,
+    /// The days in a row up to today that reached the goal, or up to yesterday while today has not yet.
+    pub streak: u32,
+    pub longest_streak: u32,
+}
+
+impl Goal {
+    /// Describes every parameter that is out of range.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.daily == 0 {
+            errors.push("daily must be at least 1".to_string());
+        }
+        errors
+    }
+
+    /// Today's progress and the streaks, from the review log, with days counted by `days`.
+    pub fn progress(&self, reviews: &[Review], days: &Days, now: i64) -> Progress {
+        let mut per_day: BTreeMap<i64, u64> = BTreeMap::new();
+        for review in reviews {
+            *per_day.entry(days.day(review.time)).or_default() += match self.unit {
+                GoalUnit::Reviews => 1,
+                GoalUnit::Minutes => review.duration_ms,
+            };
+        }
+        let in_unit = |amount: u64| match self.unit {
+            GoalUnit::Reviews => amount,
+            GoalUnit::Minutes => amount / (MINUTE as u64 * 1000),
+        };
+        let reached: BTreeSet<i64> = per_day.iter().filter(|(_, amount)| in_unit(**amount) >= self.daily).map(|(day, _)| *day).collect();
+
+        let today = days.day(now);
+        let start = if reached.contains(&today) { today } else { today - 1 };
+        let streak = (0..).take_while(|back| reached.contains(&(start - back))).count() as u32;
+        let mut longest_streak = 0;
+        let mut run = 0;
+        let mut previous = None;
+        for day in &reached {
+            run = if previous == Some(day - 1) { run + 1 } else { 1 };
+            longest_streak = longest_streak.max(run);
+            previous = Some(*day);
+        }
+        Progress { done: in_unit(per_day.get(&today).copied().unwrap_or_default()), streak, longest_streak }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::models::schedule::{CardState, Grade};
+    use crate::time::{DAY, HOUR};
+
+    /// Midnight UTC, a Tuesday.
+    const MIDNIGHT: i64 = 1_699_920_000;
+
+    fn review(time: i64, duration_ms: u64) -> Review {
+        Review {
+            note: Uuid::new_v4(),
+            ordinal: 0,
+            time,
+            grade: Grade::Good,
+            state: CardState::Review,
+            interval: 1,
+            last_interval: 1,
+            ease: 2500,
+            duration_ms,
+        }
+    }
+
+    #[test]
+    fn test_streaks_count_days_that_reached_the_goal() {
+        let goal = Goal { daily: 2, ..Goal::default() };
+        let days = Days::new(0, 0);
+        let noon = MIDNIGHT + 12 * HOUR;
+        let mut reviews = Vec::new();
+        // Three days in a row ending five days ago, then a day short of the goal, then the last two.
+        for day in [7, 6, 5, 2, 1] {
+            reviews.extend([review(noon - day * DAY, 0), review(noon - day * DAY + MINUTE, 0)]);
+        }
+        reviews.push(review(noon - 3 * DAY, 0));
+        reviews.push(review(noon, 0));
+
+        let progress = goal.progress(&reviews, &days, noon);
+        assert_eq!(progress, Progress { done: 1, streak: 2, longest_streak: 3 });
+
+        reviews.push(review(noon + MINUTE, 0));
+        assert_eq!(goal.progress(&reviews, &days, noon + HOUR), Progress { done: 2, streak: 3, longest_streak: 3 });
+        assert_eq!(goal.progress(&reviews, &days, noon + 2 * DAY).streak, 0);
+    }
+
+    #[test]
+    fn test_minutes_goal_sums_durations() {
+        let goal = Goal { unit: GoalUnit::Minutes, daily: 1 };
+        let reviews = [review(MIDNIGHT + HOUR, 40_000), review(MIDNIGHT + 2 * HOUR, 30_000), review(MIDNIGHT - HOUR, 50_000)];
+
+        assert_eq!(goal.progress(&reviews, &Days::new(0, 0), MIDNIGHT + 3 * HOUR), Progress { done: 1, streak: 1, longest_streak: 1 });
+        // Two hours ahead of UTC the review before midnight UTC falls on the same day as the others.
+        assert_eq!(goal.progress(&reviews, &Days::new(0, 2 * HOUR), MIDNIGHT + 3 * HOUR).done, 2);
+        assert_eq!(Goal { daily: 0, ..Goal::default() }.validate().len(), 1);
+    }
+}
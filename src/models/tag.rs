@@ -0,0 +1,116 @@
+use std::collections::BTreeSet;
+
+/// Separates the levels of a hierarchical tag, like `grammar::verbs`.
+pub const TAG_SEPARATOR: &str = "::";
+
+/// The tag of notes marked during practice to look at them again later, like Anki's.
+pub const MARKED_TAG: &str = "marked";
+
+/// The tag of notes with a card that keeps being forgotten, like Anki's.
+pub const LEECH_TAG: &str = "leech";
+
+/// The tags in `text`, separated by whitespace, without duplicates.
+pub fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in text.split_whitespace() {
+        if !tags.iter().any(|existing| existing.eq_ignore_ascii_case(tag)) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+/// `tags` with `tag` removed if it is there, ignoring case, or added at the end if it is not.
+pub fn toggle_tag(tags: &[String], tag: &str) -> Vec<String> {
+    match tags.iter().any(|existing| existing.eq_ignore_ascii_case(tag)) {
+        true => tags.iter().filter(|existing| !existing.eq_ignore_ascii_case(tag)).cloned().collect(),
+        false => tags.iter().cloned().chain([tag.to_string()]).collect(),
+    }
+}
+
+/// Whether `tag` is `filter` or one of its descendants, ignoring case.
+pub fn tag_matches(tag: &str, filter: &str) -> bool {
+    let (tag, filter) = (tag.to_lowercase(), filter.to_lowercase());
+    tag == filter || tag.strip_prefix(&filter).is_some_and(|rest| rest.starts_with(TAG_SEPARATOR))
+}
+
+/// A row of the tag tree: the full tag, how deeply it is nested and the last level of its name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagNode {
+    pub tag: String,
+    pub depth: usize,
+    pub name: String,
+}
+
+/// `tags` and all their ancestors as a tree, parents first and siblings in alphabetical order.
+pub fn tag_tree<'a>(tags: impl IntoIterator<Item = &'a String>) -> Vec<TagNode> {
+    let mut paths: BTreeSet<Vec<&str>> = BTreeSet::new();
+    for tag in tags {
+        let levels: Vec<&str> = tag.split(TAG_SEPARATOR).collect();
+        for depth in 1..=levels.len() {
+            paths.insert(levels[..depth].to_vec());
+        }
+    }
+    paths
+        .into_iter()
+        .map(|levels| TagNode { tag: levels.join(TAG_SEPARATOR), depth: levels.len() - 1, name: levels[levels.len() - 1].to_string() })
+        .collect()
+}
+
+/// Existing tags that start with `prefix`, ignoring case, for completing a tag being typed.
+pub fn completions<'a>(prefix: &str, tags: &'a BTreeSet<String>) -> Vec<&'a String> {
+    let prefix = prefix.to_lowercase();
+    tags.iter().filter(|tag| tag.to_lowercase().starts_with(&prefix) && tag.len() > prefix.len()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tags_skips_duplicates() {
+        assert_eq!(parse_tags("  verb grammar::verbs Verb\n"), vec!["verb", "grammar::verbs"]);
+        assert!(parse_tags(" ").is_empty());
+    }
+
+    #[test]
+    fn test_toggle_tag_ignores_case() {
+        let tags = vec!["exam".to_string(), "Marked".to_string()];
+        assert_eq!(toggle_tag(&tags, MARKED_TAG), vec!["exam"]);
+        assert_eq!(toggle_tag(&tags[..1], MARKED_TAG), vec!["exam", "marked"]);
+    }
+
+    #[test]
+    fn test_tags_match_their_ancestors() {
+        assert!(tag_matches("grammar::verbs", "grammar"));
+        assert!(tag_matches("Grammar::Verbs", "grammar::verbs"));
+        assert!(!tag_matches("grammarian", "grammar"));
+        assert!(!tag_matches("grammar", "grammar::verbs"));
+    }
+
+    #[test]
+    fn test_tag_tree_includes_ancestors_in_order() {
+        let tags = ["grammar::verbs::irregular".to_string(), "grammar-extra".to_string(), "exam".to_string()];
+
+        let tree: Vec<(String, usize)> = tag_tree(&tags).into_iter().map(|node| (node.tag, node.depth)).collect();
+
+        assert_eq!(
+            tree,
+            vec![
+                ("exam".to_string(), 0),
+                ("grammar".to_string(), 0),
+                ("grammar::verbs".to_string(), 1),
+                ("grammar::verbs::irregular".to_string(), 2),
+                ("grammar-extra".to_string(), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_completions_extend_the_prefix() {
+        let tags: BTreeSet<String> = ["exam".to_string(), "grammar::verbs".to_string(), "Grammar::nouns".to_string()].into();
+
+        assert_eq!(completions("gram", &tags), vec!["Grammar::nouns", "grammar::verbs"]);
+        assert!(completions("exam", &tags).is_empty());
+    }
+}
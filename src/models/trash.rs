@@ -0,0 +1,27 @@
+use serde::Deserialize;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{models::deck::Deck, time};
+
+/// How many deleted decks are kept around before the oldest ones are purged for good.
+pub const TRASH_CAPACITY: usize = 50;
+
+/// A deleted deck together with where it used to live, so it can be put back in place.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Debug)]
+pub struct DeletedDeck {
+    pub deck: Deck,
+    pub parent: Uuid,
+    pub position: usize,
+    pub deleted_at: i64,
+}
+
+impl DeletedDeck {
+    pub fn new(deck: Deck, parent: Uuid, position: usize) -> Self {
+        DeletedDeck { deck, parent, position, deleted_at: time::now() }
+    }
+
+    pub fn seconds_ago(&self) -> i64 {
+        (time::now() - self.deleted_at).max(0)
+    }
+}
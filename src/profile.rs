@@ -0,0 +1,180 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{Result, bail};
+
+/// The profile used when none was ever picked. It lives directly in the data directory, where the
+/// collection was kept before profiles existed.
+pub const DEFAULT_PROFILE: &str = "default";
+
+const COLLECTION_FILE: &str = "collection.sqlite";
+const LEGACY_COLLECTION_FILE: &str = "collection.json";
+const LAST_USED_FILE: &str = "last_profile";
+
+/// A named collection with its own backups and config.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Profile {
+    pub name: String,
+    dir: PathBuf,
+    collection: PathBuf,
+    /// Where a collection from before SQLite is looked for, which only profiles kept in the data
+    /// directory have.
+    legacy_collection: Option<PathBuf>,
+}
+
+impl Profile {
+    fn in_dir(name: &str, dir: PathBuf) -> Self {
+        Profile { name: name.to_string(), collection: dir.join(COLLECTION_FILE), legacy_collection: Some(dir.join(LEGACY_COLLECTION_FILE)), dir }
+    }
+
+    /// A profile for a collection at an arbitrary path, keeping everything else next to it. A
+    /// `collection.json` there is not taken for legacy data, since it may well be something else.
+    pub fn for_collection(path: &Path) -> Self {
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        Profile { name, dir, collection: path.to_path_buf(), legacy_collection: None }
+    }
+
+    pub fn collection_path(&self) -> &Path {
+        &self.collection
+    }
+
+    /// Where the collection was kept before it moved to SQLite; migrated on first start.
+    pub fn legacy_collection_path(&self) -> Option<&Path> {
+        self.legacy_collection.as_deref()
+    }
+
+    pub fn backup_dir(&self) -> PathBuf {
+        self.dir.join("backups")
+    }
+
+    pub fn config_path(&self) -> PathBuf {
+        self.dir.join("config.json5")
+    }
+}
+
+/// The profiles kept in the data directory.
+#[derive(Clone, Debug)]
+pub struct Profiles {
+    root: PathBuf,
+}
+
+impl Profiles {
+    pub fn new(root: PathBuf) -> Self {
+        Profiles { root }
+    }
+
+    pub fn get(&self, name: &str) -> Profile {
+        if name == DEFAULT_PROFILE { Profile::in_dir(name, self.root.clone()) } else { Profile::in_dir(name, self.root.join("profiles").join(name)) }
+    }
+
+    /// All profiles, the default one first and the others by name.
+    pub fn list(&self) -> Result<Vec<Profile>> {
+        let mut names = Vec::new();
+        let dir = self.root.join("profiles");
+        if dir.exists() {
+            for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() {
+                    names.push(entry.file_name().to_string_lossy().into_owned());
+                }
+            }
+        }
+        names.sort();
+        Ok([DEFAULT_PROFILE.to_string()].into_iter().chain(names).map(|name| self.get(&name)).collect())
+    }
+
+    pub fn create(&self, name: &str) -> Result<Profile> {
+        validate_name(name)?;
+        let profile = self.get(name);
+        if profile.dir.exists() && name != DEFAULT_PROFILE {
+            bail!("profile {name:?} already exists");
+        }
+        fs::create_dir_all(&profile.dir)?;
+        Ok(profile)
+    }
+
+    /// The named profile, created first if it does not exist yet.
+    pub fn get_or_create(&self, name: &str) -> Result<Profile> {
+        validate_name(name)?;
+        let profile = self.get(name);
+        fs::create_dir_all(&profile.dir)?;
+        Ok(profile)
+    }
+
+    pub fn last_used(&self) -> Option<String> {
+        let name = fs::read_to_string(self.root.join(LAST_USED_FILE)).ok()?;
+        let name = name.trim();
+        (validate_name(name).is_ok() && self.get(name).dir.exists()).then(|| name.to_string())
+    }
+
+    pub fn set_last_used(&self, name: &str) -> Result<()> {
+        fs::create_dir_all(&self.root)?;
+        fs::write(self.root.join(LAST_USED_FILE), name)?;
+        Ok(())
+    }
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+        bail!("invalid profile name {name:?}: use letters, digits, '-' and '_'");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_profile_lives_in_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let profiles = Profiles::new(dir.path().to_path_buf());
+
+        let default = profiles.get(DEFAULT_PROFILE);
+
+        assert_eq!(default.collection_path(), dir.path().join(COLLECTION_FILE));
+        assert_eq!(default.legacy_collection_path(), Some(dir.path().join(LEGACY_COLLECTION_FILE).as_path()));
+        assert_eq!(profiles.list().unwrap(), vec![default]);
+    }
+
+    #[test]
+    fn test_create_and_list_profiles() {
+        let dir = tempfile::tempdir().unwrap();
+        let profiles = Profiles::new(dir.path().to_path_buf());
+
+        let work = profiles.create("work").unwrap();
+        profiles.create("alex").unwrap();
+
+        let names: Vec<String> = profiles.list().unwrap().into_iter().map(|profile| profile.name).collect();
+        assert_eq!(names, vec!["default", "alex", "work"]);
+        assert!(work.collection_path().starts_with(dir.path().join("profiles").join("work")));
+        assert!(profiles.create("work").is_err());
+        assert!(profiles.create("../escape").is_err());
+        assert!(profiles.create("").is_err());
+        assert_eq!(profiles.get_or_create("work").unwrap(), work);
+    }
+
+    #[test]
+    fn test_last_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let profiles = Profiles::new(dir.path().to_path_buf());
+        assert_eq!(profiles.last_used(), None);
+
+        profiles.create("work").unwrap();
+        profiles.set_last_used("work").unwrap();
+        assert_eq!(profiles.last_used(), Some("work".to_string()));
+
+        profiles.set_last_used("removed").unwrap();
+        assert_eq!(profiles.last_used(), None);
+    }
+
+    #[test]
+    fn test_profile_for_collection_path() {
+        let profile = Profile::for_collection(Path::new("/tmp/tanki-test/spanish.sqlite"));
+
+        assert_eq!(profile.name, "spanish");
+        assert_eq!(profile.backup_dir(), Path::new("/tmp/tanki-test/backups"));
+        assert_eq!(profile.legacy_collection_path(), None);
+    }
+}
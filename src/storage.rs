@@ -0,0 +1,939 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use color_eyre::eyre::{Result, bail, eyre};
+use rusqlite::{Connection, OptionalExtension, Transaction, params};
+use uuid::Uuid;
+
+use crate::lock::Lock;
+use crate::models::{
+    collection::Collection,
+    deck::Deck,
+    filtered_deck::FilteredDeck,
+    note::{Note, NoteType},
+    operation::Operation,
+    review::Review,
+    schedule::{CardState, Grade, Schedule},
+    tag::parse_tags,
+    trash::DeletedDeck,
+};
+
+/// Schema changes, in order; the database's `user_version` is the number of migrations applied.
+const MIGRATIONS: [&str; 5] = [SCHEMA_V1, SCHEMA_V2, SCHEMA_V3, SCHEMA_V4, SCHEMA_V5];
+
+const SCHEMA_V1: &str = "
+    CREATE TABLE IF NOT EXISTS config (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS decks (uuid TEXT PRIMARY KEY, parent TEXT, position INTEGER NOT NULL, name TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS notes (
+        uuid TEXT PRIMARY KEY,
+        deck TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        front TEXT NOT NULL,
+        back TEXT NOT NULL,
+        note_type TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS cards (
+        note TEXT NOT NULL,
+        ordinal INTEGER NOT NULL,
+        state TEXT NOT NULL,
+        due INTEGER NOT NULL,
+        interval INTEGER NOT NULL,
+        ease INTEGER NOT NULL,
+        reps INTEGER NOT NULL,
+        lapses INTEGER NOT NULL,
+        step INTEGER NOT NULL,
+        PRIMARY KEY (note, ordinal)
+    );
+    CREATE TABLE IF NOT EXISTS revlog (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        note TEXT NOT NULL,
+        ordinal INTEGER NOT NULL,
+        time INTEGER NOT NULL,
+        grade TEXT NOT NULL,
+        state TEXT NOT NULL,
+        interval INTEGER NOT NULL,
+        last_interval INTEGER NOT NULL,
+        ease INTEGER NOT NULL,
+        duration_ms INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS trash (deck TEXT PRIMARY KEY, parent TEXT NOT NULL, position INTEGER NOT NULL, deleted_at INTEGER NOT NULL, data TEXT NOT NULL);
+    CREATE INDEX IF NOT EXISTS notes_by_deck ON notes (deck, position);
+    CREATE INDEX IF NOT EXISTS decks_by_parent ON decks (parent, position);
+";
+
+/// Records when each note was added; notes from before are left at 0.
+const SCHEMA_V2: &str = "ALTER TABLE notes ADD COLUMN created INTEGER NOT NULL DEFAULT 0;";
+
+/// Stores the tags of each note, separated by spaces.
+const SCHEMA_V3: &str = "ALTER TABLE notes ADD COLUMN tags TEXT NOT NULL DEFAULT '';";
+
+/// Filtered decks are small and only ever replaced as a whole, so they are stored as JSON.
+const SCHEMA_V4: &str = "CREATE TABLE IF NOT EXISTS filtered_decks (uuid TEXT PRIMARY KEY, position INTEGER NOT NULL, data TEXT NOT NULL);";
+
+/// Stores whether each card is suspended, until when it is buried and its flag.
+const SCHEMA_V5: &str = "
+    ALTER TABLE cards ADD COLUMN suspended INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE cards ADD COLUMN buried_until INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE cards ADD COLUMN flag TEXT;
+";
+
+/// SQLite-backed storage for a collection. The whole collection is read once on load, after which
+/// every committed [`Operation`] is written incrementally with [`Storage::persist`].
+pub struct Storage {
+    conn: Connection,
+    path: PathBuf,
+    _lock: Option<Lock>,
+    /// SQLite's `data_version` as of the last load; it changes when another connection commits.
+    data_version: Cell<i64>,
+}
+
+impl Storage {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL; PRAGMA foreign_keys = OFF;")?;
+        let mut storage = Self { conn, path: path.to_path_buf(), _lock: None, data_version: Cell::new(0) };
+        storage.migrate()?;
+        Ok(storage)
+    }
+
+    /// Loads the collection from a copy of the database at `path`, e.g. a backup, so that upgrading
+    /// an older schema leaves the original untouched.
+    pub fn load_copy(path: &Path) -> Result<Collection> {
+        let dir = tempfile::tempdir()?;
+        let copy = dir.path().join("collection.sqlite");
+        fs::copy(path, &copy)?;
+        Storage::open(&copy)?.load()
+    }
+
+    pub fn version(&self) -> Result<usize> {
+        Ok(self.conn.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+    }
+
+    /// Applies pending [`MIGRATIONS`] one at a time, each in its own transaction.
+    fn migrate(&mut self) -> Result<()> {
+        let version = self.version()?;
+        if version > MIGRATIONS.len() {
+            bail!("{} has schema version {version}, but this version of tanki only supports up to {}", self.path.display(), MIGRATIONS.len());
+        }
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let tx = self.conn.transaction()?;
+            tx.execute_batch(migration)?;
+            tx.execute_batch(&format!("PRAGMA user_version = {}", i + 1))?;
+            tx.commit()?;
+        }
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes a compacted copy of the database to `path`, which must not exist yet.
+    pub fn copy_to(&self, path: &Path) -> Result<()> {
+        self.conn.execute("VACUUM INTO ?1", params![path.to_string_lossy()])?;
+        Ok(())
+    }
+
+    /// Reads the whole collection, creating and recording a fresh collection uuid for a new database.
+    pub fn load(&self) -> Result<Collection> {
+        self.data_version.set(self.current_data_version()?);
+        let uuid = match self.get_config("collection_uuid")? {
+            Some(uuid) => Uuid::parse_str(&uuid)?,
+            None => {
+                let uuid = Uuid::new_v4();
+                self.conn.execute("INSERT INTO config (key, value) VALUES ('collection_uuid', ?1)", params![uuid.to_string()])?;
+                uuid
+            }
+        };
+
+        let mut schedules: HashMap<Uuid, Vec<Schedule>> = HashMap::new();
+        let mut stmt = self.conn.prepare(
+            "SELECT note, ordinal, state, due, interval, ease, reps, lapses, step, suspended, buried_until, flag FROM cards ORDER BY note, ordinal",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, usize>(1)?,
+                row.get::<_, String>(2)?,
+                Schedule {
+                    state: CardState::New,
+                    due: row.get(3)?,
+                    interval: row.get(4)?,
+                    ease: row.get(5)?,
+                    reps: row.get(6)?,
+                    lapses: row.get(7)?,
+                    step: row.get(8)?,
+                    suspended: row.get(9)?,
+                    buried_until: row.get(10)?,
+                    flag: None,
+                },
+                row.get::<_, Option<String>>(11)?,
+            ))
+        })?;
+        for row in rows {
+            let (note, ordinal, state, schedule, flag) = row?;
+            let note_schedules = schedules.entry(Uuid::parse_str(&note)?).or_default();
+            if note_schedules.len() <= ordinal {
+                note_schedules.resize(ordinal + 1, Schedule::default());
+            }
+            let flag = flag.map(|flag| parse_enum(&flag)).transpose()?;
+            note_schedules[ordinal] = Schedule { state: parse_enum(&state)?, flag, ..schedule };
+        }
+
+        let mut notes: HashMap<Uuid, Vec<Note>> = HashMap::new();
+        let mut stmt = self.conn.prepare("SELECT uuid, deck, front, back, note_type, created, tags FROM notes ORDER BY deck, position")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, i64>(5)?,
+                row.get::<_, String>(6)?,
+            ))
+        })?;
+        for row in rows {
+            let (uuid, deck, front, back, note_type, created, tags) = row?;
+            let uuid = Uuid::parse_str(&uuid)?;
+            let note = Note {
+                uuid,
+                front,
+                back,
+                note_type: parse_enum::<NoteType>(&note_type)?,
+                schedules: schedules.remove(&uuid).unwrap_or_default(),
+                created,
+                tags: parse_tags(&tags),
+            };
+            notes.entry(Uuid::parse_str(&deck)?).or_default().push(note);
+        }
+
+        let mut children: HashMap<Option<Uuid>, Vec<(Uuid, String)>> = HashMap::new();
+        let mut stmt = self.conn.prepare("SELECT uuid, parent, name FROM decks ORDER BY parent, position")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?, row.get::<_, String>(2)?)))?;
+        for row in rows {
+            let (uuid, parent, name) = row?;
+            let parent = parent.map(|parent| Uuid::parse_str(&parent)).transpose()?;
+            children.entry(parent).or_default().push((Uuid::parse_str(&uuid)?, name));
+        }
+        let decks = build_decks(None, &mut children, &mut notes);
+
+        let mut stmt = self.conn.prepare("SELECT deck, parent, position, deleted_at, data FROM trash ORDER BY deleted_at, rowid")?;
+        let rows =
+            stmt.query_map([], |row| Ok((row.get::<_, String>(1)?, row.get::<_, usize>(2)?, row.get::<_, i64>(3)?, row.get::<_, String>(4)?)))?;
+        let mut trash = Vec::new();
+        for row in rows {
+            let (parent, position, deleted_at, data) = row?;
+            trash.push(DeletedDeck { deck: serde_json::from_str(&data)?, parent: Uuid::parse_str(&parent)?, position, deleted_at });
+        }
+
+        let mut stmt = self.conn.prepare("SELECT data FROM filtered_decks ORDER BY position")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        let mut filtered_decks = Vec::new();
+        for row in rows {
+            filtered_decks.push(serde_json::from_str(&row?)?);
+        }
+
+        let review_log = self.load_reviews()?;
+        Ok(Collection::from_parts(uuid, decks, trash, review_log, filtered_decks))
+    }
+
+    /// Whether another process wrote to the database since it was last loaded.
+    pub fn changed_externally(&self) -> Result<bool> {
+        Ok(self.current_data_version()? != self.data_version.get())
+    }
+
+    /// Accepts the current contents on disk as seen, e.g. after overwriting them with [`Storage::save_all`].
+    pub fn acknowledge_external_changes(&self) -> Result<()> {
+        self.data_version.set(self.current_data_version()?);
+        Ok(())
+    }
+
+    fn current_data_version(&self) -> Result<i64> {
+        Ok(self.conn.query_row("PRAGMA data_version", [], |row| row.get(0))?)
+    }
+
+    fn load_reviews(&self) -> Result<Vec<Review>> {
+        let mut stmt =
+            self.conn.prepare("SELECT note, ordinal, time, grade, state, interval, last_interval, ease, duration_ms FROM revlog ORDER BY id")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, usize>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, u32>(5)?,
+                row.get::<_, u32>(6)?,
+                row.get::<_, u32>(7)?,
+                row.get::<_, u64>(8)?,
+            ))
+        })?;
+        let mut reviews = Vec::new();
+        for row in rows {
+            let (note, ordinal, time, grade, state, interval, last_interval, ease, duration_ms) = row?;
+            reviews.push(Review {
+                note: Uuid::parse_str(&note)?,
+                ordinal,
+                time,
+                grade: parse_enum::<Grade>(&grade)?,
+                state: parse_enum(&state)?,
+                interval,
+                last_interval,
+                ease,
+                duration_ms,
+            });
+        }
+        Ok(reviews)
+    }
+
+    /// Replaces everything stored with `collection`, in a single transaction.
+    pub fn save_all(&mut self, collection: &Collection) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute_batch(
+            "DELETE FROM decks; DELETE FROM notes; DELETE FROM cards; DELETE FROM revlog; DELETE FROM trash; DELETE FROM filtered_decks;",
+        )?;
+        tx.execute("INSERT OR REPLACE INTO config (key, value) VALUES ('collection_uuid', ?1)", params![collection.uuid.to_string()])?;
+        for (position, deck) in collection.get_decks().iter().enumerate() {
+            insert_deck_tree(&tx, deck, None, position)?;
+        }
+        for deleted in collection.get_trash() {
+            insert_trash(&tx, deleted)?;
+        }
+        for review in collection.get_review_log() {
+            insert_review(&tx, review)?;
+        }
+        for (position, deck) in collection.get_filtered_decks().iter().enumerate() {
+            insert_filtered_deck(&tx, position, deck)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Writes the rows touched by `operation`, which must already have been applied to `collection`.
+    pub fn persist(&mut self, collection: &Collection, operation: &Operation) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        persist_operation(&tx, collection, operation)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_config(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.conn.query_row("SELECT value FROM config WHERE key = ?1", params![key], |row| row.get(0)).optional()?)
+    }
+}
+
+/// Opens the collection stored at `path`, holding its [`Lock`] for as long as the storage lives. When no database exists yet but a legacy
+/// `collection.json` is given and exists, it is imported once and renamed so it is not picked up again.
+pub fn open_collection(path: &Path, legacy_json: Option<&Path>, ignore_lock: bool) -> Result<(Storage, Collection)> {
+    // The legacy file is parsed before the database is created, so that a file that fails to parse
+    // is tried again next time instead of being hidden behind an empty database.
+    let legacy_json = legacy_json.filter(|legacy_json| !path.exists() && legacy_json.exists());
+    let legacy = match legacy_json {
+        Some(legacy_json) => Some((legacy_json, Collection::load_from_file(legacy_json.to_path_buf())?)),
+        None => None,
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let lock = if ignore_lock { Lock::force(path)? } else { Lock::acquire(path)? };
+    let mut storage = Storage::open(path)?;
+    storage._lock = Some(lock);
+    if let Some((legacy_json, collection)) = legacy {
+        if let Err(err) = storage.save_all(&collection) {
+            drop(storage);
+            fs::remove_file(path)?;
+            return Err(err);
+        }
+        fs::rename(legacy_json, legacy_json.with_extension("json.migrated"))?;
+        // Nothing was loaded, so the database as just written is what later changes are compared to.
+        storage.acknowledge_external_changes()?;
+        return Ok((storage, collection));
+    }
+    let collection = storage.load()?;
+    Ok((storage, collection))
+}
+
+fn build_decks(parent: Option<Uuid>, children: &mut HashMap<Option<Uuid>, Vec<(Uuid, String)>>, notes: &mut HashMap<Uuid, Vec<Note>>) -> Vec<Deck> {
+    children
+        .remove(&parent)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(uuid, name)| {
+            let mut deck = Deck::new(name);
+            deck.uuid = uuid;
+            notes.remove(&uuid).unwrap_or_default().into_iter().for_each(|note| deck.add_note(note));
+            build_decks(Some(uuid), children, notes).into_iter().for_each(|subdeck| deck.add_subdeck(subdeck));
+            deck
+        })
+        .collect()
+}
+
+fn parse_enum<T: FromStr>(value: &str) -> Result<T> {
+    value.parse().map_err(|_| eyre!("invalid value {value:?} in collection database"))
+}
+
+fn parent_column(collection: &Collection, parent: Uuid) -> Option<String> {
+    if parent == collection.uuid { None } else { Some(parent.to_string()) }
+}
+
+fn persist_operation(tx: &Transaction, collection: &Collection, operation: &Operation) -> Result<()> {
+    match operation {
+        Operation::AddNote { deck, note, .. } => {
+            let position =
+                collection.find_deck(*deck).and_then(|d| d.note_position(note.uuid)).ok_or_else(|| eyre!("note {} not found", note.uuid))?;
+            shift_notes(tx, *deck, position, 1)?;
+            insert_note(tx, *deck, position, note)?;
+        }
+        Operation::RemoveNote { deck, position, note } => {
+            delete_note(tx, note.uuid)?;
+            shift_notes(tx, *deck, position + 1, -1)?;
+        }
+        Operation::EditNote { after, .. } => {
+            tx.execute(
+                "UPDATE notes SET front = ?2, back = ?3, note_type = ?4, tags = ?5 WHERE uuid = ?1",
+                params![after.uuid.to_string(), after.front, after.back, after.note_type.to_string(), after.tags.join(" ")],
+            )?;
+            tx.execute("DELETE FROM cards WHERE note = ?1", params![after.uuid.to_string()])?;
+            insert_cards(tx, after)?;
+        }
+        Operation::MoveNote { note, from, from_position, to, .. } => {
+            let to_position = collection.find_deck(*to).and_then(|d| d.note_position(*note)).ok_or_else(|| eyre!("note {} not found", note))?;
+            shift_notes(tx, *from, from_position + 1, -1)?;
+            shift_notes(tx, *to, to_position, 1)?;
+            tx.execute("UPDATE notes SET deck = ?2, position = ?3 WHERE uuid = ?1", params![note.to_string(), to.to_string(), to_position])?;
+        }
+        Operation::InsertDeck { deck, .. } => {
+            let (parent, position) = collection.deck_position(deck.uuid).ok_or_else(|| eyre!("deck {} not found", deck.uuid))?;
+            let parent = parent_column(collection, parent);
+            shift_decks(tx, &parent, position, 1)?;
+            insert_deck_tree(tx, collection.find_deck(deck.uuid).unwrap(), parent, position)?;
+        }
+        Operation::RemoveDeck { parent, position, deck } => {
+            delete_deck_tree(tx, deck)?;
+            shift_decks(tx, &parent_column(collection, *parent), position + 1, -1)?;
+        }
+        Operation::RenameDeck { deck, after, .. } => {
+            tx.execute("UPDATE decks SET name = ?2 WHERE uuid = ?1", params![deck.to_string(), after])?;
+        }
+        Operation::MoveDeck { deck, from, from_position, .. } => {
+            let (to, to_position) = collection.deck_position(*deck).ok_or_else(|| eyre!("deck {} not found", deck))?;
+            let to = parent_column(collection, to);
+            shift_decks(tx, &parent_column(collection, *from), from_position + 1, -1)?;
+            shift_decks(tx, &to, to_position, 1)?;
+            tx.execute("UPDATE decks SET parent = ?2, position = ?3 WHERE uuid = ?1", params![deck.to_string(), to, to_position])?;
+        }
+        Operation::DeleteDeck { deck } => {
+            let deleted = collection.get_trash().iter().rfind(|d| d.deck.uuid == *deck).ok_or_else(|| eyre!("deck {} not in trash", deck))?;
+            delete_deck_tree(tx, &deleted.deck)?;
+            shift_decks(tx, &parent_column(collection, deleted.parent), deleted.position + 1, -1)?;
+            insert_trash(tx, deleted)?;
+            sync_trash(tx, collection)?;
+        }
+        Operation::RestoreDeck { deck } => {
+            let (parent, position) = collection.deck_position(*deck).ok_or_else(|| eyre!("deck {} not found", deck))?;
+            let parent = parent_column(collection, parent);
+            shift_decks(tx, &parent, position, 1)?;
+            insert_deck_tree(tx, collection.find_deck(*deck).unwrap(), parent, position)?;
+            tx.execute("DELETE FROM trash WHERE deck = ?1", params![deck.to_string()])?;
+        }
+        Operation::Answer { after, review, .. } => {
+            upsert_card(tx, review.note, review.ordinal, after)?;
+            insert_review(tx, review)?;
+        }
+        Operation::UndoAnswer { before, review, .. } => {
+            upsert_card(tx, review.note, review.ordinal, before)?;
+            tx.execute(
+                "DELETE FROM revlog WHERE id = (SELECT MAX(id) FROM revlog WHERE note = ?1 AND ordinal = ?2 AND time = ?3 AND grade = ?4)",
+                params![review.note.to_string(), review.ordinal, review.time, review.grade.to_string()],
+            )?;
+        }
+        Operation::SetSchedule { note, ordinal, after, .. } => upsert_card(tx, *note, *ordinal, after)?,
+        Operation::AddFilteredDeck { position, deck } => {
+            tx.execute("UPDATE filtered_decks SET position = position + 1 WHERE position >= ?1", params![position])?;
+            insert_filtered_deck(tx, *position, deck)?;
+        }
+        Operation::RemoveFilteredDeck { position, deck } => {
+            tx.execute("DELETE FROM filtered_decks WHERE uuid = ?1", params![deck.uuid.to_string()])?;
+            tx.execute("UPDATE filtered_decks SET position = position - 1 WHERE position > ?1", params![position])?;
+        }
+        Operation::EditFilteredDeck { after, .. } => {
+            tx.execute("UPDATE filtered_decks SET data = ?2 WHERE uuid = ?1", params![after.uuid.to_string(), serde_json::to_string(after)?])?;
+        }
+        Operation::Batch(operations) => {
+            for operation in operations {
+                persist_operation(tx, collection, operation)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn shift_notes(tx: &Transaction, deck: Uuid, from_position: usize, by: i64) -> Result<()> {
+    tx.execute("UPDATE notes SET position = position + ?3 WHERE deck = ?1 AND position >= ?2", params![deck.to_string(), from_position, by])?;
+    Ok(())
+}
+
+fn shift_decks(tx: &Transaction, parent: &Option<String>, from_position: usize, by: i64) -> Result<()> {
+    tx.execute("UPDATE decks SET position = position + ?3 WHERE parent IS ?1 AND position >= ?2", params![parent, from_position, by])?;
+    Ok(())
+}
+
+fn insert_deck_tree(tx: &Transaction, deck: &Deck, parent: Option<String>, position: usize) -> Result<()> {
+    tx.execute(
+        "INSERT INTO decks (uuid, parent, position, name) VALUES (?1, ?2, ?3, ?4)",
+        params![deck.uuid.to_string(), parent, position, deck.name],
+    )?;
+    for (position, note) in deck.get_notes().iter().enumerate() {
+        insert_note(tx, deck.uuid, position, note)?;
+    }
+    for (position, subdeck) in deck.get_subdecks().iter().enumerate() {
+        insert_deck_tree(tx, subdeck, Some(deck.uuid.to_string()), position)?;
+    }
+    Ok(())
+}
+
+fn delete_deck_tree(tx: &Transaction, deck: &Deck) -> Result<()> {
+    for note in deck.get_notes() {
+        delete_note(tx, note.uuid)?;
+    }
+    for subdeck in deck.get_subdecks() {
+        delete_deck_tree(tx, subdeck)?;
+    }
+    tx.execute("DELETE FROM decks WHERE uuid = ?1", params![deck.uuid.to_string()])?;
+    Ok(())
+}
+
+fn insert_note(tx: &Transaction, deck: Uuid, position: usize, note: &Note) -> Result<()> {
+    tx.execute(
+        "INSERT INTO notes (uuid, deck, position, front, back, note_type, created, tags) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            note.uuid.to_string(),
+            deck.to_string(),
+            position,
+            note.front,
+            note.back,
+            note.note_type.to_string(),
+            note.created,
+            note.tags.join(" ")
+        ],
+    )?;
+    insert_cards(tx, note)
+}
+
+fn delete_note(tx: &Transaction, note: Uuid) -> Result<()> {
+    tx.execute("DELETE FROM cards WHERE note = ?1", params![note.to_string()])?;
+    tx.execute("DELETE FROM notes WHERE uuid = ?1", params![note.to_string()])?;
+    Ok(())
+}
+
+fn insert_cards(tx: &Transaction, note: &Note) -> Result<()> {
+    for (ordinal, schedule) in note.schedules.iter().enumerate() {
+        upsert_card(tx, note.uuid, ordinal, schedule)?;
+    }
+    Ok(())
+}
+
+fn upsert_card(tx: &Transaction, note: Uuid, ordinal: usize, schedule: &Schedule) -> Result<()> {
+    tx.execute(
+        "INSERT OR REPLACE INTO cards (note, ordinal, state, due, interval, ease, reps, lapses, step, suspended, buried_until, flag)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![
+            note.to_string(),
+            ordinal,
+            schedule.state.to_string(),
+            schedule.due,
+            schedule.interval,
+            schedule.ease,
+            schedule.reps,
+            schedule.lapses,
+            schedule.step,
+            schedule.suspended,
+            schedule.buried_until,
+            schedule.flag.map(|flag| flag.to_string())
+        ],
+    )?;
+    Ok(())
+}
+
+fn insert_review(tx: &Transaction, review: &Review) -> Result<()> {
+    tx.execute(
+        "INSERT INTO revlog (note, ordinal, time, grade, state, interval, last_interval, ease, duration_ms) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            review.note.to_string(),
+            review.ordinal,
+            review.time,
+            review.grade.to_string(),
+            review.state.to_string(),
+            review.interval,
+            review.last_interval,
+            review.ease,
+            review.duration_ms
+        ],
+    )?;
+    Ok(())
+}
+
+fn insert_trash(tx: &Transaction, deleted: &DeletedDeck) -> Result<()> {
+    tx.execute(
+        "INSERT OR REPLACE INTO trash (deck, parent, position, deleted_at, data) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            deleted.deck.uuid.to_string(),
+            deleted.parent.to_string(),
+            deleted.position,
+            deleted.deleted_at,
+            serde_json::to_string(&deleted.deck)?
+        ],
+    )?;
+    Ok(())
+}
+
+fn insert_filtered_deck(tx: &Transaction, position: usize, deck: &FilteredDeck) -> Result<()> {
+    tx.execute(
+        "INSERT INTO filtered_decks (uuid, position, data) VALUES (?1, ?2, ?3)",
+        params![deck.uuid.to_string(), position, serde_json::to_string(deck)?],
+    )?;
+    Ok(())
+}
+
+/// Drops trash rows the collection has purged to stay within its capacity.
+fn sync_trash(tx: &Transaction, collection: &Collection) -> Result<()> {
+    let kept: Vec<String> = collection.get_trash().iter().map(|deleted| deleted.deck.uuid.to_string()).collect();
+    let mut stmt = tx.prepare("SELECT deck FROM trash")?;
+    let stored: Vec<String> = stmt.query_map([], |row| row.get(0))?.collect::<rusqlite::Result<_>>()?;
+    for deck in stored.iter().filter(|deck| !kept.contains(deck)) {
+        tx.execute("DELETE FROM trash WHERE deck = ?1", params![deck])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        card::Card,
+        history::History,
+        schedule::{Flag, Scheduler},
+    };
+
+    type Build<'a> = Box<dyn Fn(&Collection) -> Option<Operation> + 'a>;
+
+    fn sample() -> Collection {
+        let mut collection = Collection::new();
+        let mut spanish = Deck::new("Spanish".to_string());
+        spanish.add_note(Note::new("uno".to_string(), "one".to_string(), NoteType::Basic));
+        spanish.add_note(Note::new("dos".to_string(), "two".to_string(), NoteType::BasicAndReverse));
+        let mut verbs = Deck::new("Verbs".to_string());
+        verbs.add_note(Note::new("ser".to_string(), "to be".to_string(), NoteType::Basic));
+        spanish.add_subdeck(verbs);
+        collection.add_deck(spanish);
+        collection.add_deck(Deck::new("French".to_string()));
+        collection
+    }
+
+    fn snapshot(collection: &Collection) -> String {
+        serde_json::to_string(collection).unwrap()
+    }
+
+    fn card(collection: &Collection, front: &str) -> Card {
+        collection.get_all_decks().iter().flat_map(|deck| deck.get_cards()).find(|card| card.front == front).unwrap()
+    }
+
+    #[test]
+    fn test_save_all_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut collection = sample();
+        let answer = Operation::answer(&card(&collection, "dos"), Grade::Good, 1_700_000_000, 900, &Scheduler::default());
+        answer.apply(&mut collection).unwrap();
+        let french = collection.get_decks()[1].uuid;
+        collection.delete_deck(french);
+
+        let mut storage = Storage::open(&dir.path().join("collection.sqlite")).unwrap();
+        storage.save_all(&collection).unwrap();
+        let loaded = Storage::open(&dir.path().join("collection.sqlite")).unwrap().load().unwrap();
+
+        assert_eq!(snapshot(&loaded), snapshot(&collection));
+        assert_eq!(loaded.find_deck(collection.get_decks()[0].get_subdecks()[0].uuid).unwrap().qualified_name(), "Spanish:Verbs");
+    }
+
+    #[test]
+    fn test_new_database_keeps_its_collection_uuid() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("collection.sqlite");
+
+        let first = Storage::open(&path).unwrap().load().unwrap();
+        let second = Storage::open(&path).unwrap().load().unwrap();
+
+        assert!(first.get_decks().is_empty());
+        assert_eq!(first.uuid, second.uuid);
+    }
+
+    #[test]
+    fn test_incremental_writes_match_collection() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("collection.sqlite");
+        let mut collection = sample();
+        let mut storage = Storage::open(&path).unwrap();
+        storage.save_all(&collection).unwrap();
+        let mut history = History::new();
+        let spanish = collection.get_decks()[0].uuid;
+        let french = collection.get_decks()[1].uuid;
+        let verbs = collection.get_decks()[0].get_subdecks()[0].uuid;
+        let uno = collection.get_decks()[0].get_notes()[0].clone();
+
+        let operations: Vec<Build> = vec![
+            Box::new(|c| Operation::add_note(c, spanish, Note::new("tres".to_string(), "three".to_string(), NoteType::Basic))),
+            Box::new(|c| {
+                Operation::edit_note(
+                    c,
+                    Note { back: "1".to_string(), note_type: NoteType::BasicAndReverse, tags: vec!["numbers".to_string()], ..uno.clone() },
+                )
+            }),
+            Box::new(|c| Operation::move_notes(c, &[uno.uuid], french)),
+            Box::new(|c| Operation::remove_note(c, c.get_decks()[0].get_notes()[0].uuid)),
+            Box::new(|c| Operation::add_deck(c, c.uuid, Deck::new("German".to_string()))),
+            Box::new(|c| Operation::rename_deck(c, verbs, "Verbos".to_string())),
+            Box::new(|c| Operation::move_deck(c, verbs, c.uuid)),
+            Box::new(|c| Operation::move_deck(c, spanish, french)),
+            Box::new(|c| Some(Operation::answer(&card(c, "tres"), Grade::Again, 1_700_000_000, 1200, &Scheduler::default()))),
+            Box::new(|c| Operation::delete_deck(c, french)),
+            Box::new(|c| Operation::restore_deck(c, french)),
+            Box::new(|c| Some(Operation::add_filtered_deck(c, FilteredDeck::new("Cram".to_string(), "dos or tres".to_string())))),
+            Box::new(|c| Some(Operation::add_filtered_deck(c, FilteredDeck::new("All".to_string(), String::new())))),
+            Box::new(|c| {
+                let deck = &c.get_filtered_decks()[0];
+                Operation::edit_filtered_deck(c, FilteredDeck { cards: deck.gather(c, 1_700_000_000).unwrap(), ..deck.clone() })
+            }),
+            Box::new(|c| Operation::remove_filtered_deck(c, c.get_filtered_decks()[0].uuid)),
+            Box::new(|c| {
+                Operation::change_schedules(&[card(c, "tres")], |schedule| Schedule { suspended: true, flag: Some(Flag::Green), ..schedule.clone() })
+            }),
+        ];
+        for build in operations {
+            let operation = build(&collection).unwrap();
+            history.commit(&mut collection, operation.clone()).unwrap();
+            storage.persist(&collection, &operation).unwrap();
+            assert_eq!(snapshot(&Storage::open(&path).unwrap().load().unwrap()), snapshot(&collection), "after {operation:?}");
+        }
+        while let Some(operation) = history.undo(&mut collection).unwrap() {
+            storage.persist(&collection, &operation.inverse()).unwrap();
+            assert_eq!(snapshot(&Storage::open(&path).unwrap().load().unwrap()), snapshot(&collection), "after undoing {operation:?}");
+        }
+    }
+
+    #[test]
+    fn test_upgrades_unversioned_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("collection.sqlite");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(SCHEMA_V1).unwrap();
+        let deck = Uuid::new_v4().to_string();
+        conn.execute("INSERT INTO decks (uuid, parent, position, name) VALUES (?1, NULL, 0, 'Spanish')", params![deck]).unwrap();
+        conn.execute(
+            "INSERT INTO notes (uuid, deck, position, front, back, note_type) VALUES (?1, ?2, 0, 'uno', 'one', 'basic')",
+            params![Uuid::new_v4().to_string(), deck],
+        )
+        .unwrap();
+        drop(conn);
+
+        let storage = Storage::open(&path).unwrap();
+
+        assert_eq!(storage.version().unwrap(), MIGRATIONS.len());
+        let collection = storage.load().unwrap();
+        assert_eq!(collection.get_decks()[0].name, "Spanish");
+        assert_eq!(collection.get_decks()[0].get_notes()[0].created, 0);
+        assert!(collection.get_decks()[0].get_notes()[0].tags.is_empty());
+    }
+
+    /// A database as an older version of tanki left it, with the first `version` migrations applied
+    /// and a Spanish deck whose single note has its single card stored.
+    fn database_at(path: &Path, version: usize) -> (Connection, String) {
+        let conn = Connection::open(path).unwrap();
+        for migration in &MIGRATIONS[..version] {
+            conn.execute_batch(migration).unwrap();
+        }
+        conn.execute_batch(&format!("PRAGMA user_version = {version}")).unwrap();
+        let (deck, note) = (Uuid::new_v4().to_string(), Uuid::new_v4().to_string());
+        conn.execute("INSERT INTO decks (uuid, parent, position, name) VALUES (?1, NULL, 0, 'Spanish')", params![deck]).unwrap();
+        conn.execute(
+            "INSERT INTO notes (uuid, deck, position, front, back, note_type) VALUES (?1, ?2, 0, 'uno', 'one', 'basic')",
+            params![note, deck],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO cards (note, ordinal, state, due, interval, ease, reps, lapses, step) VALUES (?1, 0, 'review', 500, 3, 2300, 4, 1, 0)",
+            params![note],
+        )
+        .unwrap();
+        (conn, note)
+    }
+
+    fn upgrade(path: &Path) -> Collection {
+        let storage = Storage::open(path).unwrap();
+        assert_eq!(storage.version().unwrap(), MIGRATIONS.len());
+        storage.load().unwrap()
+    }
+
+    #[test]
+    fn test_upgrades_v2_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("collection.sqlite");
+        let (conn, note) = database_at(&path, 2);
+        conn.execute("UPDATE notes SET created = 1234 WHERE uuid = ?1", params![note]).unwrap();
+        drop(conn);
+
+        let collection = upgrade(&path);
+
+        let note = &collection.get_decks()[0].get_notes()[0];
+        assert_eq!((note.front.as_str(), note.created), ("uno", 1234));
+        assert!(note.tags.is_empty());
+        assert_eq!((note.schedules[0].interval, note.schedules[0].ease), (3, 2300));
+    }
+
+    #[test]
+    fn test_upgrades_v3_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("collection.sqlite");
+        let (conn, note) = database_at(&path, 3);
+        conn.execute("UPDATE notes SET created = 1234, tags = 'verb spanish::basics' WHERE uuid = ?1", params![note]).unwrap();
+        drop(conn);
+
+        let collection = upgrade(&path);
+
+        let note = &collection.get_decks()[0].get_notes()[0];
+        assert_eq!(note.created, 1234);
+        assert_eq!(note.tags, vec!["verb".to_string(), "spanish::basics".to_string()]);
+        assert!(collection.get_filtered_decks().is_empty());
+    }
+
+    #[test]
+    fn test_upgrades_v4_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("collection.sqlite");
+        let (conn, note) = database_at(&path, 4);
+        conn.execute("UPDATE notes SET tags = 'verb' WHERE uuid = ?1", params![note]).unwrap();
+        let mut filtered = FilteredDeck::new("Cram".to_string(), "tag:verb".to_string());
+        filtered.cards = vec![(Uuid::parse_str(&note).unwrap(), 0)];
+        conn.execute(
+            "INSERT INTO filtered_decks (uuid, position, data) VALUES (?1, 0, ?2)",
+            params![filtered.uuid.to_string(), serde_json::to_string(&filtered).unwrap()],
+        )
+        .unwrap();
+        drop(conn);
+
+        let collection = upgrade(&path);
+
+        assert_eq!(collection.get_filtered_decks(), [filtered]);
+        let schedule = &collection.get_decks()[0].get_notes()[0].schedules[0];
+        assert!(!schedule.suspended && schedule.buried_until == 0 && schedule.flag.is_none());
+    }
+
+    #[test]
+    fn test_upgrades_v5_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("collection.sqlite");
+        let (conn, note) = database_at(&path, 5);
+        conn.execute("UPDATE cards SET suspended = 1, buried_until = 900, flag = 'green' WHERE note = ?1", params![note]).unwrap();
+        drop(conn);
+
+        let collection = upgrade(&path);
+
+        let schedule = &collection.get_decks()[0].get_notes()[0].schedules[0];
+        assert!(schedule.suspended);
+        assert_eq!((schedule.buried_until, schedule.flag), (900, Some(Flag::Green)));
+        assert_eq!((schedule.state, schedule.due, schedule.reps, schedule.lapses), (CardState::Review, 500, 4, 1));
+    }
+
+    #[test]
+    fn test_rejects_newer_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("collection.sqlite");
+        Connection::open(&path).unwrap().execute_batch(&format!("PRAGMA user_version = {}", MIGRATIONS.len() + 1)).unwrap();
+
+        assert!(Storage::open(&path).is_err());
+    }
+
+    #[test]
+    fn test_detects_external_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("collection.sqlite");
+        let mut storage = Storage::open(&path).unwrap();
+        let mut collection = storage.load().unwrap();
+        let mut other = Storage::open(&path).unwrap();
+
+        collection.add_deck(Deck::new("Spanish".to_string()));
+        storage.save_all(&collection).unwrap();
+        assert!(!storage.changed_externally().unwrap());
+
+        other.save_all(&sample()).unwrap();
+        assert!(storage.changed_externally().unwrap());
+        storage.load().unwrap();
+        assert!(!storage.changed_externally().unwrap());
+    }
+
+    #[test]
+    fn test_open_collection_takes_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let sqlite = dir.path().join("collection.sqlite");
+        let json = dir.path().join("collection.json");
+
+        let (storage, _) = open_collection(&sqlite, Some(&json), false).unwrap();
+        assert!(open_collection(&sqlite, Some(&json), false).is_err());
+        assert!(open_collection(&sqlite, Some(&json), true).is_ok());
+        drop(storage);
+    }
+
+    #[test]
+    fn test_migrates_legacy_json_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let json = dir.path().join("collection.json");
+        let sqlite = dir.path().join("collection.sqlite");
+        let collection = sample();
+        fs::write(&json, serde_json::to_string(&collection).unwrap()).unwrap();
+
+        let (storage, migrated) = open_collection(&sqlite, Some(&json), false).unwrap();
+
+        assert_eq!(snapshot(&migrated), snapshot(&collection));
+        assert!(!storage.changed_externally().unwrap());
+        drop(storage);
+        assert!(!json.exists());
+        assert!(dir.path().join("collection.json.migrated").exists());
+        let (_, reopened) = open_collection(&sqlite, Some(&json), false).unwrap();
+        assert_eq!(snapshot(&reopened), snapshot(&collection));
+    }
+
+    #[test]
+    fn test_explicit_collection_ignores_json_next_to_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let json = dir.path().join("collection.json");
+        let sqlite = dir.path().join("collection.sqlite");
+        fs::write(&json, serde_json::to_string(&sample()).unwrap()).unwrap();
+
+        let (_, collection) = open_collection(&sqlite, None, false).unwrap();
+
+        assert!(collection.get_decks().is_empty());
+        assert!(json.exists());
+    }
+
+    #[test]
+    fn test_unparsable_legacy_json_creates_no_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let json = dir.path().join("collection.json");
+        let sqlite = dir.path().join("collection.sqlite");
+        fs::write(&json, "{\"uuid\": ").unwrap();
+
+        assert!(open_collection(&sqlite, Some(&json), false).is_err());
+        assert!(!sqlite.exists());
+
+        let collection = sample();
+        fs::write(&json, serde_json::to_string(&collection).unwrap()).unwrap();
+        let (_, migrated) = open_collection(&sqlite, Some(&json), false).unwrap();
+        assert_eq!(snapshot(&migrated), snapshot(&collection));
+    }
+}
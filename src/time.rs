@@ -0,0 +1,93 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const MINUTE: i64 = 60;
+pub const HOUR: i64 = 60 * MINUTE;
+pub const DAY: i64 = 24 * HOUR;
+
+/// Current unix time in seconds.
+pub fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or_default()
+}
+
+/// Offset of the local time zone from UTC at `time` in seconds, or 0 where it cannot be found.
+#[cfg(unix)]
+pub fn local_offset(time: i64) -> i64 {
+    let time = time as libc::time_t;
+    // SAFETY: `tm` is plain data that localtime_r fills in, and both pointers outlive the call.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    match unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        true => 0,
+        false => tm.tm_gmtoff as i64,
+    }
+}
+
+#[cfg(not(unix))]
+pub fn local_offset(_time: i64) -> i64 {
+    0
+}
+
+/// Where one study day ends and the next begins: at `rollover_hour` in a time zone `offset` seconds
+/// ahead of UTC, so that a late session still counts for the day before, like Anki's next day setting.
+/// Daily limits, burying, streaks and stats all count days this way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Days {
+    offset: i64,
+    rollover_hour: i64,
+}
+
+impl Days {
+    pub const fn new(rollover_hour: i64, offset: i64) -> Self {
+        Days { offset, rollover_hour }
+    }
+
+    /// Days rolling over at `rollover_hour` in the local time zone, with its offset at present.
+    pub fn local(rollover_hour: i64) -> Self {
+        Days::new(rollover_hour, local_offset(now()))
+    }
+
+    /// The study day `time` falls in, counted from the unix epoch.
+    pub fn day(&self, time: i64) -> i64 {
+        self.local_time(time).div_euclid(DAY)
+    }
+
+    /// Start of the study day after the one `time` falls in.
+    pub fn next_day(&self, time: i64) -> i64 {
+        (self.day(time) + 1) * DAY - self.offset + self.rollover_hour * HOUR
+    }
+
+    /// The local hour of the day of `time`, from 0 to 23, regardless of the rollover hour.
+    pub fn hour(&self, time: i64) -> usize {
+        ((time + self.offset).rem_euclid(DAY) / HOUR) as usize
+    }
+
+    /// The day of the week of a study day, from 0 for Monday to 6 for Sunday.
+    pub fn weekday(&self, day: i64) -> usize {
+        // The unix epoch was a Thursday.
+        (day + 3).rem_euclid(7) as usize
+    }
+
+    fn local_time(&self, time: i64) -> i64 {
+        time + self.offset - self.rollover_hour * HOUR
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Midnight UTC, a Tuesday.
+    const MIDNIGHT: i64 = 1_699_920_000;
+
+    #[test]
+    fn test_days_roll_over_at_the_local_hour() {
+        // Four in the morning in UTC-7 is eleven in the morning UTC.
+        let days = Days::new(4, -7 * HOUR);
+
+        assert_eq!(days.day(MIDNIGHT + 10 * HOUR), days.day(MIDNIGHT - 10 * HOUR));
+        assert_eq!(days.day(MIDNIGHT + 11 * HOUR), days.day(MIDNIGHT - 10 * HOUR) + 1);
+        assert_eq!(days.next_day(MIDNIGHT), MIDNIGHT + 11 * HOUR);
+        assert_eq!(days.next_day(MIDNIGHT + 11 * HOUR), MIDNIGHT + DAY + 11 * HOUR);
+        assert_eq!(days.hour(MIDNIGHT + 11 * HOUR), 4);
+        assert_eq!(days.weekday(Days::new(0, 0).day(MIDNIGHT)), 1);
+    }
+}